@@ -1,6 +1,6 @@
 use std::{io, result, fmt};
 
-use crate::{MAGIC_LEN, ColTy, LitTy, CLit, AggOp, BinOp, CmpOp};
+use crate::{MAGIC_LEN, ColTy, LitTy, CLit, LitExt, AggOp, BinOp, CmpOp};
 
 #[derive(Debug)]
 pub struct ParserError<'a> {
@@ -13,9 +13,21 @@ pub struct ParserError<'a> {
 pub enum ParserErrorKind<'a> {
   SyntaxError,
   UnexpectedChar(char),
+  // a string (or date) literal without the closing ', `line` and `col` are where the literal starts
+  UnterminatedString,
   InvalidTypeSize(&'a str),
   InvalidInt(&'a str),
   InvalidFloat(&'a str),
+  // an int literal that doesn't fit i32 (the regex of int literal only accepts digits, so this is the only way to fail)
+  IntLiteralOverflow(&'a str),
+  // the fraction of `percentile_disc(p)` / `percentile_cont(p)` is not in [0, 1]
+  InvalidPercentile(&'a str),
+  // `select top n ... limit m`
+  TopWithLimit,
+  // e.g. `where count(*) > 3`, where is evaluated on each row before aggregation, such filter belongs to having
+  AggInWhere,
+  // the string of `date '...'` is not a valid date, or `current_date - n` is out of the range of date
+  InvalidDate(&'a str),
 }
 
 #[derive(Debug)]
@@ -23,7 +35,16 @@ pub enum Error<'a> {
   ParserErrors(Box<[ParserError<'a>]>),
   InvalidSize { size: usize, expect_multiply_of: usize },
   InvalidMagic([u8; MAGIC_LEN]),
+  // a table in a db of the old layout has 127 cols, which is more than MAX_COL now
+  OldTableColTooMany(String),
+  // the db file is written in another page layout, see `DB_VERSION`
+  UnsupportedDbVersion { found: u16, expected: u16 },
   NoDbInUse,
+  // `alter database old rename to new`, `new` is an existing file, or `old` is open (by any Eval or process)
+  DbExists(&'a str),
+  DbInUse(&'a str),
+  // `Eval::execute` runs exactly one stmt, this is the number of stmts in the sql
+  NotOneStmt(usize),
   TableExhausted,
   ColTooMany(usize),
   // not support table with 0 col
@@ -35,6 +56,8 @@ pub enum Error<'a> {
   DupTable(&'a str),
   DupCol(&'a str),
   DupIndex(&'a str),
+  // `create table table like like` names the copy of index x `table_x`, which is too long or already used
+  LikeIndexName { table: &'a str, like: &'a str },
   // add duplicate constraint on one col in create/alter table
   DupConstraint(&'a str),
   NoSuchTable(&'a str),
@@ -43,6 +66,8 @@ pub enum Error<'a> {
   NoSuchForeign(&'a str),
   NoSuchPrimary(&'a str),
   ForeignOnNotUnique(&'a str),
+  // the not null foreign link on the col forms a cycle of not null foreign links, no record could be inserted into these tables
+  ForeignKeyCycle(&'a str),
   // ModifyCol... : delete/update that actually affects data with a foreign link. so there is a concrete val
   // ModifyTable... : drop table/drop col, even no data with foreign link is affected, it is still rejected
   ModifyTableWithForeignLink(&'a str),
@@ -51,14 +76,28 @@ pub enum Error<'a> {
   InvalidLike { like: &'a str, reason: Box<regex::Error> },
   InvalidLikeTy(ColTy),
   InvalidLikeTy1(LitTy),
+  // `col % m` in where requires int col and m != 0
+  InvalidModTy(ColTy),
+  ModByZero,
+  // `length(col)` in where requires char or varchar col
+  InvalidLenTy(ColTy),
+  // `col in (select ...)` requires the select to have exactly one col
+  InSelectCols(usize),
+  // `col cmp (select ...)` requires the select to have exactly one col and at most one row
+  SubqueryNotScalar { cols: usize, rows: usize },
   // some operation on Varchar is not supported, e.g., create index (thus primary/foreign/unique are not supported, either)
   UnsupportedVarcharOp(&'a str),
   // require them to be exactly the same (including BareTy and size, in order to search each other in index page)
   IncompatibleForeignTy { foreign: ColTy, own: ColTy },
   ColMismatch { l: ColTy, r: ColTy },
   ColLitMismatch { ty: ColTy, val: CLit<'a> },
+  // the number can't be stored in the col without changing its meaning, e.g.: 1e10 into int, or a float overflowing f32 (to inf)
+  ValueOutOfRange { ty: ColTy, val: CLit<'a> },
   // e.g.: insert (1, 2) into (int)
   InsertTooLong { max: usize, actual: usize },
+  // the `row`-th (starting from 1) value row of insert doesn't have the same len as the first row, or is too long (then `expect` is the col num)
+  // all rows are checked before inserting any of them
+  InsertRowLen { row: usize, expect: usize, actual: usize },
   PutNullOnNotNull,
   PutDupOnUnique { col: &'a str, val: CLit<'a> },
   PutNonexistentForeign { col: &'a str, val: CLit<'a> },
@@ -66,16 +105,60 @@ pub enum Error<'a> {
   // this error is mainly for PutDupOnCompositePrimary
   // but for convenience, duplication in add/drop primary also uses this error (so no need to get the dup value)
   PutDupOnPrimary,
+  // the conflict target of `insert ... on conflict (col)` is not a unique col (or a single primary col)
+  ConflictNotUnique(&'a str),
   AmbiguousCol(&'a str),
   // check list always rejects null (because it is meaningless)
   CheckNull(&'a str),
   CheckTooLong(&'a str),
+  // a check over the row is compiled for each insert / update, so it can't run a select
+  SelectInCheck,
+  // the failed cond of a check over the row, and the values of the cols it reads
+  CheckFailed { check: String, vals: Vec<(&'a str, LitExt)> },
+  // the stored checks over the row don't compile any more, e.g., the check page is damaged
+  InvalidRowCheck { check: String, reason: String },
+  // the col is used by a check over the row, so it can't be renamed or dropped
+  ColInCheck(&'a str),
+  // the comment of the col is longer than MAX_COMMENT
+  CommentTooLong(&'a str),
   InvalidAgg { col: ColTy, op: AggOp },
-  // select agg col together with non-agg col
+  // e.g.: select a filter (where a > 1) from t
+  FilterOnNonAgg(&'a str),
+  // select agg col together with non-agg col (that is not in group by)
   MixedSelect,
+  // `order by n` where n is not in 1..=the number of selected cols
+  InvalidOrderPos(u32),
+  // `order by agg` requires the agg in the select list (and both without filter), so does any col when there is such an agg key
+  OrderByNotSelected,
+  // a catalog table (e.g., __tables) is joined with a normal table
+  CatalogJoin(&'a str),
+  // a cte refers to itself in its from list, recursive ctes are not supported
+  RecursiveCte(&'a str),
+  // a cte is joined with a normal table (ctes live in an in-memory db, like catalog tables)
+  CteJoin(&'a str),
+  // the cols of the cte can't be named: the col list doesn't have the same len as the select, or the header has duplicate or too long names
+  CteCols(&'a str),
   IncompatibleBin { op: BinOp, ty: LitTy },
   IncompatibleCmp { op: CmpOp, l: LitTy, r: LitTy },
   IncompatibleLogic(LitTy),
+  // user-defined scalar functions, see `Db::register_function`; a function can't return a string or null
+  NoSuchFunction(&'a str),
+  FunctionArity { name: &'a str, expect: usize, actual: usize },
+  InvalidFunctionRet(&'a str),
+  // the function returns a value of another type than it is registered with
+  FunctionRetMismatch { name: &'a str, expect: LitTy, actual: LitExt },
+  // for a function to report its own failure, with the reason
+  FunctionFailed(String),
+  // a LitExt is null or not a value of `to`, e.g., `i32::try_from(&LitExt::Number(1.5))`
+  LitConvert { lit: LitExt, to: &'static str },
+  // `row` starts from 1 (the header is not counted), `col` is None if the error is not about a single field
+  ImportError { row: u32, col: Option<&'a str>, reason: Box<Error<'a>> },
+  // a Char/Varchar field in the record at (page, slot) is not valid utf-8, the db file may be damaged
+  CorruptString { page: u32, slot: u32 },
+  // the cancel flag of db is set during execution
+  Cancelled,
+  // a select produces more rows than the max rows of db
+  ResultTooLarge(usize),
   IO(io::Error),
 }
 
@@ -10,6 +10,8 @@ pub use crate::{unsafe_helper::*, errors::*, ty::*};
 
 pub const MAGIC_LEN: usize = 18;
 pub const MAGIC: &[u8; MAGIC_LEN] = b"MashPlant-DataBase";
+// the layout of pages in the db file, increase it when the layout changes, so that an older file is rejected instead of misread
+pub const DB_VERSION: u16 = 1;
 pub const LOB_SUFFIX: &str = "lob";
 pub const LOG_MAX_SLOT: usize = 9;
 pub const MAX_PAGE: usize = 1 << (32 - LOG_MAX_SLOT);
@@ -1,12 +1,14 @@
-use std::{fmt, cmp::Ordering, mem, marker::PhantomData};
+use std::{fmt, cmp::Ordering, mem, marker::PhantomData, convert::TryFrom};
 use chrono::NaiveDate;
-use crate::{impossible, varchar, VARCHAR_SLOT_SIZE};
+use crate::{impossible, varchar, VARCHAR_SLOT_SIZE, Error};
 
 #[repr(u8)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize))]
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
 pub enum BareTy { Bool, Int, Float, Date, Char }
 
 #[repr(C)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize))]
 #[derive(Copy, Clone, Eq, PartialEq)]
 pub struct FixTy {
   pub ty: BareTy,
@@ -14,6 +16,7 @@ pub struct FixTy {
 }
 
 #[repr(u8)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize))]
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
 pub enum ColTy { FixTy(FixTy), Varchar(u16) }
 
@@ -48,17 +51,29 @@ impl ColTy {
   }
 }
 
-// `Date` can not be produced by parser, but can be used to pass the result of select
+// `Date` is only produced by parser for `current_date`, and can be used to pass the result of select
 #[derive(Copy, Clone)]
 pub enum Lit<'a> { Null, Bool(bool), Number(f64), Date(NaiveDate), Str(&'a str) }
 
+// Lit is not Hash + Eq (because of f64), so it is converted to this to be the key of group by and distinct
+// all the places that group or dedup values use it, so they agree: null equals null, -0.0 equals 0.0, strings are compared by bytes
+#[derive(Debug, Hash, Eq, PartialEq)]
+pub enum GroupKey<'a> { Null, Bool(bool), Number(u64), Date(NaiveDate), Str(&'a str) }
+
 // the discriminant of Lit
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
 pub enum LitTy { Null, Bool, Number, Date, Str }
 
-impl Lit<'_> {
+impl<'a> Lit<'a> {
   pub fn is_null(&self) -> bool { match self { Lit::Null => true, _ => false, } }
 
+  pub fn group_key(self) -> GroupKey<'a> {
+    match self {
+      Lit::Null => GroupKey::Null, Lit::Bool(x) => GroupKey::Bool(x), Lit::Number(x) => GroupKey::Number((x + 0.0).to_bits()),
+      Lit::Date(x) => GroupKey::Date(x), Lit::Str(x) => GroupKey::Str(x),
+    }
+  }
+
   pub fn ty(&self) -> LitTy {
     use Lit::*;
     match self { Null => LitTy::Null, Bool(_) => LitTy::Bool, Number(_) => LitTy::Number, Date(_) => LitTy::Date, Str(_) => LitTy::Str }
@@ -78,6 +93,7 @@ impl Lit<'_> {
 }
 
 // this can be used for the comparison between non-nan float (in the database we always guarantee float is not-nan)
+// nan and inf are rejected when stored (ValueOutOfRange), and there is no literal for them, so `where x = nan` can't be written
 // why not using partial_cmp + unchecked_unwrap? I've check the output asm, this way is more efficient
 pub fn fcmp<T: PartialOrd>(l: T, r: T) -> Ordering {
   if l < r { Ordering::Less } else if l > r { Ordering::Greater } else { Ordering::Equal }
@@ -97,6 +113,26 @@ impl fmt::Debug for Lit<'_> {
   }
 }
 
+// the user-facing form: null is empty, string is not quoted, date is yyyy-mm-dd
+impl fmt::Display for Lit<'_> {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    use Lit::*;
+    match *self {
+      Null => Ok(()), Bool(x) => write!(f, "{}", x), Number(x) => write!(f, "{}", x),
+      Date(x) => write!(f, "{}", x.format("%Y-%m-%d")), Str(x) => f.write_str(x)
+    }
+  }
+}
+
+// today's date in local time zone, for `current_date`
+pub fn today() -> NaiveDate { chrono::Local::today().naive_local() }
+
+// `days` days after today (before it if negative), for `current_date + n` and `current_date - n`; None if out of the range of date
+pub fn today_offset(days: i32) -> Option<NaiveDate> { today().checked_add_signed(chrono::Duration::days(days as i64)) }
+
+// the format of date literals, e.g. '2020-01-01'
+pub fn parse_date(s: &str) -> chrono::ParseResult<NaiveDate> { NaiveDate::parse_from_str(s, "%Y-%m-%d") }
+
 // C for Compressed: Lit takes 24 bytes of space, which is not efficient enough
 // Lit is used in functions to implement logic, CLit is used in data structures to save space
 #[derive(Copy, Clone)]
@@ -140,19 +176,98 @@ impl fmt::Debug for CLit<'_> {
   fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> { write!(f, "{:?}", self.lit()) }
 }
 
+// a lit is serialized as the json value of it, a date is a "yyyy-mm-dd" string
+#[cfg(feature = "serialize")]
+impl serde::Serialize for CLit<'_> {
+  fn serialize<S: serde::Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+    match self.lit() {
+      Lit::Null => s.serialize_none(), Lit::Bool(x) => s.serialize_bool(x), Lit::Number(x) => s.serialize_f64(x),
+      Lit::Date(x) => s.collect_str(&x.format("%Y-%m-%d")), Lit::Str(x) => s.serialize_str(x),
+    }
+  }
+}
+
+impl fmt::Display for CLit<'_> {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> { write!(f, "{}", self.lit()) }
+}
+
+// Ext for Extended lifetime: an owned Lit, which can be kept after the db (or the sql string) is gone
+#[derive(Debug, Clone, PartialEq)]
+pub enum LitExt { Null, Bool(bool), Number(f64), Date(NaiveDate), Str(String) }
+
+impl LitExt {
+  // borrow it as a Lit, e.g., for `Display`
+  pub fn lit(&self) -> Lit {
+    match self {
+      LitExt::Null => Lit::Null, &LitExt::Bool(x) => Lit::Bool(x), &LitExt::Number(x) => Lit::Number(x),
+      &LitExt::Date(x) => Lit::Date(x), LitExt::Str(x) => Lit::Str(x),
+    }
+  }
+
+  // the same as `Lit::group_key`
+  pub fn group_key(&self) -> GroupKey { self.lit().group_key() }
+}
+
+// typed extraction, e.g., `let age: i32 = (&row[1]).try_into()?`
+// null is an error for `T`, and is None for `Option<T>`
+macro_rules! lit_ext_try_from {
+  ($ty: ty, $name: expr, $pat: pat $(if $guard: expr)? => $val: expr) => {
+    impl<'a> TryFrom<&'a LitExt> for $ty {
+      type Error = Error<'static>;
+      fn try_from(lit: &'a LitExt) -> Result<Self, Self::Error> {
+        match lit { $pat $(if $guard)? => Ok($val), _ => Err(Error::LitConvert { lit: lit.clone(), to: $name }) }
+      }
+    }
+
+    impl<'a> TryFrom<&'a LitExt> for Option<$ty> {
+      type Error = Error<'static>;
+      fn try_from(lit: &'a LitExt) -> Result<Self, Self::Error> {
+        match lit { LitExt::Null => Ok(None), lit => <$ty>::try_from(lit).map(Some) }
+      }
+    }
+  };
+}
+
+lit_ext_try_from!(bool, "bool", &LitExt::Bool(x) => x);
+// an int col's value is always integral and in range
+lit_ext_try_from!(i32, "i32", &LitExt::Number(x) if x as i32 as f64 == x => x as i32);
+lit_ext_try_from!(f64, "f64", &LitExt::Number(x) => x);
+lit_ext_try_from!(NaiveDate, "date", &LitExt::Date(x) => x);
+lit_ext_try_from!(&'a str, "str", LitExt::Str(x) => x.as_str());
+
+impl From<Lit<'_>> for LitExt {
+  fn from(lit: Lit) -> Self {
+    match lit {
+      Lit::Null => LitExt::Null, Lit::Bool(x) => LitExt::Bool(x), Lit::Number(x) => LitExt::Number(x),
+      Lit::Date(x) => LitExt::Date(x), Lit::Str(x) => LitExt::Str(x.to_owned()),
+    }
+  }
+}
+
 // Agg, Sum is available for Int, Float
 // Min, Max, Count is available for all
 // CountAll is special, it comes from count(*), so it doesn't have ColRef
+// the percentiles store their fraction by its bits, so that AggOp can still be Eq
+#[cfg_attr(feature = "serialize", derive(serde::Serialize))]
 #[derive(Debug, Eq, PartialEq, Copy, Clone)]
-pub enum AggOp { Avg, Sum, Min, Max, Count, CountAll }
+pub enum AggOp { Avg, Sum, Min, Max, Count, CountAll, PercentileDisc(u64), PercentileCont(u64) }
 
 impl AggOp {
   pub fn name(self) -> &'static str {
     use AggOp::*;
-    match self { Avg => "avg", Sum => "sum", Min => "min", Max => "max", Count | CountAll => "count" }
+    match self {
+      Avg => "avg", Sum => "sum", Min => "min", Max => "max", Count | CountAll => "count",
+      PercentileDisc(_) => "percentile_disc", PercentileCont(_) => "percentile_cont",
+    }
+  }
+
+  // the fraction of a percentile, in [0, 1]
+  pub fn p(self) -> Option<f64> {
+    match self { AggOp::PercentileDisc(p) | AggOp::PercentileCont(p) => Some(f64::from_bits(p)), _ => None }
   }
 }
 
+#[cfg_attr(feature = "serialize", derive(serde::Serialize))]
 #[derive(Debug, Copy, Clone)]
 pub enum BinOp { Add, Sub, Mul, Div, Mod }
 
@@ -163,6 +278,7 @@ impl BinOp {
   }
 }
 
+#[cfg_attr(feature = "serialize", derive(serde::Serialize))]
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
 pub enum CmpOp { Lt, Le, Ge, Gt, Eq, Ne }
 
@@ -177,4 +293,29 @@ impl CmpOp {
     use CmpOp::*;
     match self { Lt => Gt, Le => Ge, Ge => Le, Gt => Lt, Eq => Eq, Ne => Ne }
   }
-}
\ No newline at end of file
+}
+// how strings are compared in predicates (including like and `in`), joins and order by; index is always in binary order
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Collation { Binary, NoCase }
+
+impl Default for Collation {
+  fn default() -> Self { Collation::Binary }
+}
+
+impl Collation {
+  pub fn from_name(name: &str) -> Option<Collation> {
+    match name { "binary" => Some(Collation::Binary), "nocase" => Some(Collation::NoCase), _ => None }
+  }
+
+  pub fn cmp(self, l: &str, r: &str) -> Ordering {
+    match self {
+      Collation::Binary => l.cmp(r),
+      Collation::NoCase => l.chars().flat_map(char::to_lowercase).cmp(r.chars().flat_map(char::to_lowercase)),
+    }
+  }
+
+  // strings equal under this collation have the same key
+  pub fn key(self, s: &str) -> String {
+    match self { Collation::Binary => s.to_owned(), Collation::NoCase => s.chars().flat_map(char::to_lowercase).collect() }
+  }
+}
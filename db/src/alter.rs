@@ -1,5 +1,6 @@
 use common::{*, Error::*};
 use physics::*;
+use syntax::{Lexer, TokenKind};
 use crate::{Db, is_null};
 
 impl Db {
@@ -62,11 +63,42 @@ impl Db {
     unsafe {
       let tp = self.get_tp(old)?.1;
       if new.len() > MAX_TABLE_NAME { return Err(TableNameTooLong(new)); }
+      if old != new && self.get_tp(new).is_ok() { return Err(DupTable(new)); }
       tp.name_len = new.len() as u8;
       tp.name.as_mut_ptr().copy_from_nonoverlapping(new.as_ptr(), new.len());
       Ok(())
     }
   }
+
+  // foreign links, indexes and constraints all refer to a col by its position, so only the name needs to change
+  pub fn rename_col<'a>(&mut self, table: &'a str, old: &'a str, new: &'a str) -> Result<'a, ()> {
+    unsafe {
+      let tp = self.get_tp(table)?.1;
+      let ci = tp.get_ci(old)?;
+      if new.len() > MAX_COL_NAME { return Err(ColNameTooLong(new)); }
+      if old != new && tp.get_ci(new).is_ok() { return Err(DupCol(new)); }
+      if old != new && self.row_check_uses(tp, old) { return Err(ColInCheck(old)); }
+      ci.name_len = new.len() as u8;
+      ci.name.as_mut_ptr().copy_from_nonoverlapping(new.as_ptr(), new.len());
+      Ok(())
+    }
+  }
+
+  // the checks over the row refer to cols by name (see `TablePage::check`), so renaming or dropping such col is rejected
+  // any identifier with the same name counts, it is possible that it is actually a table name, but it doesn't matter
+  pub unsafe fn row_check_uses(&self, tp: &TablePage, col: &str) -> bool {
+    if tp.check == !0 { return false; }
+    let cp = self.pr().get_page::<CheckPage>(tp.check);
+    let mut lexer = Lexer::new(cp.data.get_unchecked(..cp.count as usize));
+    loop {
+      let t = lexer.next();
+      match t.ty {
+        TokenKind::Id1 => if t.piece == col.as_bytes() { return true; }
+        TokenKind::_Eof | TokenKind::_Err => return false,
+        _ => {}
+      }
+    }
+  }
 }
 
 impl Db {
@@ -84,6 +116,7 @@ impl Db {
             if ci.index != !0 { self.dealloc_index(ci.index); }
             if ci.check != !0 { self.dealloc_page(ci.check >> 1); }
           }
+          if tp.check != !0 { self.dealloc_page(tp.check); }
           if tp.cols().iter().any(|ci| ci.ty.is_varchar()) {
             for (data, _) in self.record_iter(tp) {
               for (ci_id, ci) in tp.cols().iter().enumerate() {
@@ -1,5 +1,6 @@
-use std::{fs::{File, OpenOptions}, path::Path};
+use std::{fs::{self, File, OpenOptions}, path::Path, str, slice, iter, cell::Cell, collections::HashSet, sync::{Arc, atomic::{AtomicBool, Ordering}}, time::{SystemTime, UNIX_EPOCH}};
 use memmap::{MmapOptions, MmapMut};
+use fs2::FileExt;
 use unchecked_unwrap::UncheckedUnwrap;
 use chrono::NaiveDate;
 
@@ -7,13 +8,43 @@ use physics::*;
 use common::{*, Error::*, BareTy::*};
 use syntax::ast::*;
 
+// the capacity of an in-memory db, 1G for pages and 1G for lob
+pub const MAX_MEMORY_PAGE: usize = 1 << 17;
+pub const MAX_MEMORY_LOB_SLOT: usize = 1 << 25;
+
 pub struct Db {
   pub(crate) mmap: MmapMut,
-  pub(crate) file: File,
+  // None for an in-memory db
+  pub(crate) file: Option<File>,
   pub(crate) lob_mmap: MmapMut,
-  pub(crate) lob_file: File,
+  pub(crate) lob_file: Option<File>,
   pub(crate) pages: u32,
   pub(crate) lob_slots: u32,
+  // long-running loops check it periodically and stop with Err(Cancelled) if it is set
+  pub(crate) cancel: Arc<AtomicBool>,
+  // the maximum number of rows that a select can produce (during join), 0 for unlimited
+  pub(crate) max_rows: usize,
+  // how strings are compared, see `Collation`
+  pub(crate) collation: Collation,
+  // whether an empty string for a non-string col is null on insert and import, see `Db::set_empty_null`
+  pub(crate) empty_null: bool,
+  // whether an int is accepted as a bool (0 is false, others are true) on insert, import and comparison, see `Db::set_int_bool`
+  pub(crate) int_bool: bool,
+  // whether a string too long for a char or varchar col is cut to fit on insert, import and update, see `Db::set_truncate_str`
+  pub(crate) truncate_str: bool,
+  // the user-defined scalar functions, by name
+  pub(crate) functions: HashMap<String, ScalarFn>,
+  // the seed of `random()` in each select and update, None for a different one each time, see `Db::set_seed`
+  pub(crate) seed: Option<u64>,
+  // the state of `random()`, it is only changed by reading it, so it doesn't need `&mut self`
+  pub(crate) random: Cell<u64>,
+}
+
+// a function registered by `Db::register_function`
+pub struct ScalarFn {
+  pub arity: usize,
+  pub ret: LitTy,
+  pub f: Box<dyn Fn(&[LitExt]) -> Result<'static, LitExt> + Send + Sync>,
 }
 
 impl Db {
@@ -21,6 +52,7 @@ impl Db {
     unsafe {
       let opt = OpenOptions::new().read(true).write(true).create(true).append(true).clone();
       let file = opt.open(path.as_ref())?;
+      file.try_lock_shared()?;
       file.set_len(PAGE_SIZE as u64)?;
       // this is 64G, the maximum capacity of this db; mmap will not allocate memory unless accessed
       let mut mmap = MmapOptions::new().len(PAGE_SIZE * MAX_PAGE).map_mut(&file)?;
@@ -30,7 +62,7 @@ impl Db {
       // lob file can use all the 32 bits addr space, each addr for 32 bytes, in all 128G
       let mut lob_mmap = MmapOptions::new().len(!0u32 as usize * LOB_SLOT_SIZE).map_mut(&lob_file)?;
       (lob_mmap.as_mut_ptr() as *mut FreeLobSlot).r().init_nil();
-      Ok(Db { mmap, file, lob_mmap, lob_file, pages: 1, lob_slots: 1 })
+      Ok(Db { mmap, file: Some(file), lob_mmap, lob_file: Some(lob_file), pages: 1, lob_slots: 1, cancel: Arc::default(), max_rows: 0, collation: Collation::Binary, empty_null: false, int_bool: false, truncate_str: false, functions: HashMap::new(), seed: None, random: Cell::new(0) })
     }
   }
 
@@ -38,25 +70,166 @@ impl Db {
     unsafe {
       let opt = OpenOptions::new().read(true).write(true).append(true).clone();
       let file = opt.open(path.as_ref())?;
+      // held as long as the db is open, see `Db::rename`
+      file.try_lock_shared()?;
       let size = file.metadata()?.len() as usize;
       if size == 0 || size % PAGE_SIZE != 0 { return Err(InvalidSize { size, expect_multiply_of: PAGE_SIZE }); }
-      let mmap = MmapOptions::new().len(PAGE_SIZE * MAX_PAGE).map_mut(&file)?;
-      let dp = &*(mmap.as_ptr() as *const DbPage);
+      let mut mmap = MmapOptions::new().len(PAGE_SIZE * MAX_PAGE).map_mut(&file)?;
+      let dp = (mmap.as_mut_ptr() as *mut DbPage).r();
       if &dp.magic != MAGIC { return Err(InvalidMagic(dp.magic)); }
+      let version = if dp.version == 0 { 1 } else { dp.version };
+      if version != DB_VERSION { return Err(UnsupportedDbVersion { found: version, expected: DB_VERSION }); }
+      if !dp.flags.contains(DbFlags::ROW_CHECK) { upgrade_row_check(mmap.as_mut_ptr(), dp)?; }
       let lob_file = opt.open(path.as_ref().with_extension(LOB_SUFFIX))?;
       let lob_size = lob_file.metadata()?.len() as usize;
       if lob_size == 0 || lob_size % LOB_SLOT_SIZE != 0 { return Err(InvalidSize { size: lob_size, expect_multiply_of: LOB_SLOT_SIZE }); }
       let lob_mmap = MmapOptions::new().len(!0u32 as usize * LOB_SLOT_SIZE).map_mut(&lob_file)?;
-      Ok(Db { mmap, file, lob_file, lob_mmap, pages: (size / PAGE_SIZE) as u32, lob_slots: (lob_size / LOB_SLOT_SIZE) as u32 })
+      Ok(Db { mmap, file: Some(file), lob_file: Some(lob_file), lob_mmap, pages: (size / PAGE_SIZE) as u32, lob_slots: (lob_size / LOB_SLOT_SIZE) as u32, cancel: Arc::default(), max_rows: 0, collation: Collation::Binary, empty_null: false, int_bool: false, truncate_str: false, functions: HashMap::new(), seed: None, random: Cell::new(0) })
+    }
+  }
+
+  // the page layout is the same as a file db, only that pages are backed by anonymous memory, and are lost when the db is dropped
+  // like the file db, memory is not committed unless accessed
+  pub fn open_memory<'a>() -> Result<'a, Db> {
+    unsafe {
+      let mut mmap = MmapOptions::new().len(PAGE_SIZE * MAX_MEMORY_PAGE).map_anon()?;
+      (mmap.as_mut_ptr() as *mut DbPage).r().init();
+      let mut lob_mmap = MmapOptions::new().len(LOB_SLOT_SIZE * MAX_MEMORY_LOB_SLOT).map_anon()?;
+      (lob_mmap.as_mut_ptr() as *mut FreeLobSlot).r().init_nil();
+      Ok(Db { mmap, file: None, lob_mmap, lob_file: None, pages: 1, lob_slots: 1, cancel: Arc::default(), max_rows: 0, collation: Collation::Binary, empty_null: false, int_bool: false, truncate_str: false, functions: HashMap::new(), seed: None, random: Cell::new(0) })
     }
   }
+
+  // rename the files of the db at `old` (the page file and the lob file), nothing inside the db refers to its own path
+  // every open db holds a shared lock on its page file, so the exclusive lock here fails if it is open anywhere, in this process or not
+  pub fn rename<'a>(old: &'a str, new: &'a str) -> Result<'a, ()> {
+    let (old1, new1) = (Path::new(old), Path::new(new));
+    if new1.exists() || new1.with_extension(LOB_SUFFIX).exists() { return Err(DbExists(new)); }
+    Db::open(old1)?; // so that a file which is not a db is not renamed
+    let file = File::open(old1)?;
+    if file.try_lock_exclusive().is_err() { return Err(DbInUse(old)); }
+    fs::rename(old1, new1)?;
+    // rename the page file back, so the db is never split across two names
+    if let Err(e) = fs::rename(old1.with_extension(LOB_SUFFIX), new1.with_extension(LOB_SUFFIX)) {
+      fs::rename(new1, old1)?;
+      return Err(IO(e));
+    }
+    Ok(())
+  }
+}
+
+// a db created before `DbFlags::ROW_CHECK` used the slot of `TablePage::check` as the 127th col
+// tables that really have 127 cols can't be kept, otherwise the slot is cleared to mean no check
+unsafe fn upgrade_row_check<'a>(base: *mut u8, dp: &mut DbPage) -> Result<'a, ()> {
+  let tp_of = |id: u32| (base.add(id as usize * PAGE_SIZE) as *mut TablePage).r();
+  if let Some(&id) = dp.tables().iter().find(|&&id| tp_of(id).col_num as usize > MAX_COL) {
+    return Err(OldTableColTooMany(tp_of(id).name().to_owned()));
+  }
+  for &id in dp.tables() {
+    let tp = tp_of(id);
+    (tp.check = !0, tp.check_int_bool = false, tp._rsv = [0; 59]);
+  }
+  dp.flags.insert(DbFlags::ROW_CHECK);
+  Ok(())
+}
+
+impl Db {
+  // write the dirty pages and lob slots to the file and wait for it, instead of leaving it to the os
+  // it is a no-op for an in-memory db, and can be called any number of times
+  pub fn flush<'a>(&self) -> Result<'a, ()> {
+    if self.file.is_some() {
+      self.mmap.flush_range(0, self.pages as usize * PAGE_SIZE)?;
+      self.lob_mmap.flush_range(0, self.lob_slots as usize * LOB_SLOT_SIZE)?;
+    }
+    Ok(())
+  }
+
+  // the flag is not cleared by db, the one who sets it should clear it before executing the next stmt
+  pub fn set_cancel_flag(&mut self, cancel: Arc<AtomicBool>) { self.cancel = cancel; }
+
+  pub fn check_cancel<'a>(&self) -> Result<'a, ()> {
+    if self.cancel.load(Ordering::Relaxed) { Err(Cancelled) } else { Ok(()) }
+  }
+
+  // it is a protective ceiling against e.g. an unintended huge cross join, unlike `limit`, exceeding it is an error
+  pub fn set_max_rows(&mut self, max_rows: usize) { self.max_rows = max_rows; }
+
+  pub fn max_rows(&self) -> usize { self.max_rows }
+
+  pub fn check_rows<'a>(&self, rows: usize) -> Result<'a, ()> {
+    if self.max_rows != 0 && rows > self.max_rows { Err(ResultTooLarge(self.max_rows)) } else { Ok(()) }
+  }
+
+  pub fn set_collation(&mut self, collation: Collation) { self.collation = collation; }
+
+  pub fn collation(&self) -> Collation { self.collation }
+
+  // off by default; when on, inserting or importing '' into a bool, int, float or date col gives null (an error if the col is not null)
+  // '' in a char or varchar col is always an empty string
+  pub fn set_empty_null(&mut self, empty_null: bool) { self.empty_null = empty_null; }
+
+  pub fn empty_null(&self) -> bool { self.empty_null }
+
+  // off by default; when on, a number inserted or imported into a bool col, or compared with it in where, is false for 0 and true otherwise
+  pub fn set_int_bool(&mut self, int_bool: bool) { self.int_bool = int_bool; }
+
+  pub fn int_bool(&self) -> bool { self.int_bool }
+
+  // off by default, so a string too long for its col is an error; when on, it is cut to the longest prefix that fits (never in the middle of a char)
+  pub fn set_truncate_str(&mut self, truncate_str: bool) { self.truncate_str = truncate_str; }
+
+  pub fn truncate_str(&self) -> bool { self.truncate_str }
+
+  // `name(args...)` in the expressions of select, order by, group by and update set calls `f`, a function registered with the same name is replaced
+  // the number of args is checked against `arity` before running the stmt; if any arg is null, the result is null without calling `f`
+  // `ret` is the type of the result, which can also be null; it can't be a string, because expression values don't own strings
+  // an error of `f` (e.g., `FunctionFailed`) stops the stmt, so does a result of another type (`FunctionRetMismatch`)
+  // functions belong to this handle, they are not saved in the db file
+  pub fn register_function<'a>(&mut self, name: &'a str, arity: usize, ret: LitTy, f: impl Fn(&[LitExt]) -> Result<'static, LitExt> + Send + Sync + 'static) -> Result<'a, ()> {
+    match ret { LitTy::Bool | LitTy::Number | LitTy::Date => {} _ => return Err(InvalidFunctionRet(name)) }
+    self.functions.insert(name.to_owned(), ScalarFn { arity, ret, f: box f });
+    Ok(())
+  }
+
+  pub fn function(&self, name: &str) -> Option<&ScalarFn> { self.functions.get(name) }
+
+  // None by default, so `random()` differs between runs of the same stmt; with a seed, each select or update sees the same values
+  pub fn set_seed(&mut self, seed: Option<u64>) { self.seed = seed; }
+
+  pub fn seed(&self) -> Option<u64> { self.seed }
+
+  // restart `random()` from the seed (or the current time if there is none), called at the start of each select and update
+  pub fn reset_random(&self) {
+    let seed = self.seed.unwrap_or_else(|| SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_nanos() as u64).unwrap_or(0));
+    self.random.set(seed);
+  }
+
+  // the next value of `random()` in [0, 1), by splitmix64
+  pub fn random(&self) -> f64 {
+    let x = self.random.get().wrapping_add(0x9e3779b97f4a7c15);
+    self.random.set(x);
+    let z = (x ^ (x >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    let z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    ((z ^ (z >> 31)) >> 11) as f64 / (1u64 << 53) as f64
+  }
+
+  // strings in index are in binary order, which can't be used to search or order under other collations
+  pub fn index_usable(&self, ci: &ColInfo) -> bool {
+    match ci.ty { char!() | varchar!() => self.collation == Collation::Binary, _ => true }
+  }
 }
 
 impl Db {
+  // `v as i32` or `v as f32` can't hold `v`, it may be out of i32's range, or become inf (or is nan)
+  fn out_of_range(ty: BareTy, v: f64) -> bool {
+    match ty { Int => !(v >= i32::min_value() as f64 && v <= i32::max_value() as f64), Float => !(v as f32).is_finite(), _ => false }
+  }
+
   // like `lit2ptr`, but only do type check
   pub fn lit2ptr_ck(ty: FixTy, val: CLit) -> Result<()> {
     match (ty.ty, val.lit()) {
       (Bool, Lit::Bool(_)) => Ok(()),
+      (Int, Lit::Number(v)) | (Float, Lit::Number(v)) if Db::out_of_range(ty.ty, v) => Err(ValueOutOfRange { ty: ColTy::FixTy(ty), val }),
       (Int, Lit::Number(_)) => Ok(()),
       (Float, Lit::Number(_)) => Ok(()),
       (Date, Lit::Str(v)) => (crate::date(v)?, Ok(())).1,
@@ -81,10 +254,12 @@ impl Db {
   pub unsafe fn lit2ptr<'a>(&mut self, ptr: *mut u8, ty: FixTy, val: CLit<'a>) -> Result<'a, ()> {
     Ok(match (ty.ty, val.lit()) {
       (Bool, Lit::Bool(v)) => *(ptr as *mut bool) = v,
+      (Int, Lit::Number(v)) | (Float, Lit::Number(v)) if Db::out_of_range(ty.ty, v) => return Err(ValueOutOfRange { ty: ColTy::FixTy(ty), val }),
       (Int, Lit::Number(v)) => *(ptr as *mut i32) = v as i32,
-      (Float, Lit::Number(v)) => *(ptr as *mut f32) = v as f32,
+      // -0.0 is stored as 0.0, so that they are also the same in hash and group by (they are already equal in comparison)
+      (Float, Lit::Number(v)) => *(ptr as *mut f32) = v as f32 + 0.0,
       (Date, Lit::Str(v)) => *(ptr as *mut NaiveDate) = crate::date(v)?,
-      (Date, Lit::Date(v)) => *(ptr as *mut NaiveDate) = v, // it is not likely to enter this case, because parser cannot produce Date
+      (Date, Lit::Date(v)) => *(ptr as *mut NaiveDate) = v, // from `current_date` or the result of select
       (Char, Lit::Str(v)) if v.len() <= ty.size as usize => {
         *ptr = v.len() as u8;
         ptr.add(1).copy_from_nonoverlapping(v.as_ptr(), v.len());
@@ -114,7 +289,7 @@ impl Db {
 
   // input the whole data slot, result may be null
   pub unsafe fn data2lit<'a>(&self, data: *const u8, ci_id: u32, ci: &ColInfo) -> CLit<'a> {
-    if bsget(data as *const u32, ci_id as usize) { return CLit::new(Lit::Null); };
+    if crate::is_null(data, ci_id) { return CLit::new(Lit::Null); };
     self.ptr2lit(data.add(ci.off as usize), ci.ty)
   }
 
@@ -139,6 +314,30 @@ impl Db {
     let v = (ptr as *const VarcharSlot).r();
     self.dealloc_lob(v.lob_id, v.cap as u32);
   }
+
+  // `ptr2lit` and the predicates treat Char/Varchar bytes as utf-8 without checking, which is UB if the file is damaged
+  // call this before reading the record to reject it instead; it is skipped in release mode unless feature "check-utf8" is enabled
+  pub unsafe fn check_record<'a>(&self, tp: &TablePage, data: *const u8, rid: Rid) -> Result<'a, ()> {
+    if cfg!(any(debug_assertions, feature = "check-utf8")) {
+      for (ci_id, ci) in tp.cols().iter().enumerate() {
+        if bsget(data as *const u32, ci_id) { continue; }
+        let ptr = data.add(ci.off as usize);
+        let (s, len) = match ci.ty {
+          char!(size) if *ptr <= size => (ptr.add(1), *ptr as usize),
+          varchar!() => {
+            let v = (ptr as *const VarcharSlot).r();
+            (self.pr().get_lob(v.lob_id) as *const u8, v.len as usize)
+          }
+          char!() => return Err(CorruptString { page: rid.page(), slot: rid.slot() }),
+          _ => continue,
+        };
+        if str::from_utf8(slice::from_raw_parts(s, len)).is_err() {
+          return Err(CorruptString { page: rid.page(), slot: rid.slot() });
+        }
+      }
+    }
+    Ok(())
+  }
 }
 
 impl Db {
@@ -192,19 +391,40 @@ impl Db {
             let cd = c.cols.get_unchecked(idx);
             if cd.ty.is_varchar() { return Err(UnsupportedVarcharOp(col)); }
             let sz = cd.ty.size() as usize;
-            // default value will use one slot in check page
-            if sz * (check.len() + (cd.dft.is_some() as usize)) > MAX_CHECK_BYTES { return Err(CheckTooLong(col)); }
+            // default value will use one slot in check page, and comment is also stored in it
+            if sz * (check.len() + (if let Some(Dft::Lit(_)) = cd.dft { 1 } else { 0 })) + cd.comment.map(|x| x.len()).unwrap_or(0) > MAX_CHECK_BYTES { return Err(CheckTooLong(col)); }
             for &c in check {
               if c.is_null() { return Err(CheckNull(col)); } else { Db::lit2ptr_ck(cd.ty.fix_ty(), c)?; }
             }
           }
+          // only the cols are validated here, the conds are compiled (and type checked) by `query::create_table`
+          ColCons::RowCheck(_, conds) => for cond in conds {
+            match cond { Cond::In(..) | Cond::CmpSelect(..) => return Err(SelectInCheck), _ => {} }
+            for col in iter::once(cond.lhs_col()).chain(cond.rhs_col()) {
+              if let Some(table) = col.table { if table != c.table { return Err(NoSuchTable(table)); } }
+              let idx = if let Some(x) = cols.get_full(&col.col) { x.0 } else { return Err(NoSuchCol(col.col)); };
+              // varchar fields are written after all checks in insert, see `InsertCtx::insert`
+              if c.cols.get_unchecked(idx).ty.is_varchar() { return Err(UnsupportedVarcharOp(col.col)); }
+            }
+          }
         }
       }
+      // all row checks of the table are stored as one cond list
+      let row_check = c.cons.iter().filter_map(|x| if let ColCons::RowCheck(text, _) = x { Some(*text) } else { None }).collect::<Vec<_>>().join(" and ");
+      if row_check.len() > MAX_CHECK_BYTES { return Err(CheckTooLong(c.table)); }
       for cd in &c.cols {
-        if let Some(dft) = cd.dft {
-          if cd.ty.is_varchar() { return Err(UnsupportedVarcharOp(cd.col)); }
-          // you can set default = null to a notnull col, such insertion will be rejected though
-          if !dft.is_null() { Db::lit2ptr_ck(cd.ty.fix_ty(), dft)?; }
+        if cd.comment.map(|x| x.len()).unwrap_or(0) > MAX_COMMENT { return Err(CommentTooLong(cd.col)); }
+        match cd.dft {
+          Some(Dft::Lit(dft)) => {
+            if cd.ty.is_varchar() { return Err(UnsupportedVarcharOp(cd.col)); }
+            // you can set default = null to a notnull col, such insertion will be rejected though
+            if !dft.is_null() { Db::lit2ptr_ck(cd.ty.fix_ty(), dft)?; }
+          }
+          Some(Dft::CurrentDate) => {
+            if cd.ty.is_varchar() { return Err(UnsupportedVarcharOp(cd.col)); }
+            Db::lit2ptr_ck(cd.ty.fix_ty(), CLit::new(Lit::Date(today())))?;
+          }
+          None => {}
         }
       }
 
@@ -254,10 +474,19 @@ impl Db {
               self.lit2ptr(cp.data.as_mut_ptr().add(idx * sz), ci.ty.fix_ty(), c).unchecked_unwrap();
             }
           }
+          ColCons::RowCheck(..) => {}
         }
       }
+      if !row_check.is_empty() {
+        let (id, cp) = self.alloc_page::<CheckPage>();
+        tp.check = id;
+        cp.count = row_check.len() as u16;
+        cp.data.as_mut_ptr().copy_from_nonoverlapping(row_check.as_ptr(), row_check.len());
+      }
       for (idx, col) in c.cols.iter().enumerate() {
-        if let Some(dft) = col.dft {
+        if let Some(Dft::CurrentDate) = col.dft {
+          tp.cols.get_unchecked_mut(idx).flags.set(ColFlags::DFT_CURRENT_DATE, true);
+        } else if let Some(Dft::Lit(dft)) = col.dft {
           if !dft.is_null() {
             let ci = tp.cols.get_unchecked_mut(idx);
             let cp = if ci.check == !0 {
@@ -269,6 +498,10 @@ impl Db {
             self.lit2ptr(cp.data.as_mut_ptr().add(cp.count as usize * ci.ty.size() as usize), ci.ty.fix_ty(), dft).unchecked_unwrap();
           }
         }
+        if let Some(comment) = col.comment {
+          let ci = tp.cols.get_unchecked_mut(idx);
+          self.set_comment(ci, comment);
+        }
       }
 
       *dp.tables.get_unchecked_mut(dp.table_num as usize) = id;
@@ -284,9 +517,39 @@ impl Db {
       self.get_page::<TablePage>(tp_id1).cols().iter().enumerate().filter_map(move |(ci_id1, ci1)|
         if ci1.f_table == tp_id { Some((tp_id1, ci_id1 as u8, ci1.f_col)) } else { None }))
   }
+
+  // whether `to` is reachable from `from` by not null foreign links (`from` itself is always reachable)
+  // adding a not null foreign link from `to` to `from` makes a cycle if so
+  pub unsafe fn not_null_foreign_reach(&mut self, from: u32, to: u32) -> bool {
+    let (mut stack, mut vis) = (vec![from], HashSet::new());
+    vis.insert(from);
+    while let Some(tp_id) = stack.pop() {
+      if tp_id == to { return true; }
+      for ci in self.get_page::<TablePage>(tp_id).cols() {
+        if ci.f_table != !0 && ci.flags.intersects(ColFlags::NOTNULL1) && vis.insert(ci.f_table) { stack.push(ci.f_table); }
+      }
+    }
+    false
+  }
 }
 
 impl Db {
+  // store `comment` in the check page of `ci`, allocate an empty check page if there is none
+  pub unsafe fn set_comment(&mut self, ci: &mut ColInfo, comment: &str) {
+    let cp = if ci.check == !0 {
+      let (id, cp) = self.alloc_page::<CheckPage>();
+      ci.check = id << 1;
+      (cp.count = 0, cp).1
+    } else { self.get_page::<CheckPage>(ci.check >> 1) };
+    cp.set_comment(comment);
+    ci.flags.set(ColFlags::COMMENT, true);
+  }
+
+  // the comment of `ci`, None if it has no comment
+  pub unsafe fn comment<'a>(&self, ci: &ColInfo) -> Option<&'a str> {
+    if ci.flags.contains(ColFlags::COMMENT) { Some(self.pr().get_page::<CheckPage>(ci.check >> 1).comment()) } else { None }
+  }
+
   pub unsafe fn get_page<'a, P>(&mut self, page: u32) -> &'a mut P {
     debug_assert!(page < self.pages);
     (self.mmap.get_unchecked_mut(page as usize * PAGE_SIZE).p() as *mut P).r()
@@ -301,7 +564,9 @@ impl Db {
       dp.first_free = *self.get_page(free); // [0] stores next free(or none)
       free
     } else {
-      self.file.set_len((self.pages as u64 + 1) * PAGE_SIZE as u64).expect("Failed to allocate page. The database may already be in an invalid state.");
+      if let Some(file) = &self.file {
+        file.set_len((self.pages as u64 + 1) * PAGE_SIZE as u64).expect("Failed to allocate page. The database may already be in an invalid state.");
+      } else { assert!((self.pages as usize) < MAX_MEMORY_PAGE, "Failed to allocate page. The in-memory database is full."); }
       (self.pages, self.pages += 1).0
     };
     (free, self.get_page(free))
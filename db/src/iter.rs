@@ -6,6 +6,23 @@ impl Db {
   pub unsafe fn record_iter<'a>(&mut self, tp: &TablePage) -> RecordIter<'a> {
     RecordIter { db: self.pr(), page: tp.first, slot: 0, size: tp.size, cap: tp.cap }
   }
+
+  // a safe `record_iter` over the whole `table`: `f` gets the values of each record (in the order of cols, null for null) and its rid
+  // the values are copied out, so they don't borrow the db; stop at the first error, after calling `f` on the records before it
+  pub fn scan_table<'a>(&mut self, table: &'a str, mut f: impl FnMut(&[LitExt], Rid)) -> Result<'a, ()> {
+    unsafe {
+      let tp = self.get_tp(table)?.1;
+      let mut vals = Vec::with_capacity(tp.col_num as usize);
+      for (data, rid) in self.record_iter(tp) {
+        self.check_cancel()?;
+        self.check_record(tp, data, rid)?;
+        vals.clear();
+        vals.extend(tp.cols().iter().enumerate().map(|(ci_id, ci)| LitExt::from(self.data2lit(data, ci_id as u32, ci).lit())));
+        f(&vals, rid);
+      }
+      Ok(())
+    }
+  }
 }
 
 pub struct RecordIter<'a> {
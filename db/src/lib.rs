@@ -9,21 +9,22 @@ pub mod lob;
 
 pub use crate::{db::*, iter::*, lob::*, show::*};
 
-use regex::Regex;
+use regex::{Regex, RegexBuilder};
 
 use common::{*, Error::*, BareTy::*};
 use chrono::NaiveDate;
 use physics::ColInfo;
 
 // `data` points to the beginning of the whole data slot
-pub unsafe fn is_null(data: *const u8, ci_id: u32) -> bool { bsget(data as *const u32, ci_id as usize) }
+// a null `data` (the missing right table of left join in select) has all cols null
+pub unsafe fn is_null(data: *const u8, ci_id: u32) -> bool { data.is_null() || bsget(data as *const u32, ci_id as usize) }
 
 pub fn date(date: &str) -> Result<NaiveDate> {
-  NaiveDate::parse_from_str(date, "%Y-%m-%d").map_err(|reason| InvalidDate { date, reason })
+  parse_date(date).map_err(|reason| InvalidDate { date, reason })
 }
 
-pub fn like2re(like: &str) -> Result<Regex> {
-  Regex::new(&escape_re(like)).map_err(|e| InvalidLike { like, reason: box e })
+pub fn like2re(like: &str, collation: Collation) -> Result<Regex> {
+  RegexBuilder::new(&escape_re(like)).case_insensitive(collation == Collation::NoCase).build().map_err(|e| InvalidLike { like, reason: box e })
 }
 
 pub unsafe fn hash_pks(data: *const u8, pks: &[&ColInfo]) -> u128 {
@@ -1,6 +1,6 @@
 use common::*;
 use physics::*;
-use crate::Db;
+use crate::{Db, MAX_MEMORY_LOB_SLOT};
 
 impl Db {
   pub unsafe fn get_lob(&mut self, id: u32) -> *mut u8 {
@@ -24,7 +24,9 @@ impl Db {
       (x.p().offset_from(base) as u32, count * 32, x.p() as *mut u8)
     } else { // get out of `while` because of `break`
       let id = (self.lob_slots, self.lob_slots += count).0;
-      self.lob_file.set_len(self.lob_slots as u64 * LOB_SLOT_SIZE as u64).expect("failed to allocate lob slot. the database may already be in an invalid state.");
+      if let Some(lob_file) = &self.lob_file {
+        lob_file.set_len(self.lob_slots as u64 * LOB_SLOT_SIZE as u64).expect("failed to allocate lob slot. the database may already be in an invalid state.");
+      } else { assert!(self.lob_slots as usize <= MAX_MEMORY_LOB_SLOT, "failed to allocate lob slot. the in-memory database is full."); }
       (id, count * 32, base.add(id as usize) as *mut u8)
     }
   }
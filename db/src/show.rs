@@ -1,4 +1,4 @@
-use std::{fmt::Write, path::Path};
+use std::{fmt::Write, path::Path, cmp::Ordering};
 use unchecked_unwrap::UncheckedUnwrap;
 
 use common::*;
@@ -14,7 +14,134 @@ pub fn show_db<'a>(path: impl AsRef<Path>, s: &mut String) -> Result<'a, ()> {
   }
 }
 
+// the schema of a col, for programmatic use (`show_table` is for human)
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ColMeta<'a> {
+  pub name: &'a str,
+  pub ty: ColTy,
+  pub notnull: bool,
+  pub primary: bool,
+  pub unique: bool,
+  pub index: bool,
+  // (table, col)
+  pub foreign: Option<(&'a str, &'a str)>,
+  pub comment: Option<&'a str>,
+}
+
+// an index of a table, for programmatic use (`show_indexes` is for human)
+// an index is always on a single col, in ascending order (strings in binary order, see `Db::index_usable`)
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct IndexMeta<'a> {
+  // empty for the internal index created for primary, unique and foreign key cols
+  pub name: &'a str,
+  pub col: &'a str,
+  pub unique: bool,
+}
+
+// a profile of the values in a col, computed by `Db::column_stats`
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColStats {
+  pub rows: usize,
+  pub nulls: usize,
+  // the number of distinct non-null values (in the sense of group by)
+  pub distinct: usize,
+  // the smallest and the largest non-null values (in the order of `order by`), Null if all values are null
+  pub min: LitExt,
+  pub max: LitExt,
+  // the average of non-null values, only for int and float cols, None if all values are null
+  pub avg: Option<f64>,
+}
+
+// accumulates the values of one col into `ColStats`
+struct StatsAcc<'a> { nulls: usize, sum: f64, min: CLit<'a>, max: CLit<'a>, seen: HashSet<GroupKey<'a>> }
+
+impl<'a> StatsAcc<'a> {
+  fn new() -> StatsAcc<'a> { StatsAcc { nulls: 0, sum: 0.0, min: CLit::new(Lit::Null), max: CLit::new(Lit::Null), seen: HashSet::new() } }
+
+  unsafe fn add(&mut self, v: CLit<'a>) {
+    if v.is_null() { return self.nulls += 1; }
+    if let Lit::Number(x) = v.lit() { self.sum += x; }
+    if self.min.is_null() || v.cmp(self.min) == Ordering::Less { self.min = v; }
+    if self.max.is_null() || v.cmp(self.max) == Ordering::Greater { self.max = v; }
+    self.seen.insert(v.lit().group_key());
+  }
+
+  fn finish(self, rows: usize, ty: ColTy) -> ColStats {
+    let nulls = self.nulls;
+    let avg = match ty { int!() | float!() if rows != nulls => Some(self.sum / (rows - nulls) as f64), _ => None };
+    ColStats { rows, nulls, distinct: self.seen.len(), min: self.min.lit().into(), max: self.max.lit().into(), avg }
+  }
+}
+
 impl Db {
+  // table names, in the order of creation
+  pub fn tables(&self) -> Vec<&str> {
+    unsafe { self.pr().dp().tables().iter().map(|&tp_id| self.pr().get_page::<TablePage>(tp_id).name()).collect() }
+  }
+
+  // cols of `table`, in the declaration order (the same as `select *`)
+  pub fn columns<'a>(&self, table: &'a str) -> Result<'a, Vec<ColMeta>> {
+    unsafe {
+      let tp = self.pr().get_tp(table)?.1;
+      Ok(tp.cols().iter().map(|ci| ColMeta {
+        name: ci.name(),
+        ty: ci.ty,
+        notnull: ci.flags.intersects(ColFlags::NOTNULL1),
+        primary: ci.flags.contains(ColFlags::PRIMARY),
+        unique: ci.flags.contains(ColFlags::UNIQUE),
+        index: ci.index != !0,
+        foreign: if ci.f_table != !0 {
+          let f_tp = self.pr().get_page::<TablePage>(ci.f_table);
+          Some((f_tp.name(), f_tp.cols.get_unchecked(ci.f_col as usize).name()))
+        } else { None },
+        comment: self.comment(ci),
+      }).collect())
+    }
+  }
+
+  // indexes of `table`, in the declaration order of their cols
+  pub fn indexes<'a>(&self, table: &'a str) -> Result<'a, Vec<IndexMeta>> {
+    unsafe {
+      let tp = self.pr().get_tp(table)?.1;
+      let primary_cnt = tp.primary_cols().count();
+      Ok(tp.cols().iter().filter_map(|ci| Some(IndexMeta { name: ci.idx_name()?, col: ci.name(), unique: ci.unique(primary_cnt) })).collect())
+    }
+  }
+
+  // it scans all records of `table`, nothing is maintained by insert or delete
+  pub fn column_stats<'a>(&self, table: &'a str, col: &'a str) -> Result<'a, ColStats> {
+    unsafe {
+      let tp = self.pr().get_tp(table)?.1;
+      let ci = tp.pr().get_ci(col)?;
+      let ci_id = ci.idx(&tp.cols);
+      let (mut rows, mut acc) = (0, StatsAcc::new());
+      for (data, rid) in self.pr().record_iter(tp) {
+        self.check_cancel()?;
+        self.check_record(tp, data, rid)?;
+        rows += 1;
+        acc.add(self.data2lit(data, ci_id, ci));
+      }
+      Ok(acc.finish(rows, ci.ty))
+    }
+  }
+
+  // like `column_stats` of each col (in the declaration order), but all of them are computed in one scan
+  // the record count of the table (`TablePage::count`, which insert and delete maintain) is also recounted and corrected by the scan
+  pub fn analyze<'a>(&mut self, table: &'a str) -> Result<'a, Vec<ColStats>> {
+    unsafe {
+      let tp = self.get_tp(table)?.1;
+      let (mut rows, mut accs) = (0, tp.cols().iter().map(|_| StatsAcc::new()).collect::<Vec<_>>());
+      for (data, rid) in self.pr().record_iter(tp) {
+        self.check_cancel()?;
+        self.check_record(tp, data, rid)?;
+        rows += 1;
+        for ((ci_id, ci), acc) in tp.cols().iter().enumerate().zip(accs.iter_mut()) { acc.add(self.data2lit(data, ci_id as u32, ci)); }
+      }
+      tp.count = rows as u32;
+      Ok(accs.into_iter().zip(tp.cols()).map(|(acc, ci)| acc.finish(rows, ci.ty)).collect())
+    }
+  }
+
   pub fn show_table<'a>(&self, table: &'a str) -> Result<'a, String> {
     unsafe {
       let tp = self.pr().get_tp(table)?.1;
@@ -34,11 +161,23 @@ impl Db {
     }
   }
 
+  pub fn show_indexes<'a>(&self, table: &'a str) -> Result<'a, String> {
+    let indexes = self.indexes(table)?;
+    let mut s = String::new();
+    writeln!(s, "table `{}`: index count = {}", table, indexes.len()).unchecked_unwrap();
+    for i in indexes {
+      s += "  - index ";
+      if i.name.is_empty() { s += "<internal>"; } else { write!(s, "`{}`", i.name).unchecked_unwrap(); }
+      writeln!(s, ": col `{}`, {}, asc", i.col, if i.unique { "unique" } else { "not unique" }).unchecked_unwrap();
+    }
+    Ok((s.pop(), s).1)
+  }
+
   unsafe fn show_table_info(&self, tp: &TablePage, s: &mut String) {
     writeln!(s, "table `{}`: record count = {}, record size = {}", tp.name(), tp.count, tp.size).unchecked_unwrap();
     for (idx, ci) in tp.cols().iter().enumerate() {
       writeln!(s, "  - col {}: `{}`: {:?} @ offset +{} ", idx, ci.name(), ci.ty, ci.off).unchecked_unwrap();
-      if !ci.flags.is_empty() {
+      if ci.flags.intersects(ColFlags::PRIMARY | ColFlags::NOTNULL | ColFlags::UNIQUE) {
         *s += "    - attr: ";
         if ci.flags.contains(ColFlags::PRIMARY) { *s += "primary + "; }
         if ci.flags.contains(ColFlags::NOTNULL) { *s += "notnull + "; }
@@ -69,6 +208,12 @@ impl Db {
           writeln!(s, "    - default: {:?}", self.ptr2lit(cp.data.as_ptr().add(count * size), ci.ty)).unchecked_unwrap();
         }
       }
+      if ci.flags.contains(ColFlags::DFT_CURRENT_DATE) { *s += "    - default: current_date\n"; }
+      if let Some(comment) = self.comment(ci) { writeln!(s, "    - comment: {:?}", comment).unchecked_unwrap(); }
+    }
+    if tp.check != !0 {
+      let cp = self.pr().get_page::<CheckPage>(tp.check);
+      writeln!(s, "  - check: {}", str_from_parts(cp.data.as_ptr(), cp.count as usize)).unchecked_unwrap();
     }
   }
 }
\ No newline at end of file
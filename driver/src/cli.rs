@@ -1,17 +1,19 @@
 use rustyline::{Editor, Helper, highlight::Highlighter, completion::Completer, hint::Hinter, error::ReadlineError};
 use colored::*;
-use std::{borrow::Cow, str, fs};
+use std::{borrow::Cow, str, env, process, fs::{self, File, OpenOptions}, io::{self, BufWriter, Read, Write}, sync::atomic::Ordering};
 use typed_arena::Arena;
 
-use driver::Eval;
-use syntax::{Lexer, TokenKind};
+use driver::{Eval, ResultSink};
+use query::SelectResult;
+use common::{Error, ModifyError, Collation, LitExt};
+use db::{Db, ColStats};
+use syntax::{Lexer, TokenClass};
 
 struct SqlHelper;
 
 impl Highlighter for SqlHelper {
   #[cfg_attr(tarpaulin, skip)]
   fn highlight<'l>(&self, line: &'l str, _pos: usize) -> Cow<'l, str> {
-    use TokenKind::*;
     let mut lexer = Lexer::new(line.as_bytes());
     let mut ret = line.to_owned();
     loop {
@@ -19,14 +21,14 @@ impl Highlighter for SqlHelper {
       let piece = str::from_utf8(token.piece).unwrap();
       let start = token.col as usize - 1 + ret.len() - line.len();
       let range = start..start + piece.len();
-      match token.ty {
-        Lt | Le | Ge | Gt | Eq | Ne | LPar | RPar | Add | Sub | Mul | Div | Mod | Comma | Semicolon => {}
-        Null | True | False | FloatLit | IntLit | StrLit => ret.replace_range(range, &piece.green().to_string()),
-        Int | Bool | Char | Varchar | Float | Date => ret.replace_range(range, &piece.cyan().to_string()),
-        Sum | Avg | Min | Max | Count => ret.replace_range(range, &piece.yellow().to_string()),
-        Id1 | Dot => ret.replace_range(range, &piece.purple().to_string()),
-        _Err | _Eof => break ret.into(),
-        _ => ret.replace_range(range, &piece.blue().bold().to_string()),
+      match token.ty.class() {
+        TokenClass::Punct => {}
+        TokenClass::Lit => ret.replace_range(range, &piece.green().to_string()),
+        TokenClass::Ty => ret.replace_range(range, &piece.cyan().to_string()),
+        TokenClass::Agg => ret.replace_range(range, &piece.yellow().to_string()),
+        TokenClass::Ident => ret.replace_range(range, &piece.purple().to_string()),
+        TokenClass::End => break ret.into(),
+        TokenClass::Keyword => ret.replace_range(range, &piece.blue().bold().to_string()),
       }
     }
   }
@@ -42,12 +44,151 @@ impl Hinter for SqlHelper {}
 
 impl Helper for SqlHelper {}
 
+fn report(e: &ModifyError) {
+  // a common mistake for a new user, so give a hint
+  if let Error::NoDbInUse = e.1 { eprintln!("Error: no database in use, run `use <db>;` first"); } else { eprintln!("Error: {:?}", e); }
+}
+
+fn print_stats(col: &str, s: &ColStats) {
+  print!("{}: {} rows, {} nulls, {} distinct", col, s.rows, s.nulls, s.distinct);
+  if s.min != LitExt::Null { print!(", min {}, max {}", s.min.lit(), s.max.lit()); }
+  if let Some(avg) = s.avg { print!(", avg {}", avg); }
+  println!();
+}
+
+// print `Db::column_stats` of each col of `table`, one line per col
+fn profile<'a>(db: &'a Db, table: &'a str) -> common::Result<'a, ()> {
+  for col in db.columns(table)? { print_stats(col.name, &db.column_stats(table, col.name)?); }
+  Ok(())
+}
+
+// the same output as `profile`, but by `Db::analyze`, which also corrects the record count of `table`
+fn analyze<'a>(db: &'a mut Db, table: &'a str) -> common::Result<'a, ()> {
+  let stats = db.analyze(table)?;
+  for (col, s) in db.columns(table)?.iter().zip(&stats) { print_stats(col.name, s); }
+  Ok(())
+}
+
+// print the results, or write them to the file of `.output`, select results are streamed to the file
+// with `jsonl` (`.mode jsonl`), select results are json lines instead of csv, and `header` is not used
+// each result overwrites the file, or with `append` (`.output --append <file>`), it is appended to the file and ended with '\n'
+// `failed` is set if any write fails
+struct Output<'a> { file: &'a Option<String>, header: bool, jsonl: bool, append: bool, failed: bool }
+
+impl Output<'_> {
+  fn open(&self, file: &str) -> io::Result<File> {
+    OpenOptions::new().write(true).create(true).append(self.append).truncate(!self.append).open(file)
+  }
+}
+
+impl ResultSink for Output<'_> {
+  fn on_select(&mut self, r: &SelectResult) {
+    if (self.jsonl || !self.header) && r.row_count() == 0 { return; } // nothing to output, the same as an empty message
+    let (header, jsonl) = (self.header, self.jsonl);
+    let write = |w: &mut dyn Write| if jsonl { r.write_jsonl(w) } else { r.write_csv(w, header) };
+    if let Some(file) = self.file {
+      let res = self.open(file).and_then(|f| {
+        let mut w = BufWriter::new(f);
+        write(&mut w)?;
+        if self.append { w.write_all(b"\n")?; }
+        w.flush()
+      });
+      if res.is_err() {
+        eprintln!("Error: fails to write to {}", file);
+        self.failed = true;
+      }
+    } else if jsonl { // also streamed, there can be many rows
+      let stdout = io::stdout();
+      let mut w = stdout.lock();
+      if write(&mut w).and_then(|_| w.write_all(b"\n")).is_err() { self.failed = true; }
+    } else { println!("{}", r.csv(header)); }
+  }
+
+  fn on_message(&mut self, msg: &str) {
+    if msg.is_empty() { return; }
+    if let Some(file) = self.file {
+      if self.open(file).and_then(|mut f| f.write_all(msg.as_bytes()).and_then(|_| if self.append { f.write_all(b"\n") } else { Ok(()) })).is_err() {
+        eprintln!("Error: fails to write to {}", file);
+        self.failed = true;
+      }
+    } else { println!("{}", msg); }
+  }
+}
+
+const USAGE: &str = "Usage: db [<db file>] [--format csv|jsonl] [--output <file>] [--no-header] [<sql>]
+  with <sql>, or when stdin is not a terminal, run the stmts from it and exit (with 1 on error), otherwise start the repl";
+
+// the command line options, `db` and `sql` are positional
+struct Args { db: Option<String>, sql: Option<String>, output: Option<String>, header: bool, jsonl: bool }
+
+fn parse_args() -> Result<Args, String> {
+  let mut ret = Args { db: None, sql: None, output: None, header: true, jsonl: false };
+  let mut args = env::args().skip(1);
+  while let Some(arg) = args.next() {
+    match arg.as_str() {
+      "--format" => match args.next() {
+        Some(ref f) if f == "csv" => ret.jsonl = false,
+        Some(ref f) if f == "jsonl" => ret.jsonl = true,
+        Some(f) => return Err(format!("unsupported format {}", f)),
+        None => return Err("missing the value of --format".to_owned()),
+      }
+      "--output" => ret.output = Some(args.next().ok_or_else(|| "missing the value of --output".to_owned())?),
+      "--no-header" => ret.header = false,
+      _ if arg.starts_with("--") => return Err(format!("unknown option {}", arg)),
+      _ => if ret.db.is_none() { ret.db = Some(arg); } else if ret.sql.is_none() { ret.sql = Some(arg); } else {
+        return Err(format!("unexpected argument {}", arg));
+      }
+    }
+  }
+  Ok(ret)
+}
+
+// run `code` non-interactively, exit with 1 if any stmt fails (the stmts after it are not executed) or the output can't be written
+// like `.output`, each result overwrites the output file
+fn batch(e: &mut Eval, code: &str, output: &Option<String>, jsonl: bool) -> ! {
+  let mut out = Output { file: output, header: e.header(), jsonl, append: false, failed: false };
+  if let Err(e) = e.exec_all_sink(code, &Arena::default(), &mut out) {
+    report(&e);
+    process::exit(1);
+  }
+  process::exit(if out.failed { 1 } else { 0 })
+}
+
 fn main() {
+  let args = match parse_args() {
+    Ok(args) => args,
+    Err(msg) => {
+      eprintln!("Error: {}\n{}", msg, USAGE);
+      process::exit(2);
+    }
+  };
+  let mut e = Eval::default();
+  e.set_header(args.header);
+  if let Some(db) = &args.db {
+    if let Err(err) = e.open(db) {
+      report(&err.into());
+      process::exit(1);
+    }
+  }
+  let cancel = e.cancel_flag();
+  {
+    let cancel = cancel.clone();
+    // readline catches ctrl-c itself (as `Interrupted`), so the handler only works while executing stmts
+    ctrlc::set_handler(move || cancel.store(true, Ordering::Relaxed)).expect("Error: fails to set ctrl-c handler");
+  }
+  let (mut output, mut jsonl, mut append) = (args.output, args.jsonl, false);
+  if let Some(sql) = &args.sql { batch(&mut e, sql, &output, jsonl); }
+  if !atty::is(atty::Stream::Stdin) {
+    let mut code = String::new();
+    if io::stdin().read_to_string(&mut code).is_err() {
+      eprintln!("Error: fails to read from stdin");
+      process::exit(1);
+    }
+    batch(&mut e, &code, &output, jsonl);
+  }
   let mut rl = Editor::new();
   rl.set_helper(Some(SqlHelper));
   let mut cur = String::new();
-  let mut e = Eval::default();
-  let mut output = None;
   println!("Database repl by MashPlant. Enter sql statement separated by semicolon.");
   loop {
     match rl.readline(if cur.is_empty() { ">> " } else { ".. " }) {
@@ -55,19 +196,111 @@ fn main() {
         let line = line.trim();
         if line.is_empty() { continue; }
         rl.add_history_entry(line);
+        cancel.store(false, Ordering::Relaxed);
         if cur.is_empty() && line.starts_with('.') {
           let mut words = line.split_whitespace();
           let cmd = words.next().unwrap();
           const OUTPUT: &str = ".output";
           const READ: &str = ".read";
           const COLOR: &str = ".color";
+          const IMPORT: &str = ".import";
+          const HEADER: &str = ".header";
+          const OPEN: &str = ".open";
+          const DATABASES: &str = ".databases";
+          const WARN: &str = ".warn";
+          const MAX_ROWS: &str = ".maxrows";
+          const COLLATE: &str = ".collate";
+          const EMPTY_NULL: &str = ".emptynull";
+          const INT_BOOL: &str = ".intbool";
+          const TRUNCATE: &str = ".truncate";
+          const SEED: &str = ".seed";
+          const FLUSH: &str = ".flush";
+          const PROFILE: &str = ".profile";
+          const MODE: &str = ".mode";
+          const ANALYZE: &str = ".analyze";
           match cmd {
-            OUTPUT => output = words.next().map(|x| x.to_owned()),
+            // no file or `stdout` reverts to the console
+            OUTPUT => match words.next() {
+              Some("--append") => if let Some(file) = words.next() { (output = Some(file.to_owned()), append = true); } else {
+                eprintln!("Usage: {} [--append] <file> | stdout", OUTPUT);
+              }
+              Some("stdout") | None => output = None,
+              Some(file) => { (output = Some(file.to_owned()), append = false); }
+            }
             READ => if let Some(file) = words.next() {
               if let Ok(input) = fs::read_to_string(file) {
-                if let Err(e) = e.exec_all(&input, &Arena::default(), |_| {}, |_| {}) { eprintln!("Error: {:?}", e); }
+                if let Err(e) = e.exec_all(&input, &Arena::default(), |_| {}, |_| {}) { report(&e); }
               } else { eprintln!("Error: fails to read from {}", file); }
             } else { eprintln!("Usage: {} <file>", READ); }
+            IMPORT => if let (Some(file), Some(table)) = (words.next(), words.next()) {
+              if let Ok(input) = fs::read_to_string(file) {
+                match e.import(&input, table, &Arena::default()) {
+                  Ok(n) => println!("{} column(s) affected", n),
+                  Err(e) => report(&e),
+                }
+              } else { eprintln!("Error: fails to read from {}", file); }
+            } else { eprintln!("Usage: {} <file> <table>", IMPORT); }
+            OPEN => if let Some(file) = words.next() {
+              // e.g., InvalidMagic if the file is not a db
+              if let Err(err) = e.open(file) { eprintln!("Error: fails to open {}: {:?}", file, err); }
+            } else { eprintln!("Usage: {} <file>", OPEN); }
+            // only one db can be in use at a time
+            DATABASES => {
+              let in_use = e.db().is_ok();
+              match e.path() {
+                Some(path) => println!("{}", path),
+                None => println!("{}", if in_use { "(memory)" } else { "(no database in use)" }),
+              }
+            }
+            MODE => match words.next() {
+              Some("csv") => jsonl = false,
+              Some("jsonl") => jsonl = true,
+              _ => eprintln!("Usage: {} [csv|jsonl]", MODE),
+            }
+            HEADER => match words.next() {
+              Some("on") => e.set_header(true),
+              Some("off") => e.set_header(false),
+              _ => eprintln!("Usage: {} [on|off]", HEADER),
+            }
+            WARN => match words.next() {
+              Some("on") => e.set_warn_cross_join(true),
+              Some("off") => e.set_warn_cross_join(false),
+              _ => eprintln!("Usage: {} [on|off]", WARN),
+            }
+            MAX_ROWS => if let Some(n) = words.next().and_then(|x| x.parse().ok()) {
+              e.set_max_rows(n);
+            } else { eprintln!("Usage: {} <n> (0 for unlimited)", MAX_ROWS); }
+            COLLATE => if let Some(collation) = words.next().and_then(Collation::from_name) {
+              e.set_collation(collation);
+            } else { eprintln!("Usage: {} [binary|nocase]", COLLATE); }
+            EMPTY_NULL => match words.next() {
+              Some("on") => e.set_empty_null(true),
+              Some("off") => e.set_empty_null(false),
+              _ => eprintln!("Usage: {} [on|off]", EMPTY_NULL),
+            }
+            INT_BOOL => match words.next() {
+              Some("on") => e.set_int_bool(true),
+              Some("off") => e.set_int_bool(false),
+              _ => eprintln!("Usage: {} [on|off]", INT_BOOL),
+            }
+            TRUNCATE => match words.next() {
+              Some("on") => e.set_truncate_str(true),
+              Some("off") => e.set_truncate_str(false),
+              _ => eprintln!("Usage: {} [on|off]", TRUNCATE),
+            }
+            // with a seed, `order by random() limit n` gives the same sample each time
+            SEED => match words.next() {
+              Some("off") => e.set_seed(None),
+              Some(n) if n.parse::<u64>().is_ok() => e.set_seed(n.parse().ok()),
+              _ => eprintln!("Usage: {} <n> | off", SEED),
+            }
+            FLUSH => if let Err(err) = e.db().and_then(|db| db.flush()) { eprintln!("Error: {:?}", err); }
+            PROFILE => if let Some(table) = words.next() {
+              if let Err(err) = e.db().and_then(|db| profile(db, table)) { eprintln!("Error: {:?}", err); }
+            } else { eprintln!("Usage: {} <table>", PROFILE); }
+            ANALYZE => if let Some(table) = words.next() {
+              if let Err(err) = e.db().and_then(|db| analyze(db, table)) { eprintln!("Error: {:?}", err); }
+            } else { eprintln!("Usage: {} <table>", ANALYZE); }
             COLOR => if let Some(color) = words.next().and_then(|x| x.parse().ok()) {
               rl.set_helper(if color { Some(SqlHelper) } else { None });
             } else { eprintln!("Usage: {} [true|false]", COLOR); }
@@ -77,11 +310,8 @@ fn main() {
           cur += line;
           cur.push('\n');
           if line.contains(';') {
-            if let Err(e) = e.exec_all(&cur, &Arena::default(), |_| {}, |x| if !x.is_empty() {
-              if let Some(output) = &output {
-                if fs::write(output, x).is_err() { eprintln!("Error: fails to write to {}", output); }
-              } else { println!("{}", x); }
-            }) { eprintln!("Error: {:?}", e); }
+            let header = e.header();
+            if let Err(e) = e.exec_all_sink(&cur, &Arena::default(), &mut Output { file: &output, header, jsonl, append, failed: false }) { report(&e); }
             cur.clear();
           }
         }
@@ -1,4 +1,4 @@
-use std::{borrow::Cow, fs, path::Path};
+use std::{borrow::Cow, fs, io::{self, Write}, path::Path, sync::{Arc, atomic::AtomicBool}};
 use typed_arena::Arena;
 
 use common::{*, Error::*};
@@ -6,25 +6,175 @@ use syntax::ast::*;
 use db::{Db, show::show_db};
 use query::SelectResult;
 
-#[derive(Default)]
-pub struct Eval(Option<Db>);
+pub mod temp_db;
+pub use temp_db::TempDb;
+
+pub struct Eval {
+  db: Option<Db>,
+  // the file of the db in use, None for an in-memory db (or no db in use)
+  path: Option<String>,
+  // shared with the db in use, setting it cancels the running stmt (it is not cleared automatically)
+  cancel: Arc<AtomicBool>,
+  // whether select results have a header line of col names
+  header: bool,
+  // whether to warn about the select that contains a cartesian product of tables, see `query::unlinked_table`
+  warn_cross_join: bool,
+  // passed to the db in use, see `Db::set_max_rows`
+  max_rows: usize,
+  // passed to the db in use, see `Db::set_collation`
+  collation: Collation,
+  // passed to the db in use, see `Db::set_empty_null`
+  empty_null: bool,
+  // passed to the db in use, see `Db::set_int_bool`
+  int_bool: bool,
+  // passed to the db in use, see `Db::set_truncate_str`
+  truncate_str: bool,
+  // passed to the db in use, see `Db::set_seed`
+  seed: Option<u64>,
+}
+
+impl Default for Eval {
+  fn default() -> Self { Eval { db: None, path: None, cancel: Arc::default(), header: true, warn_cross_join: false, max_rows: 0, collation: Collation::Binary, empty_null: false, int_bool: false, truncate_str: false, seed: None } }
+}
+
+// receive the results of `exec_all_sink`, select results are passed as they are (not serialized to csv)
+pub trait ResultSink {
+  // called before executing each stmt
+  fn on_stmt(&mut self, _s: &Stmt) {}
+  fn on_select(&mut self, r: &SelectResult);
+  // the output of other stmts, may be empty
+  fn on_message(&mut self, msg: &str);
+}
 
 impl Eval {
+  // start with `db` in use, e.g., an in-memory db from `Db::open_memory`
+  pub fn with_db(db: Db) -> Eval {
+    let mut e = Eval::default();
+    e.use_db(db, None);
+    e
+  }
+
+  fn use_db(&mut self, mut db: Db, path: Option<&str>) {
+    db.set_cancel_flag(self.cancel.clone());
+    db.set_max_rows(self.max_rows);
+    db.set_collation(self.collation);
+    db.set_empty_null(self.empty_null);
+    db.set_int_bool(self.int_bool);
+    db.set_truncate_str(self.truncate_str);
+    db.set_seed(self.seed);
+    self.db = Some(db);
+    self.path = path.map(|x| x.to_owned());
+  }
+
+  // like `use <db>`, but `path` can be any file path, not only an identifier
+  // the db in use is unchanged if `path` is not a valid db file
+  pub fn open<'a>(&mut self, path: &str) -> Result<'a, ()> {
+    let db = Db::open(path)?;
+    Ok(self.use_db(db, Some(path)))
+  }
+
+  pub fn path(&self) -> Option<&str> { self.path.as_ref().map(|x| x.as_str()) }
+
+  pub fn set_header(&mut self, header: bool) { self.header = header; }
+
+  pub fn header(&self) -> bool { self.header }
+
+  // the warning is passed to `on_message` before the select result
+  pub fn set_warn_cross_join(&mut self, warn: bool) { self.warn_cross_join = warn; }
+
+  // 0 for unlimited, it applies to the db in use and later used dbs
+  pub fn set_max_rows(&mut self, max_rows: usize) {
+    self.max_rows = max_rows;
+    if let Some(db) = &mut self.db { db.set_max_rows(max_rows); }
+  }
+
+  // the collation of string comparison in later stmts, like `max_rows`, it also applies to later used dbs
+  pub fn set_collation(&mut self, collation: Collation) {
+    self.collation = collation;
+    if let Some(db) = &mut self.db { db.set_collation(collation); }
+  }
+
+  // whether '' is null for non-string cols in later insert and import, it also applies to later used dbs
+  pub fn set_empty_null(&mut self, empty_null: bool) {
+    self.empty_null = empty_null;
+    if let Some(db) = &mut self.db { db.set_empty_null(empty_null); }
+  }
+
+  // whether ints are accepted as bools in later stmts and imports, like `empty_null`, it also applies to later used dbs
+  pub fn set_int_bool(&mut self, int_bool: bool) {
+    self.int_bool = int_bool;
+    if let Some(db) = &mut self.db { db.set_int_bool(int_bool); }
+  }
+
+  // whether over-long strings are truncated instead of rejected in later stmts and imports, it also applies to later used dbs
+  pub fn set_truncate_str(&mut self, truncate_str: bool) {
+    self.truncate_str = truncate_str;
+    if let Some(db) = &mut self.db { db.set_truncate_str(truncate_str); }
+  }
+
+  // the seed of `random()` in later stmts, None for a different one each time, it also applies to later used dbs
+  pub fn set_seed(&mut self, seed: Option<u64>) {
+    self.seed = seed;
+    if let Some(db) = &mut self.db { db.set_seed(seed); }
+  }
+
+  // set the flag to cancel the running stmt, e.g., from a signal handler; remember to clear it before the next stmt
+  pub fn cancel_flag(&self) -> Arc<AtomicBool> { self.cancel.clone() }
+
   pub fn exec_all<'a>(&mut self, code: &'a str, alloc: &'a Arena<u8>, input_handler: impl Fn(&Stmt), result_handler: impl Fn(&str)) -> ModifyResult<'a, ()> {
+    struct Handlers<I, R>(I, R, bool);
+    impl<I: Fn(&Stmt), R: Fn(&str)> ResultSink for Handlers<I, R> {
+      fn on_stmt(&mut self, s: &Stmt) { (self.0)(s) }
+      fn on_select(&mut self, r: &SelectResult) { (self.1)(&r.csv(self.2)) }
+      fn on_message(&mut self, msg: &str) { (self.1)(msg) }
+    }
+    let header = self.header;
+    self.exec_all_sink(code, alloc, &mut Handlers(input_handler, result_handler, header))
+  }
+
+  pub fn exec_all_sink<'a>(&mut self, code: &'a str, alloc: &'a Arena<u8>, sink: &mut impl ResultSink) -> ModifyResult<'a, ()> {
     for s in &syntax::work(code, alloc)? {
-      input_handler(s);
-      result_handler(&self.exec(s)?);
+      sink.on_stmt(s);
+      if let Stmt::Select(s) = s {
+        if let Some(t) = self.db.as_ref().filter(|_| self.warn_cross_join).and_then(|db| query::unlinked_table(s, db)) {
+          sink.on_message(&format!("Warning: table `{}` is not linked with others by any cond, the result contains their cartesian product", t));
+        }
+        sink.on_select(&self.select(s)?);
+      } else { sink.on_message(&self.exec(s)?); }
     }
     Ok(())
   }
 
+  // parse and run exactly one stmt, return the result of select, or None for other stmts (their messages are dropped)
+  pub fn execute<'a, 'b>(&'b mut self, code: &'a str, alloc: &'a Arena<u8>) -> ModifyResult<'a, Option<SelectResult<'b>>> {
+    let mut ss = syntax::work(code, alloc)?;
+    if ss.len() != 1 { return Err(NotOneStmt(ss.len()).into()); }
+    match ss.pop() {
+      Some(Stmt::Select(s)) => Ok(Some(self.select(&s)?)),
+      Some(s) => (self.exec(&s)?, Ok(None)).1,
+      None => Err(NotOneStmt(0).into()),
+    }
+  }
+
   pub fn exec<'a>(&mut self, sql: &Stmt<'a>) -> ModifyResult<'a, Cow<str>> {
     fn fmt<'a>(n: u32) -> Cow<'a, str> { Cow::Owned(format!("{} column(s) affected", n)) }
     use Stmt::*;
     Ok(match sql {
       Insert(i) => fmt(query::insert(i, self.db()?)?),
+      InsertSelect(i) => fmt(query::insert_select(i, self.db()?)?),
       Delete(d) => fmt(query::delete(d, self.db()?)?),
-      Select(s) => query::select(s, self.db()?)?.csv().into(),
+      Select(s) => {
+        let header = self.header;
+        query::select(s, self.db()?)?.csv(header).into()
+      }
+      // the file is created or truncated, its content is the same as the result of the select, and can be imported back
+      SelectOutfile { select, path } => {
+        let header = self.header;
+        let r = query::select(select, self.db()?)?;
+        let mut w = io::BufWriter::new(fs::File::create(path)?);
+        (r.write_csv(&mut w, header)?, w.flush()?);
+        fmt(r.row_count() as u32)
+      }
       Update(u) => fmt(query::update(u, self.db()?)?),
       &CreateDb(path) => (Db::create(path), "".into()).1,
       &DropDb(path) => (fs::remove_file(path)?, fs::remove_file(AsRef::<Path>::as_ref(path).with_extension(LOB_SUFFIX))?, "".into()).2,
@@ -40,14 +190,21 @@ impl Eval {
         }
         s.into()
       }
-      &UseDb(path) => (self.0 = Some(Db::open(path)?), "".into()).1,
-      CreateTable(c) => (self.db()?.create_table(c)?, "".into()).1,
+      &UseDb(path) => (self.use_db(Db::open(path)?, Some(path)), "".into()).1,
+      &RenameDb { old, new } => (Db::rename(old, new)?, "".into()).1,
+      CreateTable(c) => (query::create_table(self.db()?, c)?, "".into()).1,
+      &CreateTableLike { table, like, data } => {
+        let n = query::create_table_like(self.db()?, table, like, data)?;
+        if data { fmt(n) } else { "".into() }
+      }
       &DropTable(table) => (self.db()?.drop_table(table)?, "".into()).1,
       &ShowTable(table) => self.db()?.show_table(table)?.into(),
       ShowTables => self.db()?.show_tables().into(),
+      &ShowIndexes(table) => self.db()?.show_indexes(table)?.into(),
       CreateIndex(c) => (index::create_index(self.db()?, c)?, "".into()).1,
       &DropIndex { index, table } => (self.db()?.drop_index(index, table)?, "".into()).1,
       &Rename { old, new } => (self.db()?.rename_table(old, new)?, "".into()).1,
+      &RenameCol { table, old, new } => (self.db()?.rename_col(table, old, new)?, "".into()).1,
       AddForeign(a) => (index::add_foreign(self.db()?, a)?, "".into()).1,
       &DropForeign { table, col } => (self.db()?.drop_foreign(table, col)?, "".into()).1,
       AddPrimary { table, cols } => (index::add_primary(self.db()?, table, cols)?, "".into()).1,
@@ -57,9 +214,24 @@ impl Eval {
     })
   }
 
+  // import csv data (with a header of col names) into `table`, see `query::import`
+  pub fn import<'a>(&mut self, csv: &'a str, table: &'a str, alloc: &'a Arena<u8>) -> ModifyResult<'a, u32> {
+    query::import(csv, table, self.db()?, alloc)
+  }
+
   pub fn select<'a, 'b>(&'b self, s: &Select<'a>) -> Result<'a, SelectResult<'b>> {
-    query::select(s, self.0.as_ref().ok_or(NoDbInUse)?)
+    query::select(s, self.db.as_ref().ok_or(NoDbInUse)?)
+  }
+
+  // run `s` and return the actual rows and time of each step in an indented tree, see `query::Analyze::tree`
+  pub fn explain_analyze<'a>(&self, s: &Select<'a>) -> Result<'a, String> {
+    Ok(query::select_analyze(s, self.db.as_ref().ok_or(NoDbInUse)?)?.1.tree())
+  }
+
+  // like `explain_analyze`, but the steps are in json for tools, see `query::Analyze::json`
+  pub fn explain_analyze_json<'a>(&self, s: &Select<'a>) -> Result<'a, String> {
+    Ok(query::select_analyze(s, self.db.as_ref().ok_or(NoDbInUse)?)?.1.json())
   }
 
-  pub fn db<'a>(&mut self) -> Result<'a, &mut Db> { self.0.as_mut().ok_or(NoDbInUse) }
+  pub fn db<'a>(&mut self) -> Result<'a, &mut Db> { self.db.as_mut().ok_or(NoDbInUse) }
 }
\ No newline at end of file
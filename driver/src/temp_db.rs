@@ -0,0 +1,59 @@
+use std::{fs, path::{Path, PathBuf}};
+use typed_arena::Arena;
+
+use common::*;
+use db::Db;
+use query::SelectResult;
+use crate::{Eval, ResultSink};
+
+// a db that is removed when dropped, for tests and embedders that don't want the callbacks of `exec_all`
+pub struct TempDb {
+  eval: Eval,
+  // the db file, None for an in-memory db
+  path: Option<PathBuf>,
+  // the sql strings are parsed in it, so errors can borrow from it; it is never cleared, so don't run too much sql on one TempDb
+  alloc: Arena<u8>,
+}
+
+impl TempDb {
+  pub fn memory<'a>() -> Result<'a, TempDb> {
+    Ok(TempDb { eval: Eval::with_db(Db::open_memory()?), path: None, alloc: Arena::default() })
+  }
+
+  // create a new db file at `path`, it and its lob file are removed on drop
+  pub fn file<'a>(path: impl AsRef<Path>) -> Result<'a, TempDb> {
+    let path = path.as_ref().to_owned();
+    Ok(TempDb { eval: Eval::with_db(Db::create(&path)?), path: Some(path), alloc: Arena::default() })
+  }
+
+  pub fn eval(&mut self) -> &mut Eval { &mut self.eval }
+
+  // run all stmts in `sql`, ignoring their outputs
+  pub fn exec<'a>(&'a mut self, sql: &'a str) -> ModifyResult<'a, ()> {
+    self.eval.exec_all(sql, &self.alloc, |_| {}, |_| {})
+  }
+
+  // run all stmts in `sql`, return the rows of the last select (empty if there is no select)
+  pub fn query<'a>(&'a mut self, sql: &'a str) -> ModifyResult<'a, Vec<Vec<LitExt>>> {
+    struct Rows(Vec<Vec<LitExt>>);
+    impl ResultSink for Rows {
+      fn on_select(&mut self, r: &SelectResult) {
+        self.0 = r.data.chunks(r.cols.len().max(1)).map(|row| row.iter().map(|x| x.lit().into()).collect()).collect();
+      }
+      fn on_message(&mut self, _msg: &str) {}
+    }
+    let mut rows = Rows(vec![]);
+    self.eval.exec_all_sink(sql, &self.alloc, &mut rows)?;
+    Ok(rows.0)
+  }
+}
+
+impl Drop for TempDb {
+  fn drop(&mut self) {
+    self.eval.db = None; // unmap the files before removing them
+    if let Some(path) = &self.path {
+      let _ = fs::remove_file(path);
+      let _ = fs::remove_file(path.with_extension(LOB_SUFFIX));
+    }
+  }
+}
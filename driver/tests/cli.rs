@@ -0,0 +1,42 @@
+use std::{env, fs, io::Write, path::PathBuf, process::{Command, Output, Stdio}};
+
+use driver::TempDb;
+
+// cargo builds the binaries before the integration tests, into the parent dir of the test executable's dir (target/<profile>/deps)
+fn db_bin() -> PathBuf {
+  let mut path = env::current_exe().unwrap();
+  path.pop();
+  if path.ends_with("deps") { path.pop(); }
+  path.join(if cfg!(windows) { "db.exe" } else { "db" })
+}
+
+fn run(args: &[&str], stdin: &str) -> Output {
+  let mut child = Command::new(db_bin()).args(args).stdin(Stdio::piped()).stdout(Stdio::piped()).stderr(Stdio::piped()).spawn().unwrap();
+  child.stdin.take().unwrap().write_all(stdin.as_bytes()).unwrap();
+  child.wait_with_output().unwrap()
+}
+
+#[test]
+fn batch() {
+  let dir = env::temp_dir();
+  let (path, out) = (dir.join(format!("cli_batch_{}.db", std::process::id())), dir.join(format!("cli_batch_{}.csv", std::process::id())));
+  let _t = TempDb::file(&path).unwrap(); // only for removing the db file at last
+  let path = path.to_str().unwrap();
+  assert!(run(&[path, "create table t (id int, s char(5)); insert into t values (1, 'a'), (2, 'b');"], "").status.success());
+
+  let o = run(&[path, "--format", "csv", "--output", out.to_str().unwrap(), "select * from t where id = 2;"], "");
+  assert!(o.status.success());
+  assert_eq!(fs::read_to_string(&out).unwrap(), "id,s\n2,\"b\"");
+  let _ = fs::remove_file(&out);
+
+  // stmts from stdin, stop at the first error
+  let o = run(&[path, "--no-header"], "select count(*) from t;\nselect * from x;\nselect id from t;");
+  assert_eq!(o.status.code(), Some(1));
+  assert_eq!(String::from_utf8(o.stdout).unwrap(), "2\n");
+  assert!(String::from_utf8(o.stderr).unwrap().contains("NoSuchTable"));
+
+  let o = run(&[path, "--format", "jsonl", "select * from t;"], "");
+  assert_eq!(String::from_utf8(o.stdout).unwrap(), "{\"id\":1,\"s\":\"a\"}\n{\"id\":2,\"s\":\"b\"}\n");
+  assert_eq!(run(&[path, "--format", "json", "select * from t;"], "").status.code(), Some(2));
+  assert_eq!(run(&["no_such_dir/x.db", "select * from t;"], "").status.code(), Some(1));
+}
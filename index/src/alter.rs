@@ -41,6 +41,7 @@ pub fn add_foreign<'a>(db: &mut Db, a: &AddForeign<'a>) -> Result<'a, ()> {
     if !f_ci.unique(f_tp.primary_cols().count()) { return Err(ForeignOnNotUnique(a.f_col)); }
     debug_assert!(!f_ci.ty.is_varchar());
     if f_ci.ty != ci.ty { return Err(IncompatibleForeignTy { foreign: f_ci.ty, own: ci.ty }); }
+    if ci.flags.intersects(ColFlags::NOTNULL1) && db.not_null_foreign_reach(f_tp_id, tp_id) { return Err(ForeignKeyCycle(a.col)); }
     macro_rules! handle {
       ($ty: ident) => {{
         let index = Index::<{ $ty }>::new(db, f_tp_id, f_ci_id);
@@ -74,6 +75,8 @@ pub fn add_primary<'a>(db: &mut Db, table: &'a str, cols: &[&'a str]) -> Result<
       let ci = tp.get_ci(col)?;
       if ci.flags.contains(ColFlags::PRIMARY) { return Err(DupConstraint(col)); }
       if ci.ty.is_varchar() { return Err(UnsupportedVarcharOp(col)); }
+      // primary key is not null, so the foreign link on it becomes not null
+      if ci.f_table != !0 && !ci.flags.contains(ColFlags::NOTNULL) && db.not_null_foreign_reach(ci.f_table, tp_id) { return Err(ForeignKeyCycle(col)); }
       pks.push(ci);
     }
     for (data, _) in db.record_iter(tp) {
@@ -124,8 +127,10 @@ pub fn add_col<'a>(db: &mut Db, table: &'a str, col: &ColDecl<'a>) -> Result<'a,
     let (tp_id, tp) = db.get_tp(table)?;
     if tp.col_num == MAX_COL as u8 { return Err(ColTooMany(tp.col_num as usize + 1)); }
     if col.col.len() > MAX_COL_NAME { return Err(ColNameTooLong(col.col)); }
+    if col.comment.map(|x| x.len()).unwrap_or(0) > MAX_COMMENT { return Err(CommentTooLong(col.col)); }
     if tp.get_ci(col.col).is_ok() { return Err(DupCol(col.col)); }
-    let dft = col.dft.unwrap_or(CLit::new(Lit::Null));
+    // existing records get today's date for `current_date`, later inserts evaluate it again
+    let dft = match col.dft { Some(Dft::Lit(dft)) => dft, Some(Dft::CurrentDate) => CLit::new(Lit::Date(today())), None => CLit::new(Lit::Null) };
     let dft = if !dft.is_null() {
       if col.ty.is_varchar() { return Err(UnsupportedVarcharOp(col.col)); }
       let buf = Align4U8::new(col.ty.size() as usize);
@@ -145,12 +150,15 @@ pub fn add_col<'a>(db: &mut Db, table: &'a str, col: &ColDecl<'a>) -> Result<'a,
     calc_size(tp);
 
     let (size, cap, col_num) = (tp.size as usize, tp.cap, tp.col_num as usize);
-    if let Some(dft) = dft.as_ref() {
+    if let Some(Dft::CurrentDate) = col.dft {
+      tp.cols.get_unchecked_mut(col_num - 1).flags.set(ColFlags::DFT_CURRENT_DATE, true);
+    } else if let Some(dft) = dft.as_ref() {
       let (cp_id, cp) = db.alloc_page::<CheckPage>();
       tp.cols.get_unchecked_mut(col_num - 1).check = (cp_id << 1) | 1;
       cp.count = 0;
       cp.data.as_mut_ptr().copy_from_nonoverlapping(dft.ptr, dft.size);
     }
+    if let Some(comment) = col.comment { db.set_comment(tp.cols.get_unchecked_mut(col_num - 1), comment); }
     let last_off = tp.cols.get_unchecked_mut(col_num - 1).off as usize;
     let (mut dp_id, mut dp) = db.alloc_page::<DataPage>();
     dp.init(!0);
@@ -177,6 +185,7 @@ pub fn drop_col<'a>(db: &mut Db, table: &'a str, col: &'a str) -> Result<'a, ()>
     let ci_id = ci.idx(&tp.cols) as usize;
     if col_num == 1 { return Err(ColTooFew); }
     if db.foreign_links_to(tp_id).any(|x| x.2 == ci_id as u8) { return Err(ModifyTableWithForeignLink(table)); }
+    if db.row_check_uses(tp, col) { return Err(ColInCheck(col)); }
     if ci.flags.contains(ColFlags::PRIMARY) {
       let pks = tp.primary_cols().filter(|&x| x.p() != ci.p()).collect::<Vec<_>>();
       if !pks.is_empty() { check_dup(db, tp, &pks)?; }
@@ -9,6 +9,7 @@ pub struct Cmp<const T: BareTy>;
 impl<const T: BareTy> Cmp<{ T }> {
   pub unsafe fn cmp(l: *const u8, r: *const u8) -> Ordering {
     use BareTy::*;
+    // keys are compared as typed values, not as raw bytes, so negative ints sort before positive ones
     match T { // should be optimized out
       Bool => (*(l as *const bool)).cmp(&*(r as *const bool)),
       Int => (*(l as *const i32)).cmp(&*(r as *const i32)),
@@ -1,5 +1,7 @@
 use std::mem::size_of;
 
+use common::str_from_parts;
+
 #[repr(C)]
 pub struct DataPage {
   // !0 for none
@@ -25,11 +27,24 @@ impl DataPage {
 #[repr(C)]
 pub struct CheckPage {
   pub count: u16,
-  pub _rsv: [u8; 2],
+  // only meaningful if the col has ColFlags::COMMENT, the comment is stored in the tail of `data`
+  pub comment_len: u16,
   pub data: [u8; MAX_CHECK_BYTES],
 }
 
 pub const MAX_CHECK_BYTES: usize = 8188;
+pub const MAX_COMMENT: usize = 255;
+
+impl CheckPage {
+  pub unsafe fn comment<'a>(&self) -> &'a str {
+    str_from_parts(self.data.as_ptr().add(MAX_CHECK_BYTES - self.comment_len as usize), self.comment_len as usize)
+  }
+
+  pub unsafe fn set_comment(&mut self, comment: &str) {
+    self.comment_len = comment.len() as u16;
+    self.data.as_mut_ptr().add(MAX_CHECK_BYTES - comment.len()).copy_from_nonoverlapping(comment.as_ptr(), comment.len());
+  }
+}
 
 // a blob slot can either be a FreeBlobSlot, or a [u8; 32]
 #[repr(C)]
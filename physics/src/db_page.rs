@@ -2,16 +2,25 @@ use std::{mem::size_of, slice};
 
 use common::*;
 
+bitflags::bitflags! {
+  pub struct DbFlags: u16 {
+    // `TablePage::check` is valid; a db created before it used that slot as the 127th col
+    const ROW_CHECK = 0b1;
+  }
+}
+
 #[repr(C)]
 pub struct DbPage {
   pub magic: [u8; MAGIC_LEN],
-  pub _rsv1: [u8; 2],
+  pub flags: DbFlags,
   // !0 for none
   pub first_free: u32,
-  // using u16 here is not to save space (since there is still enough space in _rsv)
+  // using u16 here is not to save space
   // but to explicitly show that u16 is enough
   pub table_num: u16,
-  pub _rsv2: [u8; 2],
+  // `DB_VERSION` when the db is created; the files created before it was added have 0 here, and are read as version 1
+  // (their layout may still differ from a new file of version 1 in what `flags` marks, `Db::open` upgrades them)
+  pub version: u16,
   pub tables: [u32; MAX_TABLE],
 }
 
@@ -20,6 +29,8 @@ pub const MAX_TABLE: usize = 2041;
 impl DbPage {
   pub fn init(&mut self) {
     self.magic = *MAGIC;
+    self.flags = DbFlags::ROW_CHECK;
+    self.version = DB_VERSION;
     self.first_free = !0;
     self.table_num = 0;
   }
@@ -7,6 +7,10 @@ bitflags::bitflags! {
     const PRIMARY = 0b1;
     const NOTNULL = 0b10;
     const UNIQUE = 0b100;
+    // the default value is `current_date`, not stored in check page
+    const DFT_CURRENT_DATE = 0b1000;
+    // the comment is stored in check page (the check page may contain no check or default value)
+    const COMMENT = 0b10000;
     const NOTNULL1 = Self::PRIMARY.bits | Self::NOTNULL.bits; // if any bits in NOTNULL1 exists, this slot can't be null
   }
 }
@@ -76,12 +80,19 @@ pub struct TablePage {
   pub name: [u8; MAX_TABLE_NAME],
   pub col_num: u8,
   pub cols: [ColInfo; MAX_COL],
+  // the check page of `check (cond and ...)` on the whole row, !0 for none
+  // `data[..count]` of the check page is the text of the conds, they are parsed again when used
+  pub check: u32,
+  // the `Db::int_bool` when the checks over the row were created, they are always compiled under it
+  // otherwise turning it off would make a check like `b = 1` on a bool col fail to compile on every insert
+  pub check_int_bool: bool,
+  pub _rsv: [u8; 59],
 }
 
 pub const MAX_TABLE_NAME: usize = 46;
 pub const MAX_COL_NAME: usize = 25;
 pub const MAX_IDX_NAME: usize = 15;
-pub const MAX_COL: usize = 127;
+pub const MAX_COL: usize = 126;
 
 impl TablePage {
   pub unsafe fn init(&mut self, size: u16, col_num: u8, name: &str) {
@@ -91,6 +102,7 @@ impl TablePage {
     self.name_len = name.len() as u8;
     self.name.as_mut_ptr().copy_from_nonoverlapping(name.as_ptr(), name.len());
     self.col_num = col_num;
+    (self.check = !0, self.check_int_bool = false);
   }
 
   pub unsafe fn name<'a>(&self) -> &'a str {
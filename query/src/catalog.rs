@@ -0,0 +1,64 @@
+use unchecked_unwrap::UncheckedUnwrap;
+
+use common::{*, Error::*, BareTy::*};
+use syntax::ast::*;
+use physics::*;
+use db::Db;
+use crate::{insert, select::{self, SelectResult}};
+
+// read-only virtual tables describing the schema, they can be used in select like normal tables:
+// __tables(name, ncols, nrows), __columns(tbl_name, name, type, notnull) (`table` is a keyword, so it is `tbl_name`)
+// a user table with the same name is shadowed by them in select
+pub const TABLES: &str = "__tables";
+pub const COLUMNS: &str = "__columns";
+
+pub fn is_catalog(table: &str) -> bool { table == TABLES || table == COLUMNS }
+
+// the sql form of a type, e.g., int, char(5), varchar(10)
+fn ty_name(ty: ColTy) -> String {
+  match ty {
+    ColTy::FixTy(ty) => match ty.ty {
+      Bool => "bool".into(), Int => "int".into(), Float => "float".into(), Date => "date".into(),
+      Char => format!("char({})", ty.size),
+    }
+    ColTy::Varchar(size) => format!("varchar({})", size),
+  }
+}
+
+// copy the catalog of `db` into a new in-memory db, so that all the select machinery (predicate, index, order by, ...) works on it
+unsafe fn build<'a>(db: &Db) -> Result<'a, Db> {
+  let mut cat = Db::open_memory()?;
+  let decl = |col, ty| ColDecl { col, ty, notnull: true, dft: None, comment: None };
+  let (name, int) = (ColTy::Varchar(MAX_TABLE_NAME as u16), ColTy::FixTy(FixTy { ty: Int, size: 0 }));
+  cat.create_table(&CreateTable { table: TABLES, cols: vec![decl("name", name), decl("ncols", int), decl("nrows", int)], cons: vec![] })?;
+  cat.create_table(&CreateTable {
+    table: COLUMNS,
+    cols: vec![decl("tbl_name", name), decl("name", name), decl("type", ColTy::Varchar(32)), decl("notnull", ColTy::FixTy(FixTy { ty: Bool, size: 0 }))],
+    cons: vec![],
+  })?;
+  let (mut tables, mut columns, mut tys) = (vec![], vec![], vec![]);
+  for table in db.tables() {
+    let tp = db.get_tp(table).unchecked_unwrap().1;
+    tables.push(vec![CLit::new(Lit::Str(table)), CLit::new(Lit::Number(tp.cols().len() as f64)), CLit::new(Lit::Number(tp.count as f64))]);
+    for col in db.columns(table).unchecked_unwrap() {
+      columns.push((table, col.name, col.notnull));
+      tys.push(ty_name(col.ty));
+    }
+  }
+  let columns = columns.iter().zip(tys.iter()).map(|(&(table, col, notnull), ty)|
+    vec![CLit::new(Lit::Str(table)), CLit::new(Lit::Str(col)), CLit::new(Lit::Str(ty)), CLit::new(Lit::Bool(notnull))]).collect();
+  // the values always match the cols
+  insert(&Insert { table: TABLES, cols: None, vals: tables, on_conflict: None }, &mut cat).unchecked_unwrap();
+  insert(&Insert { table: COLUMNS, cols: None, vals: columns, on_conflict: None }, &mut cat).unchecked_unwrap();
+  Ok(cat)
+}
+
+pub(crate) unsafe fn select<'a, 'b>(s: &Select<'a>, db: &'b Db, analyze: Option<&mut select::Analyze<'a>>) -> Result<'a, SelectResult<'b>> {
+  // catalog tables live in another db, so they can't be joined with normal tables
+  if let Some(&t) = s.tables.iter().find(|&&t| !is_catalog(t)) { return Err(CatalogJoin(t)); }
+  let cat = box build(db)?;
+  let mut ret = select::select_impl(s, (&*cat).pr(), analyze)?;
+  // `ret` points into `cat`, so keep it alive
+  ret._catalog = Some(cat);
+  Ok(ret)
+}
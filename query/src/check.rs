@@ -0,0 +1,144 @@
+use unchecked_unwrap::UncheckedUnwrap;
+use typed_arena::Arena;
+use std::{iter, str};
+
+use common::{*, Error::*};
+use syntax::ast::*;
+use physics::*;
+use db::{Db, is_null};
+use crate::{predicate::one_predicate_with, InsertCtx};
+
+// one cond of `check (cond and ...)` over the whole row
+pub(crate) struct RowCheck<'a> {
+  // only used in the error message
+  text: String,
+  pred: Box<dyn Fn(*const u8) -> bool + 'a>,
+  // like in sql, a check whose result is unknown doesn't reject the row, so the cond passes if any of these cols is null
+  // empty for `is (not) null`, which is never unknown
+  cols: Vec<u32>,
+}
+
+unsafe fn row_checks<'a, 'b>(db: &'a Db, conds: &[Cond<'b>], tp: &TablePage) -> Result<'b, Vec<RowCheck<'a>>> {
+  let mut ret = Vec::with_capacity(conds.len());
+  for cond in conds {
+    let pred = one_predicate_with(db, cond, tp, tp.check_int_bool)?;
+    let cols = match cond {
+      Cond::Null(..) => vec![],
+      // comparing with null is always unknown, so the cond never rejects anything
+      Cond::Cmp(_, _, Atom::Lit(r)) | Cond::Mod(_, _, _, r) if r.is_null() => continue,
+      Cond::NotInList(_, l) if l.iter().any(|v| v.is_null()) => continue,
+      // there is no comparison, so it is never unknown
+      Cond::NotInList(_, l) if l.is_empty() => vec![],
+      _ => iter::once(cond.lhs_col()).chain(cond.rhs_col()).map(|c| tp.get_ci(c.col).unchecked_unwrap().idx(&tp.cols)).collect(),
+    };
+    ret.push(RowCheck { text: format!("{:?}", cond), pred, cols });
+  }
+  Ok(ret)
+}
+
+// the checks are stored as text in the check page (see `TablePage::check`), parse and compile them again
+pub(crate) unsafe fn table_row_checks<'a, 'b>(db: &'a Db, tp: &TablePage) -> Result<'b, Vec<RowCheck<'a>>> {
+  if tp.check == !0 { return Ok(vec![]); }
+  let cp = db.get_page::<CheckPage>(tp.check);
+  let check = str_from_parts(cp.data.as_ptr(), cp.count as usize);
+  let sql = format!("select * from {} where {};", tp.name(), check);
+  let alloc = Arena::default();
+  // `query::create_table` has validated them, so an error means the stored text is not what it wrote
+  // the errors borrow `sql`, so they are kept as text
+  let res = match syntax::work(&sql, &alloc) {
+    Ok(ss) => match ss.first() {
+      Some(Stmt::Select(s)) => row_checks(db, &s.where_, tp).map_err(|e| format!("{:?}", e)),
+      _ => impossible!()
+    }
+    Err(e) => Err(format!("{:?}", e)),
+  };
+  res.map_err(|reason| InvalidRowCheck { check: check.to_owned(), reason })
+}
+
+pub(crate) unsafe fn check_row<'a>(db: &Db, checks: &[RowCheck], tp: &TablePage, data: *const u8) -> Result<'a, ()> {
+  for c in checks {
+    if !(c.pred)(data) && c.cols.iter().all(|&ci_id| !is_null(data, ci_id)) {
+      let vals = c.cols.iter().map(|&ci_id| {
+        let ci = tp.cols.get_unchecked(ci_id as usize);
+        (ci.name(), db.data2lit(data, ci_id, ci).lit().into())
+      }).collect();
+      return Err(CheckFailed { check: c.text.clone(), vals });
+    }
+  }
+  Ok(())
+}
+
+// `Db::create_table` only validates the cols of checks over the row, the conds are compiled here to validate their types
+// if error occurs, the just created table is dropped
+pub fn create_table<'a>(db: &mut Db, c: &CreateTable<'a>) -> Result<'a, ()> {
+  db.create_table(c)?;
+  unsafe {
+    let tp = db.get_tp(c.table).unchecked_unwrap().1;
+    tp.check_int_bool = db.int_bool();
+    for cons in &c.cons {
+      if let ColCons::RowCheck(_, conds) = cons {
+        if let Err(e) = row_checks(db.pr(), conds, tp) {
+          db.drop_table(c.table).unchecked_unwrap();
+          return Err(e);
+        }
+      }
+    }
+  }
+  Ok(())
+}
+
+// the new table gets the cols (with their defaults and comments), primary key, unique cols, checks and indexes of `like`
+// like in sql, foreign keys are not copied; index names are unique in the db, so the copy of index x is named `table_x`
+// with `data`, the records of `like` are also copied, return the number of them
+pub fn create_table_like<'a>(db: &mut Db, table: &'a str, like: &'a str, data: bool) -> ModifyResult<'a, u32> {
+  unsafe {
+    // the names and values below point into the pages of `like`, creating a table doesn't modify them
+    let tp = db.get_tp(like)?.1;
+    // checked before the index names, which would also be used if `table` exists
+    if db.get_tp(table).is_ok() { return Err(DupTable(table).into()); }
+    let (mut cols, mut cons) = (Vec::with_capacity(tp.col_num as usize), vec![]);
+    let pks = tp.primary_cols().map(|ci| ci.name()).collect::<Vec<_>>();
+    if !pks.is_empty() { cons.push(ColCons::Primary(pks)); }
+    for ci in tp.cols() {
+      let mut dft = if ci.flags.contains(ColFlags::DFT_CURRENT_DATE) { Some(Dft::CurrentDate) } else { None };
+      if ci.check != !0 {
+        let cp = db.get_page::<CheckPage>(ci.check >> 1);
+        let (cnt, sz) = (cp.count as usize, ci.ty.size() as usize);
+        // the check values, then the default value in the one-past-last slot (see `InsertCtx::new`)
+        if cnt != 0 { cons.push(ColCons::Check(ci.name(), (0..cnt).map(|i| db.ptr2lit(cp.data.as_ptr().add(i * sz), ci.ty)).collect())); }
+        if (ci.check & 1) == 1 { dft = Some(Dft::Lit(db.ptr2lit(cp.data.as_ptr().add(cnt * sz), ci.ty))); }
+      }
+      if ci.flags.contains(ColFlags::UNIQUE) { cons.push(ColCons::Unique(ci.name())); }
+      cols.push(ColDecl { col: ci.name(), ty: ci.ty, notnull: ci.flags.contains(ColFlags::NOTNULL), dft, comment: db.comment(ci) });
+    }
+    // the stored text of the row checks is enough, they are compiled from it on each insert and update
+    if tp.check != !0 {
+      let cp = db.get_page::<CheckPage>(tp.check);
+      cons.push(ColCons::RowCheck(str_from_parts(cp.data.as_ptr(), cp.count as usize), vec![]));
+    }
+    let indexes = tp.cols().iter().filter_map(|ci| ci.idx_name().filter(|x| !x.is_empty()).map(|x| (ci.name(), format!("{}_{}", table, x)))).collect::<Vec<_>>();
+    for (_, index) in &indexes {
+      let used = db.dp().tables().iter().any(|&tp_id| db.get_page::<TablePage>(tp_id).cols().iter().any(|ci| ci.idx_name() == Some(index.as_str())));
+      if index.len() > MAX_IDX_NAME || used { return Err(LikeIndexName { table, like }.into()); }
+    }
+    create_table(db, &CreateTable { table, cols, cons })?;
+    // the copied text is compiled under the setting of `like`
+    db.get_tp(table).unchecked_unwrap().1.check_int_bool = tp.check_int_bool;
+    // the names are checked above, and the cols of `like` with a named index have no other index
+    for (col, index) in &indexes { index::create_index(db, &CreateIndex { index, table, col }).unchecked_unwrap(); }
+    if !data { return Ok(0); }
+    let mut ctx = InsertCtx::new(db, table, None)?;
+    let buf = Align4U8::new(ctx.tp.size as usize);
+    let (mut cnt, mut vals) = (0, Vec::with_capacity(tp.col_num as usize));
+    for (p, rid) in db.pr().record_iter(tp) {
+      let res = db.check_cancel().and_then(|_| db.check_record(tp, p, rid)).and_then(|_| {
+        vals.clear();
+        vals.extend(tp.cols().iter().enumerate().map(|(ci_id, ci)| db.data2lit(p, ci_id as u32, ci)));
+        ctx.insert(buf.ptr, &vals)
+      });
+      if let Err(e) = res { return Err(ModifyError(cnt, e)); }
+      cnt += 1;
+    }
+    Ok(cnt)
+  }
+}
@@ -0,0 +1,68 @@
+use common::{*, Error::*, BareTy::*, AggOp::*};
+use syntax::ast::*;
+use db::Db;
+use crate::{insert, select::{self, SelectResult}};
+
+// `with name as (select ...), ... select ...`: each cte is run in order, and its result is stored as a table of an in-memory db
+// a select whose from list refers to ctes runs on that db, so it only sees the earlier ctes, and it can't join normal tables (like catalog tables)
+pub(crate) unsafe fn select<'a, 'b>(s: &Select<'a>, db: &'b Db, mut analyze: Option<&mut select::Analyze<'a>>) -> Result<'a, SelectResult<'b>> {
+  let mut mem = box Db::open_memory()?;
+  mem.set_seed(db.seed());
+  for (idx, cte) in s.with.iter().enumerate() {
+    if cte.select.tables.contains(&cte.name) { return Err(RecursiveCte(cte.name)); }
+    let r = if refers(&cte.select, s.with.get_unchecked(..idx))? { select::select(&cte.select, (&*mem).pr())? } else { select::select(&cte.select, db.pr())? };
+    store(&mut mem, cte, &r)?;
+  }
+  if refers(s, &s.with)? {
+    let mut ret = select::select_main(s, (&*mem).pr(), &mut analyze)?;
+    // `ret` points into `mem`, so keep it alive
+    ret._catalog = Some(mem);
+    Ok(ret)
+  } else { select::select_main(s, db, &mut analyze) }
+}
+
+// whether the from list of `s` refers to `ctes`, if so, all its tables should be ctes
+fn refers<'a>(s: &Select<'a>, ctes: &[Cte<'a>]) -> Result<'a, bool> {
+  let is_cte = |t: &str| ctes.iter().any(|c| c.name == t);
+  if !s.tables.iter().any(|t| is_cte(t)) { return Ok(false); }
+  match s.tables.iter().find(|t| !is_cte(t)) { Some(&t) => Err(CteJoin(t)), None => Ok(true) }
+}
+
+// plain cols (and min / max of them) keep their types, other cols are typed by their values:
+// a number col is int if all values are integers in i32, otherwise float; a col of only nulls is int
+unsafe fn col_ty(r: &SelectResult, idx: usize) -> ColTy {
+  let col = r.cols.get_unchecked(idx);
+  if let Some((_, ci)) = col.ci { if col.op.map(|op| op == Min || op == Max).unwrap_or(true) { return ci.ty; } }
+  let fix = |ty| ColTy::FixTy(FixTy { ty, size: 0 });
+  let (mut ty, mut int, mut len) = (LitTy::Null, true, 1);
+  for row in 0..r.row_count() {
+    let lit = r.data.get_unchecked(row * r.cols.len() + idx).lit();
+    match lit {
+      Lit::Number(x) => int &= x.fract() == 0.0 && x.abs() <= i32::max_value() as f64,
+      Lit::Str(s) => len = len.max(s.len()),
+      _ => {}
+    }
+    if !lit.is_null() { ty = lit.ty(); }
+  }
+  match ty {
+    LitTy::Null => fix(Int), LitTy::Number => fix(if int { Int } else { Float }),
+    LitTy::Bool => fix(Bool), LitTy::Date => fix(Date), LitTy::Str => ColTy::Varchar(len as u16),
+  }
+}
+
+// create the table of `cte` in `mem`, and insert the rows of `r` into it
+unsafe fn store<'a>(mem: &mut Db, cte: &Cte<'a>, r: &SelectResult<'a>) -> Result<'a, ()> {
+  let names = r.col_names();
+  let cols = match &cte.cols {
+    Some(cols) if cols.len() != names.len() => return Err(CteCols(cte.name)),
+    Some(cols) => cols.clone(),
+    // `names` outlives the creation (which copies them), and the errors about col names are replaced below, so they never refer to `names`
+    None => names.iter().map(|x| str_from_parts(x.as_ptr(), x.len())).collect(),
+  };
+  let cols = cols.iter().enumerate().map(|(idx, &col)| ColDecl { col, ty: col_ty(r, idx), notnull: false, dft: None, comment: None }).collect();
+  mem.create_table(&CreateTable { table: cte.name, cols, cons: vec![] }).map_err(|e| match e { DupCol(_) | ColNameTooLong(_) => CteCols(cte.name), e => e })?;
+  // the rows come from a select, so their len and types always match the cols
+  let vals = r.data.chunks(r.cols.len()).map(|row| row.to_vec()).collect();
+  insert(&Insert { table: cte.name, cols: None, vals, on_conflict: None }, mem).map_err(|e| e.1)?;
+  Ok(())
+}
@@ -1,8 +1,9 @@
 use common::{*, BareTy::*};
 use syntax::ast::*;
+use physics::*;
 use db::{Db, is_null};
 use index::{Index, handle_all};
-use crate::{predicate::one_where, filter::filter, check_foreign_link};
+use crate::{predicate::one_where, filter::{filter, index_rids}, check_foreign_link};
 
 pub fn delete<'a>(d: &Delete<'a>, db: &mut Db) -> ModifyResult<'a, u32> {
   unsafe {
@@ -10,7 +11,8 @@ pub fn delete<'a>(d: &Delete<'a>, db: &mut Db) -> ModifyResult<'a, u32> {
     let f_links = db.foreign_links_to(tp_id).collect::<Vec<_>>();
     let pred = one_where(db.pr(), &d.where_, tp)?;
     let mut cnt = 0;
-    if let Err(e) = filter(db.pr(), &d.where_, tp_id, pred, |data, rid| {
+    // `db` and `tp` are passed in, so that they can still be used to find the records
+    let mut del = |db: &mut Db, tp: &mut TablePage, data: *mut u8, rid: Rid| -> Result<'a, ()> {
       check_foreign_link(db, tp, data, &f_links)?;
       // now no error can occur
       for (ci_id, ci) in tp.cols().iter().enumerate() {
@@ -27,6 +29,17 @@ pub fn delete<'a>(d: &Delete<'a>, db: &mut Db) -> ModifyResult<'a, u32> {
       cnt += 1;
       tp.count -= 1;
       Ok(())
-    }, false) { Err(ModifyError(cnt, e)) } else { Ok(cnt) }
+    };
+    // the index can't be modified while iterating it, so the records found by an index are collected before deleting them
+    // otherwise they are deleted during the scan (deleting the current record doesn't break the scan)
+    let res = match index_rids(db.pr(), &d.where_, tp_id, &pred) {
+      Ok(Some(rids)) => rids.into_iter().try_for_each(|rid| {
+        let data = db.get_data_slot(tp, rid);
+        del(db, tp, data, rid)
+      }),
+      Ok(None) => filter(db.pr(), &d.where_, tp_id, pred, |data, rid| del(db, tp, data, rid), false).map(|_| ()),
+      Err(e) => Err(e),
+    };
+    if let Err(e) = res { Err(ModifyError(cnt, e)) } else { Ok(cnt) }
   }
-}
\ No newline at end of file
+}
@@ -0,0 +1,107 @@
+use unchecked_unwrap::UncheckedUnwrap;
+use regex::Regex;
+use std::cmp::Ordering::*;
+
+use common::{*, Error::*, BinOp::*, CmpOp::*, BareTy::*};
+use syntax::ast::*;
+use db::Db;
+
+// the expressions in `update ... set` and `order by`, they are used on a single table, or on the rows of a select
+// `col` resolves a col ref to its type, it can also reject the col (e.g., a col not in group by)
+pub(crate) unsafe fn check<'a>(db: &Db, e: &Expr<'a>, col: &impl Fn(&ColRef<'a>) -> Result<'a, ColTy>, re_cache: &mut HashMap<&'a str, Regex>) -> Result<'a, LitTy> {
+  match e {
+    Expr::Atom(x) => Ok(match x {
+      Atom::Lit(x) => x.lit().ty(),
+      Atom::ColRef(cr) => match col(cr)? {
+        ColTy::FixTy(ty) => match ty.ty { Bool => LitTy::Bool, Int | Float => LitTy::Number, Date => LitTy::Date, Char => LitTy::Str },
+        varchar!() => LitTy::Str,
+      }
+    }),
+    Expr::Null(x, _) => (check(db, x, col, re_cache)?, Ok(LitTy::Bool)).1,
+    Expr::Like(x, like) => {
+      match check(db, x, col, re_cache)? { LitTy::Str => {} ty => return Err(InvalidLikeTy1(ty)) };
+      re_cache.insert(like, db::like2re(like, Collation::Binary)?); // expressions always compare in binary
+      Ok(LitTy::Bool)
+    }
+    Expr::And(box (l, r)) | Expr::Or(box (l, r)) => {
+      match check(db, l, col, re_cache)? { LitTy::Bool => {} ty => return Err(IncompatibleLogic(ty)) };
+      match check(db, r, col, re_cache)? { LitTy::Bool => {} ty => return Err(IncompatibleLogic(ty)) };
+      Ok(LitTy::Bool)
+    }
+    Expr::Cmp(op, box (l, r)) => {
+      let (l, r) = (check(db, l, col, re_cache)?, check(db, r, col, re_cache)?);
+      if l == r { Ok(LitTy::Bool) } else { Err(IncompatibleCmp { op: *op, l, r }) }
+    }
+    Expr::Bin(op, box (l, r)) => {
+      match check(db, l, col, re_cache)? { LitTy::Number => {} ty => return Err(IncompatibleBin { op: *op, ty }) };
+      match check(db, r, col, re_cache)? { LitTy::Number => {} ty => return Err(IncompatibleBin { op: *op, ty }) };
+      Ok(LitTy::Number)
+    }
+    &Expr::Call(name, ref args) => {
+      // `random()` is built in, a registered function of the same name replaces it
+      let (arity, ret) = match db.function(name) {
+        Some(f) => (f.arity, f.ret),
+        None if name == "random" => (0, LitTy::Number),
+        None => return Err(NoSuchFunction(name)),
+      };
+      if args.len() != arity { return Err(FunctionArity { name, expect: arity, actual: args.len() }); }
+      for a in args { check(db, a, col, re_cache)?; }
+      Ok(ret)
+    }
+  }
+}
+
+// `col` gives the value of a col ref in the current row, `e` should have passed `check`
+// if one of the operand is null, the result is null (including comparison, e.g., (null = null) evaluates to null, instead of false in select)
+// the only exception is "is (not) null" check, it always return bool
+// if arithmetic result is NaN, the result is null
+// only a user-defined function can fail, see `Db::register_function`
+pub(crate) unsafe fn eval<'a>(db: &Db, e: &Expr<'a>, col: &impl Fn(&ColRef<'a>) -> Lit<'a>, re_cache: &HashMap<&'a str, Regex>) -> Result<'a, Lit<'a>> {
+  Ok(match e {
+    Expr::Atom(x) => match x { Atom::Lit(x) => x.lit(), Atom::ColRef(cr) => col(cr) },
+    Expr::Null(x, null) => Lit::Bool(eval(db, x, col, re_cache)?.is_null() == *null),
+    Expr::Like(x, like) => {
+      let re = re_cache.get(like).unchecked_unwrap();
+      let x = match eval(db, x, col, re_cache)? { Lit::Str(x) => x, _ => return Ok(Lit::Null) };
+      Lit::Bool(re.is_match(x))
+    }
+    Expr::And(box (l, r)) | Expr::Or(box (l, r)) => {
+      let or = if let Expr::Or(_) = e { true } else { false };
+      let l = match eval(db, l, col, re_cache)? { Lit::Bool(x) => x, _ => return Ok(Lit::Null) };
+      if or == l { return Ok(Lit::Bool(l)); } // short circuit, true or _ / false and _
+      // now it is false or _ / true and _, the result only depends on `r`
+      let r = match eval(db, r, col, re_cache)? { Lit::Bool(x) => x, _ => return Ok(Lit::Null) };
+      Lit::Bool(r)
+    }
+    Expr::Cmp(op, box (l, r)) => {
+      let l = eval(db, l, col, re_cache)?;
+      let r = eval(db, r, col, re_cache)?;
+      if l.is_null() || r.is_null() { return Ok(Lit::Null); };
+      let cmp = l.cmp(&r); // `check` and null check above guarantees they have the same type
+      Lit::Bool(match op { Lt => cmp == Less, Le => cmp != Greater, Ge => cmp != Less, Gt => cmp == Greater, Eq => cmp == Equal, Ne => cmp != Equal })
+    }
+    Expr::Bin(op, box (l, r)) => {
+      // since we cannot have type mismatch here, if it is not Number, it can only be Null
+      let l = match eval(db, l, col, re_cache)? { Lit::Number(x) => x, _ => return Ok(Lit::Null) };
+      let r = match eval(db, r, col, re_cache)? { Lit::Number(x) => x, _ => return Ok(Lit::Null) };
+      let res = match op { Add => l + r, Sub => l - r, Mul => l * r, Div => l / r, Mod => l % r, };
+      if res.is_nan() { Lit::Null } else { Lit::Number(res) }
+    }
+    // `check` has found the function (or it is `random()`), and checked its arity
+    &Expr::Call(name, ref args) => {
+      let f = match db.function(name) { Some(f) => f, None => return Ok(Lit::Number(db.random())) };
+      let mut vals = Vec::<LitExt>::with_capacity(args.len());
+      for a in args {
+        match eval(db, a, col, re_cache)? { Lit::Null => return Ok(Lit::Null), x => vals.push(x.into()) }
+      }
+      // a result not of `ret` type would break the type check above
+      match ((f.f)(&vals)?, f.ret) {
+        (LitExt::Null, _) => Lit::Null,
+        (LitExt::Bool(x), LitTy::Bool) => Lit::Bool(x),
+        (LitExt::Number(x), LitTy::Number) => if x.is_nan() { Lit::Null } else { Lit::Number(x) },
+        (LitExt::Date(x), LitTy::Date) => Lit::Date(x),
+        (actual, expect) => return Err(FunctionRetMismatch { name, expect, actual }),
+      }
+    }
+  })
+}
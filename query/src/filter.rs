@@ -6,12 +6,47 @@ use syntax::ast::*;
 use physics::*;
 use db::Db;
 use index::{Index, handle_all};
+use crate::predicate::one_where;
 
-// return true for successfully filtered with index
+// an in list longer than this is filtered by scanning, since a seek for each value costs more than a scan
+const MAX_IN_SEEKS: usize = 64;
+
+// return the number of index seeks for successfully filtered with index, None for not using index
 unsafe fn try_filter_with_index<'a>(db: &mut Db, where_: &[impl Borrow<Cond<'a>>], tp_id: u32,
-                                    pred: &impl Fn(*const u8) -> bool, f: &mut impl FnMut(*mut u8, Rid) -> Result<'a, ()>) -> Result<'a, bool> {
+                                    pred: &impl Fn(*const u8) -> bool, f: &mut impl FnMut(*mut u8, Rid) -> Result<'a, ()>) -> Result<'a, Option<u32>> {
   let tp = db.get_page::<TablePage>(tp_id);
+  let is_only_pred = where_.len() == 1;
   for cond in where_ {
+    if let &Cond::InList(l, ref vals) = cond.borrow() {
+      let ci = tp.pr().get_ci(l.col).unchecked_unwrap();
+      if ci.index != !0 && db.index_usable(ci) && vals.len() <= MAX_IN_SEEKS {
+        let ci_id = ci.idx(&tp.cols);
+        let buf = Align4U8::new(ci.ty.size() as usize);
+        // the same value may appear more than once in the list, the rids of all values are unioned
+        let mut rids = HashSet::new();
+        let mut seeks = 0;
+        for &r in vals {
+          // null and values out of the col's range match nothing
+          if r.is_null() || Db::lit2ptr_ck(ci.ty.fix_ty(), r).is_err() { continue; }
+          db.lit2ptr(buf.ptr, ci.ty.fix_ty(), r).unchecked_unwrap();
+          seeks += 1;
+          macro_rules! handle {
+            ($ty: ident) => {{
+              let mut index = Index::<{ $ty }>::new(db, tp_id, ci_id);
+              let (mut it, end) = (index.lower_bound(buf.ptr), index.upper_bound(buf.ptr));
+              while it != end {
+                let rid = it.next().unchecked_unwrap();
+                let ptr = db.get_data_slot(tp, rid);
+                db.check_record(tp, ptr, rid)?;
+                if rids.insert(rid) && (is_only_pred || pred(ptr)) { f(ptr, rid)?; }
+              }
+            }};
+          }
+          handle_all!(ci.ty.fix_ty().ty, handle);
+        }
+        return Ok(Some(seeks));
+      }
+    }
     if let &Cond::Cmp(op, l, Atom::Lit(r)) = cond.borrow() {
       match r.lit() {
         Lit::Null => {}
@@ -19,9 +54,9 @@ unsafe fn try_filter_with_index<'a>(db: &mut Db, where_: &[impl Borrow<Cond<'a>>
           // safe because `one_predicate` have verified the name
           let ci = tp.pr().get_ci(l.col).unchecked_unwrap();
           let ci_id = ci.idx(&tp.cols);
-          if ci.index != !0 {
+          // if `r` is out of the col's range, it can't be written to `buf` to search the index
+          if ci.index != !0 && db.index_usable(ci) && Db::lit2ptr_ck(ci.ty.fix_ty(), r).is_ok() {
             let buf = Align4U8::new(ci.ty.size() as usize);
-            let is_only_pred = where_.len() == 1;
             // safe because `one_predicate` have done type check
             db.lit2ptr(buf.ptr, ci.ty.fix_ty(), r).unchecked_unwrap();
             macro_rules! handle {
@@ -38,6 +73,7 @@ unsafe fn try_filter_with_index<'a>(db: &mut Db, where_: &[impl Borrow<Cond<'a>>
                     while it != end {
                       let rid = it.next().unchecked_unwrap();
                       let ptr = db.get_data_slot(tp, rid);
+                      db.check_record(tp, ptr, rid)?;
                       if is_only_pred || pred(ptr) { f(ptr, rid)?; }
                     }
                   },
@@ -45,6 +81,7 @@ unsafe fn try_filter_with_index<'a>(db: &mut Db, where_: &[impl Borrow<Cond<'a>>
                     let mut it = if op == Ge { index.lower_bound(buf.ptr) } else { index.upper_bound(buf.ptr) };
                     while let Some(rid) = it.next() {
                       let ptr = db.get_data_slot(tp, rid);
+                      db.check_record(tp, ptr, rid)?;
                       if is_only_pred || pred(ptr) { f(ptr, rid)?; }
                     }
                   },
@@ -53,24 +90,101 @@ unsafe fn try_filter_with_index<'a>(db: &mut Db, where_: &[impl Borrow<Cond<'a>>
               }};
             }
             handle_all!(ci.ty.fix_ty().ty, handle);
-            return Ok(true);
+            return Ok(Some(1));
           }
         }
       }
     }
   }
-  Ok(false)
+  Ok(None)
 }
 
 // guarantee the `*mut u8` passed to f only comes from DataPage, not from IndexPage
 // if you want to modify index while iterating, you CANNOT modify while iterating, remember to set `use_index` = false
 // if you want to delete the current data slot from data page while iterating, you CAN delete while iterating (due to the implementation)
+// `pred` should be the predicate of `where_`, so an empty `where_` accepts all records without calling `pred`
+// return the number of index seeks, 0 for a full scan
 pub(crate) unsafe fn filter<'a>(db: &mut Db, where_: &[impl Borrow<Cond<'a>>], tp_id: u32,
                                 pred: impl Fn(*const u8) -> bool, mut f: impl FnMut(*mut u8, Rid) -> Result<'a, ()>,
-                                use_index: bool) -> Result<'a, ()> {
-  if !use_index || !try_filter_with_index(db, where_, tp_id, &pred, &mut f)? {
-    let tp = db.get_page::<TablePage>(tp_id);
-    for (data, rid) in db.record_iter(tp) { if pred(data) { f(data, rid)?; } }
+                                use_index: bool) -> Result<'a, u32> {
+  if use_index {
+    if let Some(seeks) = try_filter_with_index(db, where_, tp_id, &pred, &mut f)? { return Ok(seeks); }
   }
-  Ok(())
-}
\ No newline at end of file
+  let tp = db.get_page::<TablePage>(tp_id);
+  if where_.is_empty() {
+    for (data, rid) in db.record_iter(tp) {
+      db.check_cancel()?;
+      db.check_record(tp, data, rid)?;
+      f(data, rid)?;
+    }
+  } else {
+    for (data, rid) in db.record_iter(tp) {
+      db.check_cancel()?;
+      db.check_record(tp, data, rid)?;
+      if pred(data) { f(data, rid)?; }
+    }
+  }
+  Ok(0)
+}
+
+// the rids of the records in `tp_id` accepted by `where_` (and `pred`, its predicate), if they can be found by an index
+// only the matched part of the index is visited, and the caller can modify the index after it returns, unlike in `filter`
+// return None if no cond can use an index, then nothing is visited
+pub(crate) unsafe fn index_rids<'a>(db: &mut Db, where_: &[impl Borrow<Cond<'a>>], tp_id: u32, pred: impl Fn(*const u8) -> bool) -> Result<'a, Option<Vec<Rid>>> {
+  let mut rids = Vec::new();
+  let used = try_filter_with_index(db, where_, tp_id, &pred, &mut |_, rid| (rids.push(rid), Ok(())).1)?;
+  Ok(used.map(|_| rids))
+}
+
+// the number of records in `tp_id` accepted by `where_` (they are only counted, not collected), and the number of index seeks
+pub(crate) unsafe fn count<'a>(db: &mut Db, where_: &[Cond<'a>], tp_id: u32) -> Result<'a, (usize, u32)> {
+  let pred = one_where(db.pr(), where_, db.get_page::<TablePage>(tp_id))?;
+  let mut cnt = 0;
+  let seeks = filter(db, where_, tp_id, pred, |_, _| (cnt += 1, Ok(())).1, true)?;
+  Ok((cnt, seeks))
+}
+
+// scan the table in the ascending order of the index on `ci_id`, stop after `limit` records are accepted by `pred`
+// the start (and end) position comes from the first comparison between this col and a non-null literal in `where_`
+// e.g. for keyset pagination `where id > last_id order by id limit n`, it costs O(log n + limit)
+// null is not in index, so the caller should guarantee the col has an index and null is rejected by `where_` or the col is not null
+// return the number of records read from the index (accepted or not)
+pub(crate) unsafe fn filter_ordered<'a>(db: &mut Db, where_: &[impl Borrow<Cond<'a>>], tp_id: u32, ci_id: u32, limit: usize,
+                                        pred: impl Fn(*const u8) -> bool, mut f: impl FnMut(*mut u8, Rid) -> Result<'a, ()>) -> Result<'a, usize> {
+  let tp = db.get_page::<TablePage>(tp_id);
+  let ci = tp.cols().get_unchecked(ci_id as usize);
+  let bound = where_.iter().filter_map(|cond| match *cond.borrow() {
+    Cond::Cmp(op, l, Atom::Lit(r)) if op != Ne && !r.is_null() && l.col == ci.name() && Db::lit2ptr_ck(ci.ty.fix_ty(), r).is_ok() => Some((op, r)),
+    _ => None,
+  }).next();
+  let no_pred = where_.is_empty(); // the same as `filter`
+  let buf = Align4U8::new(ci.ty.size() as usize);
+  let mut read = 0;
+  // safe because `one_predicate` have done type check
+  if let Some((_, r)) = bound { db.lit2ptr(buf.ptr, ci.ty.fix_ty(), r).unchecked_unwrap(); }
+  macro_rules! handle {
+    ($ty: ident) => {{
+      let mut index = Index::<{ $ty }>::new(db, tp_id, ci_id);
+      let (mut it, end) = match bound.map(|(op, _)| op) {
+        Some(Lt) => (index.iter(), Some(index.lower_bound(buf.ptr))),
+        Some(Le) => (index.iter(), Some(index.upper_bound(buf.ptr))),
+        Some(Eq) => (index.lower_bound(buf.ptr), Some(index.upper_bound(buf.ptr))),
+        Some(Ge) => (index.lower_bound(buf.ptr), None),
+        Some(Gt) => (index.upper_bound(buf.ptr), None),
+        Some(Ne) => impossible!(),
+        None => (index.iter(), None),
+      };
+      let mut cnt = 0;
+      while cnt < limit && Some(&it) != end.as_ref() {
+        let rid = if let Some(rid) = it.next() { rid } else { break; };
+        read += 1;
+        db.check_cancel()?;
+        let ptr = db.get_data_slot(tp, rid);
+        db.check_record(tp, ptr, rid)?;
+        if no_pred || pred(ptr) { (f(ptr, rid)?, cnt += 1); }
+      }
+    }};
+  }
+  handle_all!(ci.ty.fix_ty().ty, handle);
+  Ok(read)
+}
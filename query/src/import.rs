@@ -0,0 +1,112 @@
+use unchecked_unwrap::UncheckedUnwrap;
+use typed_arena::Arena;
+use std::str;
+
+use common::{*, BareTy::*, Error::*};
+use physics::*;
+use db::Db;
+use crate::insert::{InsertCtx, empty_is_null};
+
+// a minimal csv reader, accepting the format produced by `SelectResult::csv`
+struct Csv<'a> {
+  s: &'a str,
+  pos: usize,
+  alloc: &'a Arena<u8>,
+}
+
+impl<'a> Csv<'a> {
+  fn peek(&self) -> Option<u8> { self.s.as_bytes().get(self.pos).copied() }
+
+  // return None if there is no more record, empty lines are skipped
+  // an empty unquoted field is None (null), an empty quoted field ("") is an empty string
+  fn record(&mut self) -> Option<Vec<Option<&'a str>>> {
+    while let Some(b'\r') | Some(b'\n') = self.peek() { self.pos += 1; }
+    if self.pos >= self.s.len() { return None; }
+    let mut ret = vec![self.field()];
+    while self.peek() == Some(b',') {
+      self.pos += 1;
+      ret.push(self.field());
+    }
+    Some(ret)
+  }
+
+  fn field(&mut self) -> Option<&'a str> {
+    let ret = if self.peek() == Some(b'"') {
+      self.pos += 1;
+      let mut buf = Vec::new();
+      loop {
+        match self.peek() {
+          Some(b'"') => {
+            self.pos += 1;
+            if self.peek() == Some(b'"') { (buf.push(b'"'), self.pos += 1); } else { break; }
+          }
+          Some(ch) => (buf.push(ch), self.pos += 1).0,
+          None => break, // unclosed quote, just take the rest
+        }
+      }
+      // only '"'s are removed from a valid utf-8 string, so it is still valid
+      Some(unsafe { str::from_utf8_unchecked(self.alloc.alloc_extend(buf)) })
+    } else { None };
+    let start = self.pos;
+    while let Some(ch) = self.peek() {
+      if ch == b',' || ch == b'\r' || ch == b'\n' { break; }
+      self.pos += 1;
+    }
+    // for a quoted field, the characters after the closing quote are ignored
+    ret.or_else(|| if start == self.pos { None } else { Some(unsafe { self.s.get_unchecked(start..self.pos) }) })
+  }
+}
+
+// the conversion of csv field to lit, according to the col type; type errors are detected here, so that the col can be reported
+// with `Db::empty_null`, a quoted empty field ("") is null for a non-string col; with `Db::int_bool`, an int field is accepted for a bool col
+fn field2lit<'a>(field: Option<&'a str>, ty: ColTy, db: &Db) -> Result<'a, CLit<'a>> {
+  let mismatch = |s| ColLitMismatch { ty, val: CLit::new(Lit::Str(s)) };
+  Ok(CLit::new(match (field, ty) {
+    (None, _) => Lit::Null,
+    (Some(s), _) if db.empty_null() && empty_is_null(ty, Lit::Str(s)) => Lit::Null,
+    (Some(s), bool!()) => match s {
+      "true" => Lit::Bool(true), "false" => Lit::Bool(false),
+      _ => match s.parse::<i32>() { Ok(v) if db.int_bool() => Lit::Bool(v != 0), _ => return Err(mismatch(s)) }
+    },
+    (Some(s), int!()) => Lit::Number(s.parse::<i32>().map_err(|_| mismatch(s))? as f64),
+    (Some(s), float!()) => Lit::Number(s.parse::<f32>().map_err(|_| mismatch(s))? as f64),
+    (Some(s), date!()) => Lit::Date(db::date(s)?),
+    (Some(s), _) => Lit::Str(s),
+  }))
+}
+
+// the first record of `csv` is the header, which contains the names of the cols to import (like `insert into table (cols)`)
+// a record with fewer fields than the header is padded with nulls, other checks are the same as `insert`
+// the `row` in ImportError starts from 1, not counting the header
+pub fn import<'a>(csv: &'a str, table: &'a str, db: &mut Db, alloc: &'a Arena<u8>) -> ModifyResult<'a, u32> {
+  unsafe {
+    let mut csv = Csv { s: csv, pos: 0, alloc };
+    let header = csv.record().unwrap_or_default();
+    let mut cols = Vec::with_capacity(header.len());
+    for col in header {
+      if let Some(col) = col { cols.push(col); } else { return Err(NoSuchCol("").into()); }
+    }
+    let mut ctx = InsertCtx::new(db, table, Some(cols.as_slice()))?;
+    let cis = cols.iter().map(|&col| ctx.tp.get_ci(col).unchecked_unwrap()).collect::<Vec<_>>(); // checked in `InsertCtx::new`
+    let buf = Align4U8::new(ctx.tp.size as usize);
+    let mut cnt = 0;
+    let mut vals = Vec::with_capacity(cols.len());
+    while let Some(mut fields) = csv.record() {
+      let row = cnt + 1;
+      if fields.len() > cols.len() { return Err(ModifyError(cnt, ImportError { row, col: None, reason: box InsertTooLong { max: cols.len(), actual: fields.len() } })); }
+      fields.resize(cols.len(), None);
+      vals.clear();
+      for (&field, ci) in fields.iter().zip(cis.iter()) {
+        let col = Some(ci.name());
+        let val = match field2lit(field, ci.ty, db) { Ok(val) => val, Err(e) => return Err(ModifyError(cnt, ImportError { row, col, reason: box e })) };
+        if val.is_null() && ci.flags.intersects(ColFlags::NOTNULL1) {
+          return Err(ModifyError(cnt, ImportError { row, col, reason: box PutNullOnNotNull }));
+        }
+        vals.push(val);
+      }
+      if let Err(e) = ctx.insert(buf.ptr, &vals) { return Err(ModifyError(cnt, ImportError { row, col: None, reason: box e })); }
+      cnt += 1;
+    }
+    Ok(cnt)
+  }
+}
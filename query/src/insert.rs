@@ -6,6 +6,21 @@ use syntax::ast::*;
 use physics::*;
 use index::{Index, cmp::Cmp, handle_all};
 use db::{Db, is_null, hash_pks};
+use crate::{select::select, update::Sets, check::{RowCheck, table_row_checks, check_row}};
+
+// the values that `Db::empty_null` turns into null, import also uses it
+pub(crate) fn empty_is_null(ty: ColTy, val: Lit) -> bool {
+  match (ty, val) { (char!(), _) | (varchar!(), _) => false, (_, Lit::Str(s)) => s.is_empty(), _ => false }
+}
+
+// the prefix of a string too long for a char or varchar col that `Db::truncate_str` keeps, update also uses it
+pub(crate) fn truncate_str(ty: ColTy, val: Lit) -> Option<Lit> {
+  let size = match ty { char!(size) => size as usize, varchar!(size) => size as usize, _ => return None };
+  match val {
+    Lit::Str(s) if s.len() > size => Some(Lit::Str(&s[..(0..=size).rev().find(|&i| s.is_char_boundary(i)).unwrap_or(0)])),
+    _ => None,
+  }
+}
 
 // update can also use this
 pub(crate) struct InsertCtx<'a> {
@@ -13,10 +28,12 @@ pub(crate) struct InsertCtx<'a> {
   pub(crate) tp_id: u32,
   pub(crate) tp: &'a mut TablePage,
   pub(crate) pks: Vec<&'a ColInfo>,
+  // only used for composite primary key; a single primary col is `unique`, so it is checked with its index in `check_col`
   pub(crate) pk_set: HashSet<u128>,
   // these 2 not used in update (it may be a little waste, but is acceptable)
   cols: Option<Box<[u32]>>,
   dfts: Box<[CLit<'a>]>,
+  row_checks: Vec<RowCheck<'a>>,
 }
 
 impl<'a> InsertCtx<'a> {
@@ -39,9 +56,12 @@ impl<'a> InsertCtx<'a> {
         let cp = db.get_page::<CheckPage>(ci.check >> 1);
         let ptr = cp.data.as_ptr().add(cp.count as usize * ci.ty.size() as usize); // the one-past-last slot
         *dfts.get_unchecked_mut(idx) = db.ptr2lit(ptr, ci.ty);
+      } else if ci.flags.contains(ColFlags::DFT_CURRENT_DATE) {
+        *dfts.get_unchecked_mut(idx) = CLit::new(Lit::Date(today())); // so all records in one insert get the same date
       }
     }
-    Ok(InsertCtx { db: db.pr(), tp, tp_id, pks, pk_set, cols, dfts })
+    let row_checks = table_row_checks(db.pr(), tp)?;
+    Ok(InsertCtx { db: db.pr(), tp, tp_id, pks, pk_set, cols, dfts, row_checks })
   }
 
   // result's len == table's col num
@@ -66,8 +86,26 @@ impl<'a> InsertCtx<'a> {
     }
   }
 
-  unsafe fn insert(&mut self, buf: *mut u8, vals: &[CLit<'a>]) -> Result<'a, ()> {
-    let vals = self.get_insert_val(vals)?;
+  pub(crate) unsafe fn insert(&mut self, buf: *mut u8, vals: &[CLit<'a>]) -> Result<'a, ()> {
+    let vals = self.fill(buf, vals)?;
+    self.insert_filled(buf, &vals)
+  }
+
+  // write the non-varchar fields of `vals` to `buf`, the returned values are those of all cols
+  unsafe fn fill<'c>(&mut self, buf: *mut u8, vals: &'c [CLit<'a>]) -> Result<'a, Cow<'c, [CLit<'a>]>> {
+    let mut vals = self.get_insert_val(vals)?;
+    let (empty_null, int_bool, truncate) = (self.db.empty_null(), self.db.int_bool(), self.db.truncate_str());
+    if empty_null || int_bool || truncate {
+      for ci_id in 0..vals.len() {
+        let v = match (self.tp.cols.get_unchecked(ci_id).ty, vals.get_unchecked(ci_id).lit()) {
+          (bool!(), Lit::Number(v)) if int_bool => Lit::Bool(v != 0.0),
+          (ty, v) if empty_null && empty_is_null(ty, v) => Lit::Null,
+          (ty, v) if truncate => match truncate_str(ty, v) { Some(v) => v, None => continue },
+          _ => continue,
+        };
+        *vals.to_mut().get_unchecked_mut(ci_id) = CLit::new(v);
+      }
+    }
     (buf as *mut u32).write_bytes(0, (vals.len() + 31) / 32); // clear null-bitset
     for (ci_id, &val) in vals.iter().enumerate() {
       let ci = self.tp.cols.get_unchecked(ci_id);
@@ -78,14 +116,20 @@ impl<'a> InsertCtx<'a> {
         self.db.lit2ptr(buf.add(ci.off as usize), ci.ty.fix_ty(), val)?;
       }
     }
+    Ok(vals)
+  }
+
+  // `buf` and `vals` come from `fill`
+  unsafe fn insert_filled(&mut self, buf: *mut u8, vals: &[CLit<'a>]) -> Result<'a, ()> {
     for ci_id in 0..self.tp.col_num as u32 {
       self.check_col(buf, ci_id, *vals.get_unchecked(ci_id as usize), None)?;
     }
     if self.pks.len() > 1 {
       if !self.pk_set.insert(hash_pks(buf, &self.pks)) { return Err(PutDupOnPrimary); }
     }
+    self.check_row(buf)?;
     // now fill varchar fields, unlike non-varchar fields:
-    // 1. they never affect the result of `check_col` and `pk_set`
+    // 1. they never affect the result of `check_col`, `pk_set` and `check_row`
     // 2. if one varchar field is written, the whole insertion must succeed (otherwise need to deallocate the space, which is not handled currently)
     for (ci_id, &val) in vals.iter().enumerate() {
       if !val.is_null() { Db::varchar_ck(self.tp.cols.get_unchecked(ci_id).ty, val)?; }
@@ -115,6 +159,21 @@ impl<'a> InsertCtx<'a> {
     Ok(())
   }
 
+  // the record with the same value of the unique col `ci_id` as `data`, null never conflicts
+  unsafe fn find_dup(&mut self, data: *const u8, ci_id: u32) -> Option<Rid> {
+    if is_null(data, ci_id) { return None; }
+    let ci = self.tp.cols.get_unchecked(ci_id as usize);
+    let ptr = data.add(ci.off as usize);
+    macro_rules! handle {
+      ($ty: ident) => {{
+        let mut index = Index::<{ $ty }>::new(self.db, self.tp_id, ci_id);
+        let (mut it, end) = (index.lower_bound(ptr), index.upper_bound(ptr));
+        if it != end { it.next() } else { None }
+      }};
+    }
+    handle_all!(ci.ty.fix_ty().ty, handle)
+  }
+
   // `rid` is used for unique check, if rid is Some && a rid `rid1` is found in Index && `rid1` is equal to `rid`, it is not regarded as a duplicate
   // the return value's life time can't come from `data`, because `data` are on the stack in all usage
   // varchar fields never affect the result of `check_col`, so caller can first write non-varchar fields, then call `check_col`, then write varchar fields
@@ -155,17 +214,78 @@ impl<'a> InsertCtx<'a> {
     }
     Ok(())
   }
+
+  // the checks over the whole row, all non-varchar fields of `data` should have been written
+  pub(crate) unsafe fn check_row(&self, data: *const u8) -> Result<'a, ()> { check_row(self.db, &self.row_checks, self.tp, data) }
 }
 
 pub fn insert<'a>(i: &Insert<'a>, db: &mut Db) -> ModifyResult<'a, u32> {
   unsafe {
     let mut ctx = InsertCtx::new(db, i.table, i.cols.as_deref())?;
+    // rows shorter than the cols are filled by default values (see `get_insert_val`), but they should have the same len
+    let max = if let Some(cols) = &ctx.cols { cols.len() } else { ctx.tp.col_num as usize };
+    let expect = i.vals.first().map(|x| x.len().min(max)).unwrap_or(0);
+    if let Some((row, vals)) = i.vals.iter().enumerate().find(|(_, x)| x.len() != expect) {
+      return Err(ModifyError(0, InsertRowLen { row: row + 1, expect, actual: vals.len() }));
+    }
     let buf = Align4U8::new(ctx.tp.size as usize);
     let mut cnt = 0;
-    for vals in &i.vals {
+    match &i.on_conflict {
+      Some(oc) => if let Err(e) = upsert(db, &mut ctx, oc, &i.vals, buf.ptr, &mut cnt) { return Err(ModifyError(cnt, e)); }
+      None => for vals in &i.vals {
+        if let Err(e) = ctx.insert(buf.ptr, vals) { return Err(ModifyError(cnt, e)); }
+        cnt += 1;
+      }
+    }
+    Ok(cnt)
+  }
+}
+
+// a row whose conflict target col is duplicate is skipped (do nothing), or used to update the existing record (do update)
+// both inserted and updated rows are counted in `cnt`
+unsafe fn upsert<'a>(db: &mut Db, ctx: &mut InsertCtx<'a>, oc: &OnConflict<'a>, rows: &[Vec<CLit<'a>>], buf: *mut u8, cnt: &mut u32) -> Result<'a, ()> {
+  let target = ctx.tp.get_ci(oc.col)?;
+  if !target.unique(ctx.pks.len()) { return Err(ConflictNotUnique(oc.col)); }
+  let target = target.idx(&ctx.tp.cols);
+  let (tp, rdb) = (&*ctx.tp.pr(), &*db.pr());
+  // `excluded.col` is the col of the row being inserted, other col refs are those of the existing record
+  let ty = |col: &ColRef<'a>| {
+    if let Some(t) = col.table { if t != tp.name() && t != "excluded" { return Err(NoSuchTable(t)); } }
+    Ok(tp.pr().get_ci(col.col)?.ty)
+  };
+  let mut sets = match &oc.sets { Some(sets) => Some(Sets::new(db, ctx, sets, &ty)?), None => None };
+  let old = Align4U8::new(tp.size as usize); // `Sets::apply` needs a buf, while `buf` holds the inserted row
+  for vals in rows {
+    let vals = ctx.fill(buf, vals)?;
+    match ctx.find_dup(buf, target) {
+      Some(rid) => if let Some(sets) = &mut sets {
+        let data = db.get_data_slot(tp, rid);
+        let col = |col: &ColRef<'a>| {
+          let ci = tp.pr().get_ci(col.col).unchecked_unwrap();
+          let ci_id = ci.idx(&tp.cols);
+          if col.table == Some("excluded") { vals.get_unchecked(ci_id as usize).lit() } else { rdb.data2lit(data, ci_id, ci).lit() }
+        };
+        sets.apply(db, ctx, old.ptr, data, rid, &col)?;
+        *cnt += 1;
+      }
+      None => (ctx.insert_filled(buf, &vals)?, *cnt += 1).1,
+    }
+  }
+  Ok(())
+}
+// run the select first, so the result rows are fixed before inserting (even if inserting into the same table)
+// rows are inserted in the same way as `insert`, so `cols` and type check work the same
+pub fn insert_select<'a>(i: &InsertSelect<'a>, db: &mut Db) -> ModifyResult<'a, u32> {
+  unsafe {
+    let result = select(&i.select, db.pr())?;
+    let mut ctx = InsertCtx::new(db, i.table, i.cols.as_deref())?;
+    let buf = Align4U8::new(ctx.tp.size as usize);
+    let mut cnt = 0;
+    for row in 0..result.row_count() {
+      let vals = result.data.get_unchecked(row * result.cols.len()..(row + 1) * result.cols.len());
       if let Err(e) = ctx.insert(buf.ptr, vals) { return Err(ModifyError(cnt, e)); }
       cnt += 1;
     }
     Ok(cnt)
   }
-}
\ No newline at end of file
+}
@@ -7,10 +7,15 @@ pub mod insert;
 pub mod delete;
 pub mod select;
 pub mod update;
+pub mod import;
+pub mod catalog;
+mod cte;
 mod predicate;
 mod filter;
+mod check;
+mod expr;
 
-pub use crate::{insert::*, delete::*, select::*, update::*};
+pub use crate::{insert::*, delete::*, select::*, update::*, import::*, check::{create_table, create_table_like}};
 
 use db::{Db, is_null};
 use physics::*;
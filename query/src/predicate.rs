@@ -1,6 +1,9 @@
 use chrono::NaiveDate;
+use unchecked_unwrap::UncheckedUnwrap;
+use std::{collections::HashSet, cell::RefCell, cmp::Ordering};
+use regex::Regex;
 
-use common::{*, Error::*, BareTy::*, CmpOp::*};
+use common::{*, Error::*, BareTy::*, CmpOp::*, AggOp::*};
 use syntax::ast::*;
 use physics::*;
 use db::{is_null, Db};
@@ -14,15 +17,44 @@ macro_rules! handle_op {
   };
 }
 
+// the values of `in (select ...)`, they are copied out, so modifying the table of select (e.g., in delete) doesn't affect them
+#[derive(Hash, Eq, PartialEq)]
+enum InKey { Bool(bool), Number(u64), Date(NaiveDate), Str(Box<str>) }
+
+impl InKey {
+  // `lit` should not be null
+  unsafe fn new(lit: Lit, collation: Collation) -> InKey {
+    match lit {
+      Lit::Bool(x) => InKey::Bool(x), Lit::Number(x) => InKey::Number((x + 0.0).to_bits()), // -0.0 == 0.0
+      Lit::Date(x) => InKey::Date(x), Lit::Str(x) => InKey::Str(collation.key(x).into()), Lit::Null => impossible!(),
+    }
+  }
+}
+
+fn lit_ty(ty: ColTy) -> LitTy {
+  match ty { bool!() => LitTy::Bool, int!() | float!() => LitTy::Number, date!() => LitTy::Date, char!() | varchar!() => LitTy::Str }
+}
+
+// the string in a char or varchar col, `p` points to the col in a record
+// comparisons between string cols, in one table or across tables, read both sides through it, so char and varchar are treated the same way
+// char is stored as written (it is not padded), so a char and a varchar with the same content are equal
+unsafe fn col_str<'a>(db: &'a Db, ty: ColTy, p: *const u8) -> &'a str { if ty.is_varchar() { db.varchar(p) } else { str_from_db(p) } }
+
 // the pointer from IndexPage cannot be passed to predicate!
 // It is just the data ptr, but all these predicate accept the pointer to the beginning of the whole data slot
 
 // assume both lhs and rhs belongs to tp's table, so ColRef::table is not checked
 pub unsafe fn one_predicate<'a, 'b>(db: &'a Db, e: &Cond<'b>, tp: &TablePage) -> Result<'b, Box<dyn Fn(*const u8) -> bool + 'a>> {
+  one_predicate_with(db, e, tp, db.int_bool())
+}
+
+// like `one_predicate`, but with the given `int_bool` instead of `Db::int_bool`, see `TablePage::check_int_bool`
+pub unsafe fn one_predicate_with<'a, 'b>(db: &'a Db, e: &Cond<'b>, tp: &TablePage, int_bool: bool) -> Result<'b, Box<dyn Fn(*const u8) -> bool + 'a>> {
   let tp = tp.pr();
   let l = tp.get_ci(e.lhs_col().col)?;
   let l_id = l.idx(&tp.cols) as u8; // reduce the size of lambda closure, do conversion inside lambda
   let l_off = l.off;
+  let coll = db.collation();
   match *e {
     Cond::Cmp(op, _, r) => match r {
       Atom::Lit(r) => {
@@ -32,20 +64,19 @@ pub unsafe fn one_predicate<'a, 'b>(db: &'a Db, e: &Cond<'b>, tp: &TablePage) ->
         // the match logic is basically the same as the logic in `lit2ptr`, though the content is different
         match (l.ty, r.lit()) {
           (_, Lit::Null) => Ok(box |_| false), // comparing with null always returns false
+          // bools are ordered as false < true, the same as in index and order by
           (bool!(), Lit::Bool(v)) => handle_op!(cmp, op, p, *(p.add(l_off as _) as *const bool), v),
+          (bool!(), Lit::Number(v)) if int_bool => handle_op!(cmp, op, p, *(p.add(l_off as _) as *const bool), v != 0.0),
           (int!(), Lit::Number(v)) => handle_op!(cmp, op, p, *(p.add(l_off as _) as *const i32), v as i32),
           (float!(), Lit::Number(v)) => handle_op!(cmp, op, p, *(p.add(l_off as _) as *const f32), v as f32),
           (date!(), Lit::Str(v)) => {
             let date = db::date(v)?;
             handle_op!(cmp, op, p, *(p.add(l_off as _) as *const NaiveDate), date)
           }
-          (char!(), Lit::Str(v)) => {
-            let v = Box::<str>::from(v);
-            handle_op!(cmp, op, p, str_from_db(p.add(l_off as _)), v.as_ref())
-          }
-          (varchar!(), Lit::Str(v)) => {
-            let v = Box::<str>::from(v);
-            handle_op!(cmp, op, p, db.varchar(p.add(l_off as _)), v.as_ref())
+          (date!(), Lit::Date(v)) => handle_op!(cmp, op, p, *(p.add(l_off as _) as *const NaiveDate), v), // current_date
+          (char!(), Lit::Str(v)) | (varchar!(), Lit::Str(v)) => {
+            let (v, l_ty) = (Box::<str>::from(v), l.ty);
+            handle_op!(cmp, op, p, coll.cmp(col_str(db, l_ty, p.add(l_off as _)), v.as_ref()), Ordering::Equal)
           }
           _ => return Err(ColLitMismatch { ty: l.ty, val: r })
         }
@@ -64,23 +95,118 @@ pub unsafe fn one_predicate<'a, 'b>(db: &'a Db, e: &Cond<'b>, tp: &TablePage) ->
           (int!(), float!()) => handle_op!(cmp, op, p, *(p.add(l_off as _) as *const i32) as f32, *(p.add(r_off as _) as *const f32)),
           (float!(), int!()) => handle_op!(cmp, op, p, *(p.add(l_off as _) as *const f32), *(p.add(r_off as _) as *const i32) as f32),
           (date!(), date!()) => handle_op!(cmp, op, p, *(p.add(l_off as _) as *const NaiveDate), *(p.add(r_off as _) as *const NaiveDate)),
-          (char!(), char!()) => handle_op!(cmp, op, p, str_from_db(p.add(l_off as _)), str_from_db(p.add(r_off as _))),
-          (char!(), varchar!()) => handle_op!(cmp, op, p, str_from_db(p.add(l_off as _)), db.varchar(p.add(r_off as _))),
-          (varchar!(), char!()) => handle_op!(cmp, op, p, db.varchar(p.add(l_off as _)), str_from_db(p.add(r_off as _))),
-          (varchar!(), varchar!()) => handle_op!(cmp, op, p, db.varchar(p.add(l_off as _)), db.varchar(p.add(r_off as _))),
+          (char!(), char!()) | (char!(), varchar!()) | (varchar!(), char!()) | (varchar!(), varchar!()) => {
+            let (l_ty, r_ty) = (l.ty, r.ty);
+            handle_op!(cmp, op, p, coll.cmp(col_str(db, l_ty, p.add(l_off as _)), col_str(db, r_ty, p.add(r_off as _))), Ordering::Equal)
+          }
           (l, r) => return Err(ColMismatch { l, r })
         }
       }
     },
     Cond::Null(_, null) => Ok(if null { box move |p| is_null(p, l_id as u32) } else { box move |p| !is_null(p, l_id as u32) }),
     Cond::Like(_, like) => {
-      let re = db::like2re(like)?;
+      let re = db::like2re(like, coll)?;
       match l.ty {
-        char!() => Ok(box move |p| !is_null(p, l_id as u32) && re.is_match(str_from_db(p.add(l_off as _)))),
-        varchar!() => Ok(box move |p| !is_null(p, l_id as u32) && re.is_match(db.varchar(p.add(l_off as _)))),
+        char!() | varchar!() => {
+          let l_ty = l.ty;
+          Ok(box move |p| !is_null(p, l_id as u32) && re.is_match(col_str(db, l_ty, p.add(l_off as _))))
+        }
+        // match against the same format as date literals, e.g. '2000-%' for year 2000
+        date!() => Ok(box move |p| !is_null(p, l_id as u32) && re.is_match(&(*(p.add(l_off as _) as *const NaiveDate)).format("%Y-%m-%d").to_string())),
         _ => Err(InvalidLikeTy(l.ty))
       }
     }
+    Cond::LikeCol(_, r) => {
+      let r = tp.get_ci(r.col)?;
+      let p = like_col_predicate(db, (l, r), (tp, tp))?;
+      Ok(box move |x| p((x, x)))
+    }
+    Cond::In(_, ref s) => {
+      // the select runs only once, before the predicate is used
+      let r = crate::select(s, db)?;
+      if r.cols.len() != 1 { return Err(InSelectCols(r.cols.len())); }
+      let col = *r.cols.get_unchecked(0);
+      if let Some(x) = col.lit { // a constant col, null is ignored below
+        if !x.is_null() && lit_ty(l.ty) != x.lit().ty() { return Err(IncompatibleCmp { op: Eq, l: lit_ty(l.ty), r: x.lit().ty() }); }
+      } else if col.expr.is_some() {
+        // an arithmetic expression is always a number, a function call has the type of its values (see `Db::register_function`)
+        let r_ty = r.data.iter().find(|x| !x.is_null()).map(|x| x.lit().ty()).unwrap_or(LitTy::Number);
+        if lit_ty(l.ty) != r_ty { return Err(IncompatibleCmp { op: Eq, l: lit_ty(l.ty), r: r_ty }); }
+      } else {
+        // count is int, avg and sum are float, others have the type of their col
+        let r_ty = match col.op {
+          Some(Count) | Some(CountAll) => ColTy::FixTy(FixTy { ty: Int, size: 0 }),
+          Some(Avg) | Some(Sum) => ColTy::FixTy(FixTy { ty: Float, size: 0 }),
+          _ => col.ci.unchecked_unwrap().1.ty,
+        };
+        if lit_ty(l.ty) != lit_ty(r_ty) { return Err(ColMismatch { l: l.ty, r: r_ty }); }
+      }
+      // null never equals anything, so it is not in the set
+      let set = r.data.iter().filter(|x| !x.is_null()).map(|x| InKey::new(x.lit(), coll)).collect::<HashSet<_>>();
+      Ok(box move |p| !is_null(p, l_id as u32) && set.contains(&InKey::new(db.data2lit(p, l_id as u32, l).lit(), coll)))
+    }
+    // the select runs once, and its value is compared like a literal
+    Cond::CmpSelect(op, l1, ref s) => {
+      let r = crate::select(s, db.pr())?;
+      if r.cols.len() != 1 || r.row_count() > 1 { return Err(SubqueryNotScalar { cols: r.cols.len(), rows: r.row_count() }); }
+      // no row is null, which never satisfies the comparison
+      let v = r.data.first().map(|x| x.lit()).unwrap_or(Lit::Null);
+      if !v.is_null() && lit_ty(l.ty) != v.ty() { return Err(IncompatibleCmp { op, l: lit_ty(l.ty), r: v.ty() }); }
+      match (l.ty, v) {
+        // compared in f64, so an int col is not compared with a truncated value (e.g., of avg)
+        (int!(), Lit::Number(v)) => {
+          macro_rules! cmp {
+            ($op: tt, $p: ident, $l: expr, $r: expr) => { Ok(box move |$p| !is_null($p, l_id as u32) && $l $op $r) };
+          }
+          handle_op!(cmp, op, p, *(p.add(l_off as _) as *const i32) as f64, v)
+        }
+        // the value is copied into the predicate before `r` is dropped, and after the type check no error can refer to it
+        _ => one_predicate(db, &Cond::Cmp(op, l1, Atom::Lit(CLit::new(v))), tp),
+      }
+    }
+    Cond::InList(_, ref vals) | Cond::NotInList(_, ref vals) => {
+      let not = if let Cond::NotInList(..) = e { true } else { false };
+      let mut set = HashSet::with_capacity(vals.len());
+      for &v in vals {
+        match (l.ty, v.lit()) {
+          (_, Lit::Null) => {} // the same as `In`
+          (date!(), Lit::Str(s)) => { set.insert(InKey::Date(db::date(s)?)); }
+          // a number out of the col's range matches nothing, others are converted to the col's type, like in comparison
+          (int!(), Lit::Number(x)) | (float!(), Lit::Number(x)) => if Db::lit2ptr_ck(l.ty.fix_ty(), v).is_ok() {
+            set.insert(InKey::new(Lit::Number(if let int!() = l.ty { x as i32 as f64 } else { x as f32 as f64 }), coll));
+          }
+          (ty, lit) if lit_ty(ty) == lit.ty() => { set.insert(InKey::new(lit, coll)); }
+          _ => return Err(ColLitMismatch { ty: l.ty, val: v }),
+        }
+      }
+      // `in ()` is false, `not in ()` is true even for null, since there is no comparison to be unknown
+      if vals.is_empty() { return Ok(box move |_| not); }
+      if not && vals.iter().any(|v| v.is_null()) { return Ok(box |_| false); }
+      Ok(box move |p| !is_null(p, l_id as u32) && set.contains(&InKey::new(db.data2lit(p, l_id as u32, l).lit(), coll)) != not)
+    }
+    Cond::Mod(_, m, op, r) => {
+      if m == 0 { return Err(ModByZero); }
+      macro_rules! cmp {
+        ($op: tt, $p: ident, $l: expr, $r: expr) => { Ok(box move |$p| !is_null($p, l_id as u32) && $l $op $r) };
+      }
+      match (l.ty, r.lit()) {
+        (int!(), Lit::Null) => Ok(box |_| false),
+        (int!(), Lit::Number(v)) => handle_op!(cmp, op, p, (*(p.add(l_off as _) as *const i32)).wrapping_rem(m), v as i32),
+        (int!(), _) => Err(ColLitMismatch { ty: l.ty, val: r }),
+        _ => Err(InvalidModTy(l.ty)),
+      }
+    }
+    // the stored length is compared, the string itself is never read
+    Cond::Len(_, op, r) => {
+      macro_rules! cmp {
+        ($op: tt, $p: ident, $l: expr, $r: expr) => { Ok(box move |$p| !is_null($p, l_id as u32) && $l $op $r) };
+      }
+      match l.ty {
+        char!() => handle_op!(cmp, op, p, *p.add(l_off as _) as i32, r),
+        varchar!() => handle_op!(cmp, op, p, (*(p.add(l_off as _) as *const VarcharSlot)).len as i32, r),
+        _ => Err(InvalidLenTy(l.ty)),
+      }
+    }
   }
 }
 
@@ -89,28 +215,56 @@ pub unsafe fn cross_predicate<'a, 'b>(db: &'a Db, op: CmpOp, col: (&ColInfo, &Co
   let (l, r) = col;
   let (l_id, r_id) = (l.idx(&tp.0.cols) as u16, r.idx(&tp.1.cols) as u16);
   let (l_off, r_off) = (l.off, r.off);
+  let coll = db.collation();
   macro_rules! cmp {
     ($op: tt, $p: ident, $l: expr, $r: expr) => { Ok(box move |$p| !is_null($p.0, l_id as u32) && !is_null($p.1, r_id as u32) && $l $op $r) };
   }
   match (l.ty, r.ty) {
+    // false < true, consistent with `one_predicate`
     (bool!(), bool!()) => handle_op!(cmp, op, p, *(p.0.add(l_off as _) as *const bool), *(p.1.add(r_off as _) as *const bool)),
     (int!(), int!()) => handle_op!(cmp, op, p, *(p.0.add(l_off as _) as *const i32), *(p.1.add(r_off as _) as *const i32)),
     (float!(), float!()) => handle_op!(cmp, op, p, *(p.0.add(l_off as _) as *const f32), *(p.1.add(r_off as _) as *const f32)),
     (int!(), float!()) => handle_op!(cmp, op, p, *(p.0.add(l_off as _) as *const i32) as f32, *(p.1.add(r_off as _) as *const f32)),
     (float!(), int!()) => handle_op!(cmp, op, p, *(p.0.add(l_off as _) as *const f32), *(p.1.add(r_off as _) as *const i32) as f32),
     (date!(), date!()) => handle_op!(cmp, op, p, *(p.0.add(l_off as _) as *const NaiveDate), *(p.1.add(r_off as _) as *const NaiveDate)),
-    (char!(), char!()) => handle_op!(cmp, op, p, str_from_db(p.0.add(l_off as _)), str_from_db(p.1.add(r_off as _))),
-    (char!(), varchar!()) => handle_op!(cmp, op, p, str_from_db(p.0.add(l_off as _)), db.varchar(p.1.add(r_off as _))),
-    (varchar!(), char!()) => handle_op!(cmp, op, p, db.varchar(p.0.add(l_off as _)), str_from_db(p.1.add(r_off as _))),
-    (varchar!(), varchar!()) => handle_op!(cmp, op, p, db.varchar(p.0.add(l_off as _)), db.varchar(p.1.add(r_off as _))),
+    (char!(), char!()) | (char!(), varchar!()) | (varchar!(), char!()) | (varchar!(), varchar!()) => {
+      let (l_ty, r_ty) = (l.ty, r.ty);
+      handle_op!(cmp, op, p, coll.cmp(col_str(db, l_ty, p.0.add(l_off as _)), col_str(db, r_ty, p.1.add(r_off as _))), Ordering::Equal)
+    }
     (l, r) => return Err(ColMismatch { l, r })
   }
 }
 
+// `l like r`, where `r` is the pattern, compiled for each row (the last one is cached, which helps when a pattern is used for consecutive rows)
+pub unsafe fn like_col_predicate<'a, 'b>(db: &'a Db, col: (&ColInfo, &ColInfo), tp: (&TablePage, &TablePage))
+                                         -> Result<'b, Box<dyn Fn((*const u8, *const u8)) -> bool + 'a>> {
+  let (l, r) = col;
+  let (l_id, r_id) = (l.idx(&tp.0.cols) as u16, r.idx(&tp.1.cols) as u16);
+  let (l_off, r_off) = (l.off, r.off);
+  let (l_ty, r_ty, coll) = (l.ty, r.ty, db.collation());
+  match r_ty { char!() | varchar!() => {} _ => return Err(InvalidLikeTy(r_ty)) }
+  match l_ty { char!() | varchar!() | date!() => {} _ => return Err(InvalidLikeTy(l_ty)) }
+  let cache = RefCell::new(None::<(String, Option<Regex>)>);
+  Ok(box move |p| {
+    if is_null(p.0, l_id as u32) || is_null(p.1, r_id as u32) { return false; }
+    let like = col_str(db, r_ty, p.1.add(r_off as _));
+    let mut cache = cache.borrow_mut();
+    if cache.as_ref().map(|(x, _)| x != like).unwrap_or(true) {
+      // an invalid pattern matches nothing
+      *cache = Some((like.to_owned(), db::like2re(like, coll).ok()));
+    }
+    let re = if let Some((_, Some(re))) = cache.as_ref() { re } else { return false; };
+    match l_ty {
+      char!() | varchar!() => re.is_match(col_str(db, l_ty, p.0.add(l_off as _))),
+      _ => re.is_match(&(*(p.0.add(l_off as _) as *const NaiveDate)).format("%Y-%m-%d").to_string()),
+    }
+  })
+}
+
 pub unsafe fn one_where<'a, 'b>(db: &'a Db, where_: &[Cond<'b>], tp: &TablePage) -> Result<'b, impl Fn(*const u8) -> bool + 'a> {
   let mut preds = Vec::with_capacity(where_.len());
   for cond in where_ {
-    let (l, r) = (cond.lhs_col(), cond.rhs_col_op().map(|x| x.0));
+    let (l, r) = (cond.lhs_col(), cond.rhs_col());
     if let Some(t) = l.table { if t != tp.name() { return Err(NoSuchTable(t)); } }
     if let Some(&ColRef { table: Some(t), .. }) = r { if t != tp.name() { return Err(NoSuchTable(t)); } }
     // table name is checked before, col name & type & value format/size all checked in one_predicate
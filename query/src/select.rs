@@ -1,130 +1,325 @@
 use unchecked_unwrap::UncheckedUnwrap;
-use std::{fmt::Write, mem};
+use std::{io, fmt::Write, mem, ptr, slice, cmp::Ordering, time::{Duration, Instant}};
 
 use common::{*, BareTy::*, Error::*, AggOp::*, CmpOp::*};
 use syntax::ast::*;
 use physics::*;
 use db::{Db, is_null};
-use crate::{predicate::{and, one_predicate, cross_predicate}, filter::filter};
+use crate::{catalog, cte, expr, predicate::{and, one_predicate, cross_predicate, like_col_predicate}, filter::{filter, filter_ordered, count}};
 use chrono::NaiveDate;
 use ordslice::Ext;
+use regex::Regex;
 
 #[derive(Copy, Clone)]
 pub struct Col<'a> {
   // if op == Some(CountAll), `ci` is None, otherwise `ci` will always be Some
   pub op: Option<AggOp>,
   pub ci: Option<(u32, &'a ColInfo)>,
+  // the index of the table (in the from list) that `ci` belongs to, meaningless for CountAll (except `count(t.*)`)
+  pub tbl: usize,
+  // the table name of `count(t.*)`, None for `count(*)` and other cols
+  pub count_tbl: Option<&'a str>,
+  // `op(distinct col)`, see `Agg::distinct`
+  pub distinct: bool,
+  // a constant col has the same value in every row, `op` and `ci` are None
+  pub lit: Option<CLit<'a>>,
+  // an expression col (in the select list or group by) is the index in `SelectResult::exprs`, `op` and `ci` are None
+  // the same expression (by its text) always has the same index, so that a selected one can match one in group by
+  pub expr: Option<usize>,
+}
+
+impl Col<'_> {
+  fn same_col(&self, other: &Col) -> bool { self.tbl == other.tbl && self.ci.map(|x| x.0) == other.ci.map(|x| x.0) && self.expr == other.expr }
+
+  fn same_agg(&self, other: &Col) -> bool {
+    self.same_col(other) && self.op == other.op && self.count_tbl == other.count_tbl && self.distinct == other.distinct
+  }
 }
 
 pub struct SelectResult<'a> {
   pub cols: Vec<Col<'a>>,
   // `data` is a 2-d array, dim = cols.len() * (data.len() / cols.len()) (data.len() / cols.len() is row_count())
   pub data: Vec<CLit<'a>>,
+  // the names of the tables in the from list, `Col::tbl` is the index in it
+  pub tbls: Vec<&'a str>,
+  // the in-memory db of catalog tables or ctes, `cols` and `data` may point into it
+  pub(crate) _catalog: Option<Box<Db>>,
+  // the strings of constant cols are copied from the sql, so that the result can outlive it
+  pub(crate) _consts: Vec<Box<str>>,
+  // the texts of the expression cols, they are only used in the header
+  pub exprs: Vec<String>,
 }
 
-impl SelectResult<'_> {
-  // `data` is 2-d array of dimension = tbls.len() * (data.len() / tbls.len())
-  // tbls[i] <-> data[i], both belongs to a table
-  unsafe fn new<'a>(db: &Db, tbls: &[Vec<Col<'a>>], data: &[*const u8]) -> SelectResult<'a> {
-    let result_num = data.len() / tbls.len();
-    // if has agg, all col should have agg (checked in mk_tbls)
-    let has_agg = tbls.iter().flatten().any(|col| col.op.is_some());
-    let data = if has_agg {
-      tbls.iter().enumerate().flat_map(|(idx, tbl)| {
-        tbl.iter().map(move |col| {
-          // avg, sum, min, max, count should ignore null, if none is not null, all except count should return null, count should return 0
-          // avg's denominator should also ignore null
-          // count(*) should not ignore null
-          let op = col.op.unchecked_unwrap();
-          match op {
-            Avg | Sum => { // only accept Int, Float, checked in mk_tbls
-              let (ci_id, ci) = col.ci.unchecked_unwrap();
-              let mut sum = 0.0; // use f64 for better precision (cover i32)
-              let mut notnull_cnt = 0;
-              for i in 0..result_num {
-                let data = *data.get_unchecked(i * tbls.len() + idx);
-                if !is_null(data, ci_id) {
-                  let ptr = data.add(ci.off as usize);
-                  match ci.ty { int!() => sum += *(ptr as *const i32) as f64, float!() => sum += *(ptr as *const f32) as f64, _ => impossible!() }
-                  notnull_cnt += 1;
-                }
-              }
-              CLit::new(if notnull_cnt == 0 { Lit::Null } else { Lit::Number(if op == Avg { sum / notnull_cnt as f64 } else { sum }) })
-            }
-            Min | Max => {
-              let (ci_id, ci) = col.ci.unchecked_unwrap();
-              let it = (0..result_num).filter_map(|i| {
-                let lit = db.data2lit(*data.get_unchecked(i * tbls.len() + idx), ci_id, ci);
-                if lit.is_null() { None } else { Some(lit) }
-              });
-              // can't use function reference directly because `cmp` is unsafe
-              if op == Max { it.max_by(|l, r| l.cmp(*r)) } else { it.min_by(|l, r| l.cmp(*r)) }
-                .unwrap_or(CLit::new(Lit::Null))
-            }
-            Count => CLit::new(Lit::Number((0..result_num).filter(|&i| {
-              !is_null(*data.get_unchecked(i * tbls.len() + idx), col.ci.unchecked_unwrap().0)
-            }).count() as f64)),
-            CountAll => CLit::new(Lit::Number(result_num as f64)),
-          }
-        })
-      }).collect()
-    } else {
-      let row = (tbls.iter().map(|tbl| tbl.len())).sum::<usize>();
-      let mut ret = Vec::<CLit>::with_capacity(result_num * row);
-      ret.set_len(result_num * row);
-      for i in 0..result_num {
-        let mut j = 0;
-        for (idx, tbl) in tbls.iter().enumerate() {
-          let data = *data.get_unchecked(i * tbls.len() + idx);
-          for col in tbl {
-            let (ci_id, ci) = col.ci.unchecked_unwrap();
-            ret.as_mut_ptr().add(i * row + j).write(db.data2lit(data, ci_id, ci));
-            j += 1;
+// null is smaller than any other value, strings are compared by `coll`
+unsafe fn cmp_null_first(l: CLit, r: CLit, coll: Collation) -> Ordering {
+  match (l.is_null(), r.is_null()) {
+    (true, true) => Ordering::Equal, (true, false) => Ordering::Less, (false, true) => Ordering::Greater,
+    (false, false) => if let (Lit::Str(l), Lit::Str(r)) = (l.lit(), r.lit()) { coll.cmp(l, r) } else { l.cmp(r) },
+  }
+}
+
+// `data` yields the data slot of `col`'s table in each row (for CountAll, any table is ok)
+// avg, sum, min, max, count should ignore null, if none is not null, all except count should return null, count should return 0
+// avg's denominator should also ignore null
+// count(*) should not ignore null
+unsafe fn agg<'a>(db: &Db, op: AggOp, col: &Col, data: impl Iterator<Item=*const u8>) -> CLit<'a> {
+  match op {
+    PercentileDisc(_) | PercentileCont(_) => { // only accept Int, Float, like avg
+      let (ci_id, ci) = col.ci.unchecked_unwrap();
+      let mut vals = data.filter(|&data| !is_null(data, ci_id)).map(|data| {
+        let ptr = data.add(ci.off as usize);
+        match ci.ty { int!() => *(ptr as *const i32) as f64, float!() => *(ptr as *const f32) as f64, _ => impossible!() }
+      }).collect::<Vec<_>>();
+      if vals.is_empty() { return CLit::new(Lit::Null); }
+      vals.sort_unstable_by(|l, r| l.partial_cmp(r).unwrap_or(Ordering::Equal));
+      let (p, n) = (op.p().unchecked_unwrap(), vals.len());
+      CLit::new(Lit::Number(if let PercentileDisc(_) = op {
+        *vals.get_unchecked(((p * n as f64).ceil() as usize).max(1).min(n) - 1)
+      } else {
+        let rank = p * (n - 1) as f64;
+        let (lo, hi) = (*vals.get_unchecked(rank.floor() as usize), *vals.get_unchecked(rank.ceil() as usize));
+        lo + (hi - lo) * (rank - rank.floor())
+      }))
+    }
+    Avg | Sum => { // only accept Int, Float, checked in mk_cols
+      let (ci_id, ci) = col.ci.unchecked_unwrap();
+      let mut sum = 0.0; // use f64 for better precision (cover i32)
+      let mut notnull_cnt = 0;
+      // for distinct, the values seen before; f64 represents both i32 and f32 exactly
+      let mut seen = HashSet::new();
+      for data in data {
+        if !is_null(data, ci_id) {
+          let ptr = data.add(ci.off as usize);
+          let v = match ci.ty { int!() => *(ptr as *const i32) as f64, float!() => *(ptr as *const f32) as f64, _ => impossible!() };
+          if !col.distinct || seen.insert(Lit::Number(v).group_key()) {
+            sum += v;
+            notnull_cnt += 1;
           }
         }
       }
-      ret
+      CLit::new(if notnull_cnt == 0 { Lit::Null } else { Lit::Number(if op == Avg { sum / notnull_cnt as f64 } else { sum }) })
+    }
+    Min | Max => {
+      let (ci_id, ci) = col.ci.unchecked_unwrap();
+      let it = data.filter_map(|data| {
+        let lit = db.data2lit(data, ci_id, ci);
+        if lit.is_null() { None } else { Some(lit) }
+      });
+      // can't use function reference directly because `cmp` is unsafe
+      if op == Max { it.max_by(|l, r| l.cmp(*r)) } else { it.min_by(|l, r| l.cmp(*r)) }
+        .unwrap_or(CLit::new(Lit::Null))
+    }
+    // a count is a Number like other values, f64 is exact up to 2^53, so it never overflows or truncates
+    Count => {
+      let (ci_id, ci) = col.ci.unchecked_unwrap();
+      let it = data.filter(|&data| !is_null(data, ci_id));
+      CLit::new(Lit::Number(if col.distinct { it.map(|data| db.data2lit(data, ci_id, ci).lit().group_key()).collect::<HashSet<_>>().len() } else { it.count() } as f64))
+    }
+    // for `count(t.*)`, `data` is from `t`, which is null if `t` is the right table of left join and there is no match
+    CountAll => CLit::new(Lit::Number(if col.count_tbl.is_some() { data.filter(|data| !data.is_null()).count() } else { data.count() } as f64)),
+  }
+}
+
+// a key of order by that can be sorted before aggregation, an expression is the index of its item in `Select::order_by`
+#[derive(Copy, Clone)]
+enum SortKey<'a> { Col(Col<'a>), Expr(usize) }
+
+// append `s` as a json string (with quotes) to `out`
+fn json_str(s: &str, out: &mut String) {
+  out.reserve(s.len() + 2);
+  out.push('"');
+  for ch in s.chars() {
+    match ch {
+      '"' => *out += "\\\"", '\\' => *out += "\\\\", '\n' => *out += "\\n", '\r' => *out += "\\r", '\t' => *out += "\\t",
+      ch if (ch as u32) < 0x20 => write!(out, "\\u{:04x}", ch as u32).unchecked_unwrap(),
+      ch => out.push(ch),
+    }
+  }
+  out.push('"');
+}
+
+// a predicate that accepts a whole row (one data slot from each table)
+type RowPred<'a> = Box<dyn Fn(&[*const u8]) -> bool + 'a>;
+// the predicates of `filter (where ...)` of an agg
+type AggFilter<'a> = Vec<RowPred<'a>>;
+
+impl SelectResult<'_> {
+  // `data` is 2-d array of dimension = (data.len() / tbl_num) * tbl_num, each row contains one data slot from each table
+  // if there is agg or `group` is not empty, each group produces one result row (without group by, all rows are in one group)
+  // in this case, a non-agg col must be in `group` (checked in `select`), so its value is the same in the whole group
+  // `filters` is either empty or has the same length as `cols`
+  // `exprs` is the text of each expression col and its value in each row
+  unsafe fn new<'a>(db: &Db, mut cols: Vec<Col<'a>>, tbls: Vec<&'a str>, filters: &[AggFilter], group: &[Col<'a>], tbl_num: usize, data: &[*const u8], exprs: Vec<(String, Vec<CLit<'a>>)>) -> SelectResult<'a> {
+    let row_num = data.len() / tbl_num;
+    let mut consts = Vec::new();
+    for col in &mut cols {
+      if let Some(Lit::Str(s)) = col.lit.map(|x| x.lit()) {
+        let s = Box::<str>::from(s);
+        col.lit = Some(CLit::new(Lit::Str(str_from_parts(s.as_ptr(), s.len()))));
+        consts.push(s);
+      }
+    }
+    let (exprs, vals) = exprs.into_iter().unzip::<_, _, Vec<_>, Vec<_>>();
+    let lit = |i: usize, col: &Col<'a>| {
+      if let Some(x) = col.lit { return x; }
+      if let Some(idx) = col.expr { return *vals.get_unchecked(idx).get_unchecked(i); }
+      let (ci_id, ci) = col.ci.unchecked_unwrap();
+      db.data2lit(*data.get_unchecked(i * tbl_num + col.tbl), ci_id, ci)
     };
-    SelectResult { cols: tbls.iter().flatten().copied().collect(), data }
+    let mut ret = Vec::new();
+    if group.is_empty() && cols.iter().all(|col| col.op.is_none()) {
+      ret.reserve(row_num * cols.len());
+      for i in 0..row_num {
+        for col in &cols { ret.push(lit(i, col)); }
+      }
+    } else {
+      let groups = if group.is_empty() { vec![(0..row_num).collect()] } else {
+        // keep the order of the first appearance of each group
+        let mut groups = IndexMap::<Vec<GroupKey>, Vec<usize>>::default();
+        for i in 0..row_num {
+          groups.entry(group.iter().map(|col| lit(i, col).lit().group_key()).collect()).or_insert_with(Vec::new).push(i);
+        }
+        groups.into_iter().map(|(_, rows)| rows).collect::<Vec<_>>()
+      };
+      ret.reserve(groups.len() * cols.len());
+      for rows in &groups {
+        for (idx, col) in cols.iter().enumerate() {
+          ret.push(if let Some(op) = col.op {
+            let filter = filters.get(idx).map(|f| f.as_slice()).unwrap_or(&[]);
+            agg(db, op, col, rows.iter().filter(|&&i| filter.iter().all(|p| p(data.get_unchecked(i * tbl_num..(i + 1) * tbl_num))))
+              .map(|&i| *data.get_unchecked(i * tbl_num + col.tbl)))
+          } else if let Some(x) = col.lit { x } else { lit(*rows.get_unchecked(0), col) });
+        }
+      }
+    }
+    SelectResult { cols, data: ret, tbls, _catalog: None, _consts: consts, exprs }
+  }
+
+  // stable sort of the result rows, each key is (index in `cols`, desc)
+  unsafe fn sort(&mut self, keys: &[(usize, bool)], coll: Collation) {
+    let n = self.cols.len();
+    let data = &self.data;
+    let mut rows = (0..self.row_count()).collect::<Vec<_>>();
+    rows.sort_by(|&l, &r| keys.iter().map(|&(idx, desc)| {
+      let ord = cmp_null_first(*data.get_unchecked(l * n + idx), *data.get_unchecked(r * n + idx), coll);
+      if desc { ord.reverse() } else { ord }
+    }).find(|&ord| ord != Ordering::Equal).unwrap_or(Ordering::Equal));
+    self.data = rows.iter().flat_map(|&i| data.get_unchecked(i * n..(i + 1) * n).iter().copied()).collect();
   }
 
   pub fn row_count(&self) -> usize {
     self.data.len().checked_div(self.cols.len()).unwrap_or(0)
   }
 
-  pub fn csv(&self) -> String {
+  // the names of the selected cols that come from more than one table, they are qualified as `table.col` in the header
+  pub fn ambiguous_cols(&self) -> HashSet<&str> {
+    let (mut tbl_of, mut ret) = (HashMap::new(), HashSet::new());
+    for col in &self.cols {
+      if let Some((_, ci)) = col.ci {
+        let name = unsafe { ci.name() };
+        if *tbl_of.entry(name).or_insert(col.tbl) != col.tbl { ret.insert(name); }
+      }
+    }
+    ret
+  }
+
+  // the names of the selected cols, the same as in the header of csv
+  pub fn col_names(&self) -> Vec<String> {
+    let ambiguous = self.ambiguous_cols();
+    self.cols.iter().map(|&Col { op, ci, tbl, count_tbl, distinct, lit, expr }| unsafe {
+      let mut name = String::new();
+      if let Some(lit) = lit { write!(name, "{:?}", lit).unchecked_unwrap(); } else if let Some(idx) = expr { name += self.exprs.get_unchecked(idx); } else if let Some((_, ci)) = ci {
+        match op.map(|op| (op, op.p())) {
+          Some((op, Some(p))) => write!(name, "{}({}) within group (order by ", op.name(), p).unchecked_unwrap(),
+          Some((op, None)) => write!(name, "{}({}", op.name(), if distinct { "distinct " } else { "" }).unchecked_unwrap(),
+          None => {}
+        }
+        if ambiguous.contains(ci.name()) { write!(name, "{}.", self.tbls.get_unchecked(tbl)).unchecked_unwrap(); }
+        name += ci.name();
+        if op.is_some() { name.push(')'); }
+      } else if let Some(t) = count_tbl { write!(name, "count({}.*)", t).unchecked_unwrap(); } else { name += "count(*)"; }
+      name
+    }).collect()
+  }
+
+  // `header` controls whether the first line is col names
+  pub fn csv(&self, header: bool) -> String {
+    let mut csv = Vec::new();
+    // writing to a `Vec` never fails, and all pieces are valid utf8
+    unsafe { (self.write_csv(&mut csv, header).unchecked_unwrap(), String::from_utf8_unchecked(csv)).1 }
+  }
+
+  // the same content as `csv`, but written to `w` row by row, so a large result is not serialized in memory as a whole
+  // lines are separated by '\n', there is no '\n' after the last line
+  pub fn write_csv(&self, mut w: impl io::Write, header: bool) -> io::Result<()> {
     unsafe {
-      let mut csv = String::new();
-      for &Col { op, ci, .. } in &self.cols {
-        if let Some((_, ci)) = ci {
-          if let Some(op) = op { write!(csv, "{}({})", op.name(), ci.name()).unchecked_unwrap(); } else { csv += ci.name(); }
-        } else { csv += "count(*)"; }
-        csv.push(',');
+      let mut first = true;
+      let mut line = String::new();
+      macro_rules! flush_line {
+        () => {
+          if !mem::replace(&mut first, false) { w.write_all(b"\n")?; }
+          w.write_all(line.as_bytes())?;
+          line.clear();
+        };
+      }
+      if header {
+        line = self.col_names().join(",");
+        flush_line!();
       }
-      (csv.pop(), csv.push('\n'));
       for i in 0..self.row_count() {
         let row = self.data.get_unchecked(i * self.cols.len()..(i + 1) * self.cols.len());
         for lit in row {
-          match lit.lit() { // some tiny modifications to Lit's `debug` method
-            Lit::Null => {}
+          match lit.lit() { // only string needs quoting in csv, others are the same as Lit's `Display`
             Lit::Str(s) => {
-              csv.reserve(s.len() + 2);
-              csv.push('"');
+              line.reserve(s.len() + 2);
+              line.push('"');
               for ch in s.chars() {
-                if ch == '"' { csv.push('"'); } // csv format, "" to escape "
-                csv.push(ch);
+                if ch == '"' { line.push('"'); } // csv format, "" to escape "
+                line.push(ch);
               }
-              csv.push('"');
+              line.push('"');
             }
-            _ => write!(csv, "{:?}", lit).unchecked_unwrap(),
+            _ => write!(line, "{}", lit).unchecked_unwrap(),
           }
-          csv.push(',');
+          line.push(',');
         }
-        (csv.pop(), csv.push('\n'));
+        line.pop();
+        flush_line!();
       }
-      (csv.pop(), csv).1
+      Ok(())
     }
   }
+
+  // each row is a json object from the col names (see `col_names`) to the values, one object per line
+  // like `write_csv`, lines are separated by '\n' and the rows are written one by one; a date is a "yyyy-mm-dd" string
+  pub fn write_jsonl(&self, mut w: impl io::Write) -> io::Result<()> {
+    let keys = self.col_names().iter().map(|name| {
+      let mut key = String::new();
+      json_str(name, &mut key);
+      key + ":"
+    }).collect::<Vec<_>>();
+    let mut line = String::new();
+    for i in 0..self.row_count() {
+      if i != 0 { w.write_all(b"\n")?; }
+      let row = unsafe { self.data.get_unchecked(i * self.cols.len()..(i + 1) * self.cols.len()) };
+      line.push('{');
+      for (key, lit) in keys.iter().zip(row) {
+        line += key;
+        match lit.lit() {
+          Lit::Null => line += "null",
+          Lit::Str(s) => json_str(s, &mut line),
+          Lit::Date(_) => write!(line, "\"{}\"", lit).unchecked_unwrap(),
+          _ => write!(line, "{}", lit).unchecked_unwrap(), // bool and number are the same as Lit's `Display`
+        }
+        line.push(',');
+      }
+      line.pop(); // a row is never empty, since there is at least one col
+      line.push('}');
+      w.write_all(line.as_bytes())?;
+      line.clear();
+    }
+    Ok(())
+  }
 }
 
 struct SelectCtx<'a, 'b> {
@@ -133,6 +328,8 @@ struct SelectCtx<'a, 'b> {
 }
 
 impl<'a, 'b> SelectCtx<'a, 'b> {
+  unsafe fn tbl_names(&self) -> Vec<&'b str> { self.tbls.values().map(|&(_, tp)| tp.name()).collect() }
+
   unsafe fn one_where(&self, cr: &ColRef<'a>) -> Result<'a, (&'b TablePage, &'b ColInfo, usize)> {
     if let Some(t) = cr.table {
       if let Some((tbl_idx_l, _, &tp)) = self.tbls.get_full(t) {
@@ -147,41 +344,223 @@ impl<'a, 'b> SelectCtx<'a, 'b> {
     }
   }
 
-  // the validity of AggOp is checked here
-  unsafe fn mk_tbls(&self, ops: &Option<Vec<Agg<'a>>>) -> Result<'a, Vec<Vec<Col<'b>>>> {
+  // return the table index and page if `agg` is `t.*`
+  fn tbl_all(&self, agg: &Agg<'a>) -> Result<'a, Option<(usize, &'b TablePage)>> {
+    match agg.col {
+      ColRef { table: Some(t), col: "*" } if agg.op.is_none() =>
+        self.tbls.get_full(t).map(|(tbl, _, &(_, tp))| Some((tbl, tp))).ok_or(NoSuchTable(t)),
+      _ => Ok(None),
+    }
+  }
+
+  // the validity of AggOp is checked here, the result is in the same order as `ops` (`t.*` is expanded to all cols of `t`)
+  unsafe fn mk_cols<'c>(&self, db: &Db, ops: &'c Option<Vec<Agg<'a>>>, exprs: &mut Vec<(String, &'c Expr<'a>)>, re_cache: &mut HashMap<&'a str, Regex>) -> Result<'a, Vec<Col<'b>>> {
     if let Some(ops) = ops {
-      if ops.iter().any(|agg| agg.op.is_some()) != ops.iter().all(|agg| agg.op.is_some()) {
-        return Err(MixedSelect);
-      }
-      let mut ret = vec![vec![]; self.tbls.len()];
-      for &Agg { op, col } in ops {
-        if op == Some(CountAll) {
-          // I admit it is quite ugly...
-          ret.get_unchecked_mut(0).push(Col { op, ci: None });
-        } else {
-          let (tp, ci, idx) = self.one_where(&col)?;
-          if let Some(op) = op {
-            if op == Avg || op == Sum {
-              match ci.ty { int!() | float!() => {} col => return Err(InvalidAgg { col, op }), }
-            }
-          }
-          ret.get_unchecked_mut(idx).push(Col { op, ci: Some((ci.idx(&tp.cols), ci)) });
-        }
+      let mut ret = Vec::with_capacity(ops.len());
+      for agg in ops {
+        if let Some((tbl, tp)) = self.tbl_all(agg)? {
+          ret.extend(tp.cols().iter().enumerate().map(|(ci_id, ci)| Col { op: None, ci: Some((ci_id as u32, ci)), tbl, count_tbl: None, distinct: false, lit: None, expr: None }));
+        } else if let Some(e) = &agg.expr { ret.push(self.mk_expr(db, e, exprs, re_cache)?); } else { ret.push(self.mk_col(agg)?); }
       }
       Ok(ret)
-    } else { // select *
-      Ok(self.tbls.iter().map(|(_, &(_, tp))| {
-        tp.cols().iter().enumerate().map(|(ci_id, ci)| Col { op: None, ci: Some((ci_id as u32, ci)) }).collect()
+    } else { // select *, `tp.cols()` is always in the declaration order (add col appends to it, drop col keeps the others' order)
+      Ok(self.tbls.iter().enumerate().flat_map(|(tbl, (_, &(_, tp)))| {
+        tp.cols().iter().enumerate().map(move |(ci_id, ci)| Col { op: None, ci: Some((ci_id as u32, ci)), tbl, count_tbl: None, distinct: false, lit: None, expr: None })
       }).collect())
     }
   }
+
+  // `agg` should not be `t.*`
+  unsafe fn mk_col(&self, agg: &Agg<'a>) -> Result<'a, Col<'b>> {
+    let Agg { op, col, distinct, lit, .. } = *agg;
+    if let Some(lit) = lit {
+      // it points into the sql until `SelectResult::new` copies it
+      Ok(Col { op: None, ci: None, tbl: 0, count_tbl: None, distinct: false, lit: Some(mem::transmute(lit)), expr: None })
+    } else if op == Some(CountAll) {
+      if let Some(t) = col.table {
+        let (tbl, _, &(_, tp)) = self.tbls.get_full(t).ok_or(NoSuchTable(t))?;
+        Ok(Col { op, ci: None, tbl, count_tbl: Some(tp.name()), distinct: false, lit: None, expr: None })
+      } else { Ok(Col { op, ci: None, tbl: 0, count_tbl: None, distinct: false, lit: None, expr: None }) }
+    } else {
+      let (tp, ci, tbl) = self.one_where(&col)?;
+      if let Some(op) = op {
+        if op == Avg || op == Sum || op.p().is_some() {
+          match ci.ty { int!() | float!() => {} col => return Err(InvalidAgg { col, op }), }
+        }
+      }
+      Ok(Col { op, ci: Some((ci.idx(&tp.cols), ci)), tbl, count_tbl: None, distinct, lit: None, expr: None })
+    }
+  }
+
+  unsafe fn mk_group<'c>(&self, db: &Db, group_by: &'c [Expr<'a>], exprs: &mut Vec<(String, &'c Expr<'a>)>, re_cache: &mut HashMap<&'a str, Regex>) -> Result<'a, Vec<Col<'b>>> {
+    let mut ret = Vec::with_capacity(group_by.len());
+    for e in group_by {
+      if let Expr::Atom(Atom::ColRef(cr)) = e {
+        let (tp, ci, tbl) = self.one_where(cr)?;
+        ret.push(Col { op: None, ci: Some((ci.idx(&tp.cols), ci)), tbl, count_tbl: None, distinct: false, lit: None, expr: None });
+      } else { ret.push(self.mk_expr(db, e, exprs, re_cache)?); }
+    }
+    Ok(ret)
+  }
+
+  // `e` is an arithmetic expression or a function call on any cols of the tables, it is added to `exprs` if no expression there has the same text
+  // the args of a function can have a like, its regex is added to `re_cache`
+  unsafe fn mk_expr<'c>(&self, db: &Db, e: &'c Expr<'a>, exprs: &mut Vec<(String, &'c Expr<'a>)>, re_cache: &mut HashMap<&'a str, Regex>) -> Result<'a, Col<'b>> {
+    expr::check(db, e, &|cr: &ColRef<'a>| Ok(self.one_where(cr)?.1.ty), re_cache)?;
+    let text = format!("{:?}", e);
+    let idx = match exprs.iter().position(|x| x.0 == text) { Some(idx) => idx, None => (exprs.push((text, e)), exprs.len() - 1).1 };
+    Ok(Col { op: None, ci: None, tbl: 0, count_tbl: None, distinct: false, lit: None, expr: Some(idx) })
+  }
+
+  // evaluate an arithmetic expression or a function call on each row of `data` (one data slot from each table)
+  // the result is a number or null for arithmetic, a function can also give a bool or a date, but never a string
+  // it stops at the first row that a function fails on
+  unsafe fn eval_rows<'c>(&self, db: &Db, e: &Expr<'a>, data: &[*const u8], tbl_num: usize, re_cache: &HashMap<&'a str, Regex>) -> Result<'a, Vec<CLit<'c>>> {
+    (0..data.len() / tbl_num).map(|row| Ok(match expr::eval(db, e, &|cr: &ColRef<'a>| {
+      let (tp, ci, tbl) = self.one_where(cr).unchecked_unwrap();
+      db.data2lit(*data.get_unchecked(row * tbl_num + tbl), ci.idx(&tp.cols), ci).lit()
+    }, re_cache)? {
+      Lit::Number(x) => CLit::new(Lit::Number(x)), Lit::Bool(x) => CLit::new(Lit::Bool(x)), Lit::Date(x) => CLit::new(Lit::Date(x)),
+      _ => CLit::new(Lit::Null),
+    })).collect()
+  }
+
+  // compile `cond` to a predicate on the whole row, for the conds that can't be pushed down to the scan of one table
+  unsafe fn row_pred<'c>(&self, db: &'c Db, cond: &Cond<'a>) -> Result<'a, RowPred<'c>> {
+    let (tp_l, ci_l, idx_l) = self.one_where(cond.lhs_col())?;
+    if let Some(r) = cond.rhs_col() {
+      let (tp_r, ci_r, idx_r) = self.one_where(r)?;
+      if idx_l != idx_r {
+        let p = match cond.rhs_col_op() {
+          Some((_, op)) => cross_predicate(db, op, (ci_l, ci_r), (tp_l, tp_r))?,
+          None => like_col_predicate(db, (ci_l, ci_r), (tp_l, tp_r))?,
+        };
+        return Ok(box move |row: &[*const u8]| p((*row.get_unchecked(idx_l), *row.get_unchecked(idx_r))));
+      }
+    }
+    let p = one_predicate(db, cond, tp_l)?;
+    Ok(box move |row: &[*const u8]| p(*row.get_unchecked(idx_l)))
+  }
 }
 
-pub fn select<'a, 'b>(s: &Select<'a>, db: &'b Db) -> Result<'a, SelectResult<'b>> {
+// return a table that is not linked with the first table by the conds between tables (in where or on), directly or indirectly
+// if there is one, the result contains the cartesian product of them, which is usually a mistake
+// cols that can't be resolved are ignored here, `select` will report them
+pub fn unlinked_table<'a>(s: &Select<'a>, db: &Db) -> Option<&'a str> {
   unsafe {
+    let tbl_of = |cr: &ColRef| if let Some(t) = cr.table { s.tables.iter().position(|&x| x == t) } else {
+      let mut it = s.tables.iter().enumerate().filter(|&(_, &t)| db.get_tp(t).map(|(_, tp)| tp.get_ci(cr.col).is_ok()).unwrap_or(false));
+      match (it.next(), it.next()) { (Some((idx, _)), None) => Some(idx), _ => None }
+    };
+    // union-find on the index of tables
+    let mut parent = (0..s.tables.len()).collect::<Vec<_>>();
+    fn find(parent: &mut [usize], x: usize) -> usize {
+      let p = parent[x];
+      if p == x { return x; }
+      let root = find(parent, p);
+      (parent[x] = root, root).1
+    }
+    for cond in s.where_.iter().chain(s.left_on.iter().flatten()) {
+      if let Some(r) = cond.rhs_col() {
+        if let (Some(l), Some(r)) = (tbl_of(cond.lhs_col()), tbl_of(r)) {
+          let (l, r) = (find(&mut parent, l), find(&mut parent, r));
+          parent[l] = r;
+        }
+      }
+    }
+    let root = find(&mut parent, 0);
+    (1..s.tables.len()).find(|&idx| find(&mut parent, idx) != root).map(|idx| s.tables[idx])
+  }
+}
+
+// the actual row counts and time of each step in running a select, collected by `select_analyze`
+#[derive(Debug, Default)]
+pub struct Analyze<'a> {
+  // (table, rows accepted by the predicates on only this table, all rows in this table, index seeks (0 for a full scan), time)
+  pub scans: Vec<(&'a str, usize, usize, u32, Duration)>,
+  // (rows, time) after joining each table except the first one
+  pub joins: Vec<(usize, Duration)>,
+  // rows after the conds that are applied after join (the where on the right table of left join), None if there is no such cond
+  pub post: Option<usize>,
+  // (col, records read) if the only scan goes through the index on col in order and stops at the limit, see `filter_ordered`
+  pub ordered: Option<(String, usize)>,
+  // rows in the result, and the time of the whole select
+  pub rows: usize,
+  pub time: Duration,
+}
+
+impl Analyze<'_> {
+  // an indented tree, each step is above the steps it uses
+  pub fn tree(&self) -> String {
+    let mut s = String::new();
+    let line = |s: &mut String, depth: usize, name: &str, rows: usize, time: Duration|
+      writeln!(s, "{:indent$}{}: {} rows ({:?})", "", name, rows, time, indent = depth * 2).unchecked_unwrap();
+    line(&mut s, 0, "select", self.rows, self.time);
+    let mut depth = 1;
+    if let Some(rows) = self.post { (writeln!(s, "  where after join: {} rows", rows).unchecked_unwrap(), depth += 1); }
+    // joins are left-deep: the i-th join uses the (i - 1)-th join (or the first scan) and the (i + 1)-th scan
+    for (i, &(rows, time)) in self.joins.iter().enumerate().rev() {
+      line(&mut s, depth + self.joins.len() - 1 - i, &format!("join `{}`", self.scans[i + 1].0), rows, time);
+    }
+    for (i, &(table, rows, all, seeks, time)) in self.scans.iter().enumerate() {
+      let depth = depth + self.joins.len() - i.saturating_sub(1);
+      write!(s, "{:indent$}scan `{}`: {} of {} rows", "", table, rows, all, indent = depth * 2).unchecked_unwrap();
+      if seeks != 0 { write!(s, ", {} index seeks", seeks).unchecked_unwrap(); }
+      if let Some((col, read)) = &self.ordered { write!(s, ", in the order of `{}`, {} records read", col, read).unchecked_unwrap(); }
+      writeln!(s, " ({:?})", time).unchecked_unwrap();
+    }
+    (s.pop(), s).1
+  }
+
+  // the same steps as `tree` in json, each node has "node", "rows" and "time_us", a join has the two steps it uses in "inputs"
+  pub fn json(&self) -> String {
+    let select = format!(r#"{{"node":"select","rows":{},"time_us":{}"#, self.rows, self.time.as_micros());
+    // no scan is recorded if the select reads no table
+    if self.scans.is_empty() { return select + "}"; }
+    let scan = |i: usize| {
+      let (table, rows, all, seeks, time) = self.scans[i];
+      let ordered = self.ordered.as_ref().map(|(col, read)| format!(r#","ordered":{{"col":"{}","read":{}}}"#, col, read)).unwrap_or_default();
+      format!(r#"{{"node":"scan","table":"{}","rows":{},"all":{},"seeks":{}{},"time_us":{}}}"#, table, rows, all, seeks, ordered, time.as_micros())
+    };
+    let mut s = scan(0);
+    for (i, &(rows, time)) in self.joins.iter().enumerate() {
+      s = format!(r#"{{"node":"join","table":"{}","rows":{},"time_us":{},"inputs":[{},{}]}}"#, self.scans[i + 1].0, rows, time.as_micros(), s, scan(i + 1));
+    }
+    if let Some(rows) = self.post { s = format!(r#"{{"node":"where after join","rows":{},"input":{}}}"#, rows, s); }
+    format!(r#"{},"input":{}}}"#, select, s)
+  }
+}
+
+pub fn select<'a, 'b>(s: &Select<'a>, db: &'b Db) -> Result<'a, SelectResult<'b>> {
+  unsafe { select_impl(s, db, None) }
+}
+
+// like `select`, also return the numbers of each step
+pub fn select_analyze<'a, 'b>(s: &Select<'a>, db: &'b Db) -> Result<'a, (SelectResult<'b>, Analyze<'a>)> {
+  let mut a = Analyze::default();
+  unsafe { Ok((select_impl(s, db, Some(&mut a))?, a)) }
+}
+
+pub(crate) unsafe fn select_impl<'a, 'b>(s: &Select<'a>, db: &'b Db, mut analyze: Option<&mut Analyze<'a>>) -> Result<'a, SelectResult<'b>> {
+  let start = Instant::now();
+  db.reset_random();
+  let ret = select_inner(s, db, &mut analyze)?;
+  if let Some(a) = analyze { (a.rows = ret.row_count(), a.time = start.elapsed()); }
+  Ok(ret)
+}
+
+unsafe fn select_inner<'a, 'b>(s: &Select<'a>, db: &'b Db, analyze: &mut Option<&mut Analyze<'a>>) -> Result<'a, SelectResult<'b>> {
+  if !s.with.is_empty() { return cte::select(s, db, analyze.as_mut().map(|a| &mut **a)); }
+  select_main(s, db, analyze)
+}
+
+// the select without its ctes, `cte::select` runs it after the ctes
+pub(crate) unsafe fn select_main<'a, 'b>(s: &Select<'a>, db: &'b Db, analyze: &mut Option<&mut Analyze<'a>>) -> Result<'a, SelectResult<'b>> {
+  {
     let db = db.pr();
+    if s.tables.iter().any(|t| catalog::is_catalog(t)) { return catalog::select(s, db, analyze.as_mut().map(|a| &mut **a)); }
     let tbl_num = s.tables.len();
-    if tbl_num == 0 { return Ok(SelectResult { cols: vec![], data: vec![] }); }
+    if tbl_num == 0 { return Ok(SelectResult { cols: vec![], data: vec![], tbls: vec![], _catalog: None, _consts: vec![], exprs: vec![] }); }
     macro_rules! at { ($arr: expr, $x: expr, $y: expr) => { $arr.get_unchecked_mut($x * tbl_num + $y) }; }
     let mut tbls = IndexMap::default();
     let mut cols = HashMap::new();
@@ -194,6 +573,85 @@ pub fn select<'a, 'b>(s: &Select<'a>, db: &'b Db) -> Result<'a, SelectResult<'b>
       }
     }
     let ctx = SelectCtx { tbls, cols };
+    // the expression cols in the select list and group by, they are evaluated on the rows before aggregation
+    let (mut exprs, mut expr_re) = (Vec::new(), HashMap::new());
+    let (cols, group) = (ctx.mk_cols(db, &s.ops, &mut exprs, &mut expr_re)?, ctx.mk_group(db, &s.group_by, &mut exprs, &mut expr_re)?);
+    // without group by, agg col and non-agg col can't be selected together; with group by, non-agg col must be in group by
+    // constant cols can be selected with both
+    if if group.is_empty() { cols.iter().any(|col| col.op.is_some()) && cols.iter().any(|col| col.op.is_none() && col.lit.is_none()) } else {
+      cols.iter().any(|col| col.op.is_none() && col.lit.is_none() && !group.iter().any(|g| g.same_col(col)))
+    } { return Err(MixedSelect); }
+    let mut filters = Vec::new();
+    for agg in s.ops.iter().flatten() {
+      if agg.op.is_none() && !agg.filter.is_empty() { return Err(FilterOnNonAgg(agg.col.col)); }
+      // keep `filters` in the same length as `cols`
+      if let Some((_, tp)) = ctx.tbl_all(agg)? { (0..tp.col_num).for_each(|_| filters.push(AggFilter::new())); continue; }
+      let mut preds = AggFilter::with_capacity(agg.filter.len());
+      for cond in &agg.filter { preds.push(ctx.row_pred(db.pr(), cond)?); }
+      filters.push(preds);
+    }
+    // each key is (col or expression, its index in `cols` if selected, desc)
+    let mut keys = Vec::with_capacity(s.order_by.len());
+    let mut re_cache = HashMap::new();
+    for (i, o) in s.order_by.iter().enumerate() {
+      let (col, idx) = match o.key {
+        OrderKey::Expr(ref e) => {
+          // like a col key, with group by, the cols should be in group by
+          let ty = |cr: &ColRef<'a>| {
+            let (tp, ci, tbl) = ctx.one_where(cr)?;
+            let ci_id = ci.idx(&tp.cols);
+            if !group.is_empty() && !group.iter().any(|g| g.tbl == tbl && g.ci.map(|x| x.0) == Some(ci_id)) { return Err(MixedSelect); }
+            Ok(ci.ty)
+          };
+          expr::check(db, e, &ty, &mut re_cache)?;
+          keys.push((SortKey::Expr(i), None, o.desc));
+          continue;
+        }
+        OrderKey::Pos(pos) => {
+          let idx = (pos as usize).wrapping_sub(1);
+          (*cols.get(idx).ok_or(InvalidOrderPos(pos))?, Some(idx))
+        }
+        OrderKey::Agg(ref agg) => {
+          if ctx.tbl_all(agg)?.is_some() { return Err(OrderByNotSelected); }
+          let col = ctx.mk_col(agg)?;
+          let idx = if agg.filter.is_empty() {
+            cols.iter().enumerate().position(|(idx, c)| c.same_agg(&col) && filters.get(idx).map(|f| f.is_empty()).unwrap_or(true))
+          } else { None };
+          (col, idx)
+        }
+      };
+      if col.lit.is_some() { continue; } // all rows have the same value
+      if col.op.is_none() && !group.is_empty() && !group.iter().any(|g| g.same_col(&col)) { return Err(MixedSelect); }
+      keys.push((SortKey::Col(col), idx, o.desc));
+    }
+    // an agg key (or an expression col by its position) can only be sorted after aggregation, then all keys are sorted there by the selected cols
+    let post_order = if keys.iter().any(|(k, _, _)| if let SortKey::Col(col) = k { col.op.is_some() || col.expr.is_some() } else { false }) {
+      keys.iter().map(|&(_, idx, desc)| idx.map(|idx| (idx, desc)).ok_or(OrderByNotSelected)).collect::<Result<Vec<_>>>()?
+    } else { vec![] };
+    // otherwise rows are sorted before aggregation; with group by, the order of groups follows the order of their first row
+    let order = if post_order.is_empty() { keys.iter().map(|&(col, _, desc)| (col, desc)).collect::<Vec<_>>() } else { vec![] };
+    // `select count(*) from t where ...` only needs the number of accepted records, so they are not collected
+    if let [Col { op: Some(CountAll), .. }] = cols[..] {
+      if tbl_num == 1 && group.is_empty() && filters.iter().all(|f| f.is_empty()) {
+        let (_, &(tp_id, tp)) = ctx.tbls.get_index(0).unchecked_unwrap();
+        let start = Instant::now();
+        let (cnt, seeks) = count(db, &s.where_, tp_id)?;
+        if let Some(a) = analyze { a.scans.push((s.tables[0], cnt, tp.count as usize, seeks, start.elapsed())); }
+        let data = if s.limit == Some(0) { vec![] } else { vec![CLit::new(Lit::Number(cnt as f64))] };
+        return Ok(SelectResult { cols, data, tbls: ctx.tbl_names(), _catalog: None, _consts: vec![], exprs: vec![] });
+      }
+    }
+    // for a single table, `order by col limit n` (asc) can scan the index on `col` and stop early, so no sort is needed
+    let index_order = match (order.as_slice(), s.limit) {
+      (&[(SortKey::Col(col), false)], Some(_)) if tbl_num == 1 && group.is_empty() && cols.iter().all(|col| col.op.is_none()) => {
+        let (ci_id, ci) = col.ci.unchecked_unwrap();
+        let null_rejected = ci.flags.intersects(ColFlags::NOTNULL1) || s.where_.iter().any(|cond| match cond {
+          Cond::Cmp(_, l, Atom::Lit(r)) => l.col == ci.name() && !r.is_null(), _ => false,
+        });
+        Some(ci_id).filter(|_| ci.index != !0 && db.index_usable(ci) && null_rejected)
+      }
+      _ => None,
+    };
 
     let mut one_preds = Vec::with_capacity(tbl_num);
     // `cross_preds` is 2-d array, dim = tbl_num * tbl_num
@@ -205,14 +663,29 @@ pub fn select<'a, 'b>(s: &Select<'a>, db: &'b Db) -> Result<'a, SelectResult<'b>
     for _ in 0..tbl_num { one_preds.push(vec![]); } // Box<Fn> is not Clone, so must use loop to push
     for _ in 0..tbl_num * tbl_num { cross_preds.push(vec![]); }
     let mut one_wheres = vec![vec![]; tbl_num];
-    for cond in &s.where_ {
-      let (l, r) = (cond.lhs_col(), cond.rhs_col_op());
+    // the right table of left join, !0 for none
+    let lt = if s.left_on.is_some() { tbl_num - 1 } else { !0 };
+    // where on `lt` is applied after join, because `lt` can be null there
+    // on that doesn't involve `lt` decides whether a row of the other tables has any match
+    let (mut post, mut on_others) = (AggFilter::new(), AggFilter::new());
+    for (cond, on) in s.where_.iter().map(|c| (c, false)).chain(s.left_on.iter().flatten().map(|c| (c, true))) {
+      let involve = ctx.one_where(cond.lhs_col())?.2 == lt || match cond.rhs_col() { Some(r) => ctx.one_where(r)?.2 == lt, None => false };
+      if involve != on {
+        (if on { &mut on_others } else { &mut post }).push(ctx.row_pred(db.pr(), cond)?);
+        continue;
+      }
+      let (l, r) = (cond.lhs_col(), cond.rhs_col());
       let (mut tp_l, mut ci_l, mut idx_l) = ctx.one_where(l)?;
-      if let Some(((mut tp_r, mut ci_r, mut idx_r), mut op)) = {
-        if let Some((r, op)) = r {
-          Some((ctx.one_where(r)?, op)).filter(|((_, _, idx_r), _)| *idx_r != idx_l)
+      if let Some(((mut tp_r, mut ci_r, mut idx_r), op)) = {
+        if let Some(r) = r {
+          Some((ctx.one_where(r)?, cond.rhs_col_op().map(|x| x.1))).filter(|((_, _, idx_r), _)| *idx_r != idx_l)
         } else { None }
       } { // not in one table
+        let mut op = if let Some(op) = op { op } else { // `LikeCol`, it is not symmetric, so build the predicate before swapping
+          let p = like_col_predicate(db.pr(), (ci_l, ci_r), (tp_l, tp_r))?;
+          if idx_l > idx_r { at!(cross_preds, idx_l, idx_r).push(p); } else { at!(cross_preds, idx_r, idx_l).push(box move |x: (*const u8, *const u8)| p((x.1, x.0))); }
+          continue;
+        };
         if idx_l < idx_r {
           op = op.rev();
           mem::swap(&mut tp_l, &mut tp_r);
@@ -235,15 +708,24 @@ pub fn select<'a, 'b>(s: &Select<'a>, db: &'b Db) -> Result<'a, SelectResult<'b>
       let (_, &(tp_id, tp)) = ctx.tbls.get_index(idx).unchecked_unwrap();
       let where_ = one_wheres.get_unchecked(idx);
       let one_result = one_results.get_unchecked_mut(idx);
-      filter(db, where_, tp_id, and(pred), |x, _| {
-        // remove some null data, it can optimize a little, but mainly for making later handling easier
-        // if it participate in any comparison, then reject null results, so later the sort + binary search can avoid handling null
-        if (0..idx).all(|idx1| at!(cross_cols, idx, idx1).map(|(_, ci, _)| !is_null(x, ci.idx(&tp.cols))).unwrap_or(true)) &&
-          (idx + 1..tbl_num).all(|idx1| at!(cross_cols, idx1, idx).map(|(_, _, ci)| !is_null(x, ci.idx(&tp.cols))).unwrap_or(true)) {
-          one_result.push(x as *const u8);
-        }
-        Ok(())
-      }, true).unchecked_unwrap();
+      let start = Instant::now();
+      let seeks = if let Some(ci_id) = index_order { // there is only one table, so no cross predicate
+        let read = filter_ordered(db, where_, tp_id, ci_id, s.limit.unchecked_unwrap() as usize, and(pred), |x, _| (one_result.push(x as *const u8), Ok(())).1)?;
+        if let Some(a) = analyze { a.ordered = Some((tp.cols().get_unchecked(ci_id as usize).name().to_owned(), read)); }
+        1
+      } else {
+        filter(db, where_, tp_id, and(pred), |x, _| {
+          // remove some null data, it can optimize a little, but mainly for making later handling easier
+          // if it participate in any comparison, then reject null results, so later the sort + binary search can avoid handling null
+          if (0..idx).all(|idx1| at!(cross_cols, idx, idx1).map(|(_, ci, _)| !is_null(x, ci.idx(&tp.cols))).unwrap_or(true)) &&
+            // for left join, the other tables' rows can't be rejected, they are kept even if there is no match
+            (idx + 1..tbl_num).all(|idx1| idx1 == lt || at!(cross_cols, idx1, idx).map(|(_, _, ci)| !is_null(x, ci.idx(&tp.cols))).unwrap_or(true)) {
+            one_result.push(x as *const u8);
+          }
+          Ok(())
+        }, true)? // the callback never fails, but `filter` may find a corrupt record
+      };
+      if let Some(a) = analyze { a.scans.push((s.tables[idx], one_result.len(), tp.count as usize, seeks, start.elapsed())); }
     }
 
     let res0 = one_results.get_unchecked(0);
@@ -252,56 +734,108 @@ pub fn select<'a, 'b>(s: &Select<'a>, db: &'b Db) -> Result<'a, SelectResult<'b>
     for (i, &x) in res0.iter().enumerate() {
       final_.as_mut_ptr().add(i * tbl_num).write(x);
     }
+    db.check_rows(res0.len())?;
 
     for idx_r in 1..one_results.len() {
+      let start = Instant::now();
       let rs = one_results.get_unchecked_mut(idx_r);
       let mut new_final_ = Vec::<*const u8>::new();
+      let outer = idx_r == lt;
+      macro_rules! push {
+        ($old_row: expr, $r: expr) => {
+          let old_len = new_final_.len();
+          new_final_.reserve(tbl_num);
+          new_final_.set_len(old_len + tbl_num);
+          new_final_.as_mut_ptr().add(old_len).copy_from_nonoverlapping($old_row, tbl_num);
+          *new_final_.get_unchecked_mut(old_len + idx_r) = $r;
+          db.check_rows(new_final_.len() / tbl_num)?;
+        };
+      }
       macro_rules! join {
         ($old_row: expr, $range: expr) => {
-          for &r in rs.get_unchecked($range) {
-            if (0..idx_r).all(|idx_l| at!(cross_preds, idx_r, idx_l)((r, *$old_row.add(idx_l)))) {
-              let old_len = new_final_.len();
-              new_final_.reserve(tbl_num);
-              new_final_.set_len(old_len + tbl_num);
-              new_final_.as_mut_ptr().add(old_len).copy_from_nonoverlapping($old_row, tbl_num);
-              *new_final_.get_unchecked_mut(old_len + idx_r) = r;
+          let old_len = new_final_.len();
+          if !outer || on_others.iter().all(|p| p(slice::from_raw_parts($old_row, idx_r))) {
+            for &r in rs.get_unchecked($range) {
+              if (0..idx_r).all(|idx_l| at!(cross_preds, idx_r, idx_l)((r, *$old_row.add(idx_l)))) { push!($old_row, r); }
             }
           }
+          // for left join, a row without any match is kept, with a null data ptr for `lt`
+          if outer && new_final_.len() == old_len { push!($old_row, ptr::null()); }
         };
       }
       if let Some((idx_l, (op, ci_r, ci_l))) = (0..idx_r).filter_map(|idx_l| at!(cross_cols,idx_r, idx_l).map(|x| (idx_l, x))).next() {
         let (off_l, off_r) = (ci_l.off as usize, ci_r.off as usize);
+        let l_id = ci_l.idx(&ctx.tbls.get_index(idx_l).unchecked_unwrap().1 .1.cols);
+        let coll = db.collation();
         match ci_r.ty.fix_ty().ty {
           Bool => rs.sort_unstable_by_key(|&x| *(x.add(off_r) as *const bool)),
           Int => rs.sort_unstable_by_key(|&x| *(x.add(off_r) as *const i32)),
           // note that both `l` and `r` use `off_r` here, because they are both from the `rs`
           Float => rs.sort_unstable_by(|&l, &r| fcmp(*(l.add(off_r) as *const f32), *(r.add(off_r) as *const f32))),
           Date => rs.sort_unstable_by_key(|&x| *(x.add(off_r) as *const NaiveDate)),
-          Char => rs.sort_unstable_by_key(|&x| str_from_db(x.add(off_r))),
+          Char => rs.sort_unstable_by(|&l, &r| coll.cmp(str_from_db(l.add(off_r)), str_from_db(r.add(off_r)))),
         }
         for old_idx in 0..(final_.len() / tbl_num) {
+          db.check_cancel()?;
           let old_row = final_.as_ptr().add(old_idx * tbl_num);
           let l = (*old_row.add(idx_l)).add(off_l);
-          let rg = match ci_r.ty.fix_ty().ty {
-            Bool => rs.equal_range_by(|&r| (*(r.add(off_r) as *const bool)).cmp(&*(l as *const bool))),
-            Int => rs.equal_range_by(|&r| (*(r.add(off_r) as *const i32)).cmp(&*(l as *const i32))),
-            Float => rs.equal_range_by(|&r| fcmp(*(r.add(off_r) as *const f32), *(l as *const f32))),
-            Date => rs.equal_range_by(|&r| (*(r.add(off_r) as *const NaiveDate)).cmp(&*(l as *const NaiveDate))),
-            Char => rs.equal_range_by(|&r| str_from_db(r.add(off_r)).cmp(str_from_db(l))),
-          };
-          let rg = match op {
-            Lt => 0..rg.start, Le => 0..rg.end, Ge => rg.start..rs.len(), Gt => rg.end..rs.len(), Eq => rg, Ne => impossible!(),
+          // `l` can only be null for left join (otherwise it is rejected in scan), and null matches nothing
+          let rg = if is_null(*old_row.add(idx_l), l_id) { 0..0 } else {
+            let rg = match ci_r.ty.fix_ty().ty {
+              Bool => rs.equal_range_by(|&r| (*(r.add(off_r) as *const bool)).cmp(&*(l as *const bool))),
+              Int => rs.equal_range_by(|&r| (*(r.add(off_r) as *const i32)).cmp(&*(l as *const i32))),
+              Float => rs.equal_range_by(|&r| fcmp(*(r.add(off_r) as *const f32), *(l as *const f32))),
+              Date => rs.equal_range_by(|&r| (*(r.add(off_r) as *const NaiveDate)).cmp(&*(l as *const NaiveDate))),
+              Char => rs.equal_range_by(|&r| coll.cmp(str_from_db(r.add(off_r)), str_from_db(l))),
+            };
+            match op {
+              Lt => 0..rg.start, Le => 0..rg.end, Ge => rg.start..rs.len(), Gt => rg.end..rs.len(), Eq => rg, Ne => impossible!(),
+            }
           };
           join!(old_row, rg);
         }
       } else {
         for old_idx in 0..(final_.len() / tbl_num) {
+          db.check_cancel()?;
           let old_row = final_.as_ptr().add(old_idx * tbl_num);
           join!(old_row, ..);
         }
       }
       final_ = new_final_;
+      if let Some(a) = analyze { a.joins.push((final_.len() / tbl_num, start.elapsed())); }
+    }
+    if !post.is_empty() {
+      final_ = final_.chunks(tbl_num).filter(|&row| post.iter().all(|p| p(row))).flat_map(|row| row.iter().copied()).collect();
+      if let Some(a) = analyze { a.post = Some(final_.len() / tbl_num); }
+    }
+    if !order.is_empty() && index_order.is_none() {
+      let row_num = final_.len() / tbl_num;
+      // an expression key is evaluated once for each row, cols in it read the row's data slots
+      // so `order by random() limit n` samples n rows, but all rows are still collected and sorted (there is no reservoir sampling)
+      let vals = order.iter().map(|&(k, _)| match k {
+        SortKey::Expr(i) => if let OrderKey::Expr(e) = &s.order_by.get_unchecked(i).key { ctx.eval_rows(db, e, &final_, tbl_num, &re_cache) } else { impossible!() },
+        SortKey::Col(_) => Ok(vec![]),
+      }).collect::<Result<Vec<Vec<_>>>>()?;
+      let key = |i: usize, k: usize| match order.get_unchecked(k).0 {
+        SortKey::Col(col) => {
+          let (ci_id, ci) = col.ci.unchecked_unwrap();
+          db.data2lit(*final_.get_unchecked(i * tbl_num + col.tbl), ci_id, ci)
+        }
+        SortKey::Expr(_) => *vals.get_unchecked(k).get_unchecked(i),
+      };
+      let mut rows = (0..row_num).collect::<Vec<_>>();
+      let coll = db.collation();
+      // stable sort, rows with equal keys keep their original order
+      rows.sort_by(|&l, &r| order.iter().enumerate().map(|(k, &(_, desc))| {
+        let ord = cmp_null_first(key(l, k), key(r, k), coll);
+        if desc { ord.reverse() } else { ord }
+      }).find(|&ord| ord != Ordering::Equal).unwrap_or(Ordering::Equal));
+      final_ = rows.iter().flat_map(|&i| final_.get_unchecked(i * tbl_num..(i + 1) * tbl_num).iter().copied()).collect();
     }
-    Ok(SelectResult::new(db, &ctx.mk_tbls(&s.ops)?, &final_))
+    let exprs = exprs.into_iter().map(|(text, e)| Ok((text, ctx.eval_rows(db, e, &final_, tbl_num, &expr_re)?))).collect::<Result<Vec<_>>>()?;
+    let mut ret = SelectResult::new(db, cols, ctx.tbl_names(), &filters, &group, tbl_num, &final_, exprs);
+    if !post_order.is_empty() { ret.sort(&post_order, db.collation()); }
+    if let Some(limit) = s.limit { ret.data.truncate(limit as usize * ret.cols.len()); }
+    Ok(ret)
   }
 }
\ No newline at end of file
@@ -1,21 +1,29 @@
 use common::*;
 use std::fmt;
 
+#[cfg_attr(feature = "serialize", derive(serde::Serialize))]
 #[derive(derive_more::From, Debug)]
 pub enum Stmt<'a> {
   Insert(Insert<'a>),
+  InsertSelect(InsertSelect<'a>),
   Delete(Delete<'a>),
   Select(Select<'a>),
+  // `select ... into outfile 'path'`, the result is written to `path` as csv, instead of returned
+  SelectOutfile { select: Select<'a>, path: &'a str },
   Update(Update<'a>),
   CreateDb(&'a str),
   DropDb(&'a str),
   ShowDb(&'a str),
   ShowDbs,
   UseDb(&'a str),
+  RenameDb { old: &'a str, new: &'a str },
   CreateTable(CreateTable<'a>),
+  // `create table table like like [including data]`
+  CreateTableLike { table: &'a str, like: &'a str, data: bool },
   DropTable(&'a str),
   ShowTable(&'a str),
   ShowTables,
+  ShowIndexes(&'a str),
   CreateIndex(CreateIndex<'a>),
   DropIndex {
     index: &'a str,
@@ -24,6 +32,7 @@ pub enum Stmt<'a> {
     table: Option<&'a str>,
   },
   Rename { old: &'a str, new: &'a str },
+  RenameCol { table: &'a str, old: &'a str, new: &'a str },
   AddForeign(AddForeign<'a>),
   DropForeign { table: &'a str, col: &'a str },
   AddPrimary { table: &'a str, cols: Vec<&'a str> },
@@ -32,13 +41,34 @@ pub enum Stmt<'a> {
   DropCol { table: &'a str, col: &'a str },
 }
 
+#[cfg_attr(feature = "serialize", derive(serde::Serialize))]
 #[derive(Debug)]
 pub struct Insert<'a> {
   pub table: &'a str,
   pub cols: Option<Vec<&'a str>>,
   pub vals: Vec<Vec<CLit<'a>>>,
+  pub on_conflict: Option<OnConflict<'a>>,
 }
 
+// on conflict (col) do nothing / do update set ..., `sets` is None for do nothing
+// in `sets`, `excluded.col` refers to the value of the row being inserted
+#[cfg_attr(feature = "serialize", derive(serde::Serialize))]
+#[derive(Debug)]
+pub struct OnConflict<'a> {
+  pub col: &'a str,
+  pub sets: Option<Vec<(&'a str, Expr<'a>)>>,
+}
+
+// insert into table [(cols)] select ...
+#[cfg_attr(feature = "serialize", derive(serde::Serialize))]
+#[derive(Debug)]
+pub struct InsertSelect<'a> {
+  pub table: &'a str,
+  pub cols: Option<Vec<&'a str>>,
+  pub select: Select<'a>,
+}
+
+#[cfg_attr(feature = "serialize", derive(serde::Serialize))]
 #[derive(Debug)]
 pub struct Update<'a> {
   pub table: &'a str,
@@ -46,20 +76,41 @@ pub struct Update<'a> {
   pub where_: Vec<Cond<'a>>,
 }
 
+#[cfg_attr(feature = "serialize", derive(serde::Serialize))]
 #[derive(Debug)]
 pub struct Select<'a> {
   // None for select *
   pub ops: Option<Vec<Agg<'a>>>,
   pub tables: Vec<&'a str>,
+  // `left join t on ...`: `t` is the last one in `tables` and this is the `on` list; None for no left join
+  pub left_on: Option<Vec<Cond<'a>>>,
   pub where_: Vec<Cond<'a>>,
+  // empty for no group by, each key is a col (`Expr::Atom`) or an arithmetic expression on the cols
+  pub group_by: Vec<Expr<'a>>,
+  // empty for no order by
+  pub order_by: Vec<OrderBy<'a>>,
+  pub limit: Option<u32>,
+  // the ctes of `with name as (select ...), ... select ...`, empty for no with
+  pub with: Vec<Cte<'a>>,
 }
 
+// `name [(cols)] as (select ...)`, without `cols`, the cols are named by the header of the select
+#[cfg_attr(feature = "serialize", derive(serde::Serialize))]
+#[derive(Debug)]
+pub struct Cte<'a> {
+  pub name: &'a str,
+  pub cols: Option<Vec<&'a str>>,
+  pub select: Select<'a>,
+}
+
+#[cfg_attr(feature = "serialize", derive(serde::Serialize))]
 #[derive(Debug)]
 pub struct Delete<'a> {
   pub table: &'a str,
   pub where_: Vec<Cond<'a>>,
 }
 
+#[cfg_attr(feature = "serialize", derive(serde::Serialize))]
 #[derive(Copy, Clone)]
 pub struct ColRef<'a> {
   pub table: Option<&'a str>,
@@ -67,11 +118,37 @@ pub struct ColRef<'a> {
 }
 
 // Agg is short for Aggregation
+#[cfg_attr(feature = "serialize", derive(serde::Serialize))]
 pub struct Agg<'a> {
   pub col: ColRef<'a>,
   pub op: Option<AggOp>,
+  // `filter (where ...)`, only rows satisfying all of them are aggregated; empty for no filter
+  pub filter: Vec<Cond<'a>>,
+  // `op(distinct col)`, only for avg, sum and count
+  pub distinct: bool,
+  // a constant in the select list, e.g., `select 'label', name from t`, `col` is meaningless then
+  pub lit: Option<CLit<'a>>,
+  // an arithmetic expression in the select list, e.g., `select price * quantity from t`, `col` is meaningless then
+  pub expr: Option<Expr<'a>>,
 }
 
+#[cfg_attr(feature = "serialize", derive(serde::Serialize))]
+pub struct OrderBy<'a> {
+  pub key: OrderKey<'a>,
+  pub desc: bool,
+}
+
+#[cfg_attr(feature = "serialize", derive(serde::Serialize))]
+pub enum OrderKey<'a> {
+  // a col, or an agg which should also be in the select list (without filter)
+  Agg(Agg<'a>),
+  // `order by 2`, the position in the select list, starting from 1
+  Pos(u32),
+  // an arithmetic expression on the cols of a row, e.g., `order by price * quantity`
+  Expr(Expr<'a>),
+}
+
+#[cfg_attr(feature = "serialize", derive(serde::Serialize))]
 #[derive(Debug)]
 pub struct CreateTable<'a> {
   pub table: &'a str,
@@ -79,6 +156,7 @@ pub struct CreateTable<'a> {
   pub cons: Vec<ColCons<'a>>,
 }
 
+#[cfg_attr(feature = "serialize", derive(serde::Serialize))]
 #[derive(Debug)]
 pub struct CreateIndex<'a> {
   pub index: &'a str,
@@ -86,6 +164,7 @@ pub struct CreateIndex<'a> {
   pub col: &'a str,
 }
 
+#[cfg_attr(feature = "serialize", derive(serde::Serialize))]
 #[derive(Debug)]
 pub struct AddForeign<'a> {
   pub table: &'a str,
@@ -94,33 +173,61 @@ pub struct AddForeign<'a> {
   pub f_col: &'a str,
 }
 
+#[cfg_attr(feature = "serialize", derive(serde::Serialize))]
 #[derive(Debug)]
 pub struct ColDecl<'a> {
   pub col: &'a str,
   pub ty: ColTy,
   pub notnull: bool,
-  pub dft: Option<CLit<'a>>,
+  pub dft: Option<Dft<'a>>,
+  pub comment: Option<&'a str>,
+}
+
+#[cfg_attr(feature = "serialize", derive(serde::Serialize))]
+#[derive(Debug, Copy, Clone)]
+pub enum Dft<'a> {
+  Lit(CLit<'a>),
+  // evaluated on each insert
+  CurrentDate,
 }
 
 // Cons for Constraint
+#[cfg_attr(feature = "serialize", derive(serde::Serialize))]
 #[derive(Debug)]
 pub enum ColCons<'a> {
   Primary(Vec<&'a str>),
   Foreign { col: &'a str, f_table: &'a str, f_col: &'a str },
   Unique(&'a str),
   Check(&'a str, Vec<CLit<'a>>),
+  // a check over the whole row, the str is its source text (without the parens), which is what gets stored
+  RowCheck(&'a str, Vec<Cond<'a>>),
 }
 
-#[derive(Copy, Clone)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize))]
 pub enum Cond<'a> {
   Cmp(CmpOp, ColRef<'a>, Atom<'a>),
   // true for `is null`, false for `is not null`
   Null(ColRef<'a>, bool),
   Like(ColRef<'a>, &'a str),
+  // the pattern is the value of another col, it is compiled for each row, so it is much slower than `Like`
+  LikeCol(ColRef<'a>, ColRef<'a>),
+  // col % m cmp lit, only for int col
+  Mod(ColRef<'a>, i32, CmpOp, CLit<'a>),
+  // length(col) cmp int, only for char and varchar col, the length is in bytes
+  Len(ColRef<'a>, CmpOp, i32),
+  // col in (select ...), the select should have only one col
+  In(ColRef<'a>, Box<Select<'a>>),
+  // col cmp (select ...), the select should have only one col and at most one row
+  CmpSelect(CmpOp, ColRef<'a>, Box<Select<'a>>),
+  // col in (lit, ...), the list can be empty
+  InList(ColRef<'a>, Vec<CLit<'a>>),
+  // col not in (lit, ...), like in sql, a null in the list makes it unknown for every row, and `not in ()` is true even for null
+  NotInList(ColRef<'a>, Vec<CLit<'a>>),
 }
 
-// this is arithmetic expr, only appears in the set list of update, not in where list of select and delete
+// this is arithmetic expr, it appears in the set list of update, and in order by, group by and the select list of select, not in where list of select and delete
 // Cond is a proper subset of Expr
+#[cfg_attr(feature = "serialize", derive(serde::Serialize))]
 pub enum Expr<'a> {
   Atom(Atom<'a>),
   Null(Box<Expr<'a>>, bool),
@@ -129,11 +236,18 @@ pub enum Expr<'a> {
   Or(Box<(Expr<'a>, Expr<'a>)>),
   Cmp(CmpOp, Box<(Expr<'a>, Expr<'a>)>),
   Bin(BinOp, Box<(Expr<'a>, Expr<'a>)>),
+  // `name(args...)`, a user-defined scalar function
+  Call(&'a str, Vec<Expr<'a>>),
 }
 
 impl<'a> Cond<'a> {
   pub fn lhs_col(&self) -> &ColRef<'a> {
-    match self { Cond::Cmp(_, l, _) | Cond::Null(l, _) | Cond::Like(l, _) => l }
+    match self { Cond::Cmp(_, l, _) | Cond::Null(l, _) | Cond::Like(l, _) | Cond::LikeCol(l, _) | Cond::Mod(l, ..) | Cond::Len(l, ..) | Cond::In(l, _) | Cond::CmpSelect(_, l, _) | Cond::InList(l, _) | Cond::NotInList(l, _) => l }
+  }
+
+  // the rhs col of comparison or `LikeCol`
+  pub fn rhs_col(&self) -> Option<&ColRef<'a>> {
+    match self { Cond::Cmp(_, _, Atom::ColRef(r)) | Cond::LikeCol(_, r) => Some(r), _ => None }
   }
 
   pub fn rhs_col_op(&self) -> Option<(&ColRef<'a>, CmpOp)> {
@@ -141,6 +255,7 @@ impl<'a> Cond<'a> {
   }
 }
 
+#[cfg_attr(feature = "serialize", derive(serde::Serialize))]
 #[derive(Copy, Clone)]
 pub enum Atom<'a> { ColRef(ColRef<'a>), Lit(CLit<'a>) }
 
@@ -150,9 +265,25 @@ impl fmt::Debug for ColRef<'_> {
   }
 }
 
+impl fmt::Debug for OrderBy<'_> {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match &self.key { OrderKey::Agg(agg) => write!(f, "{:?}", agg)?, OrderKey::Pos(pos) => write!(f, "{}", pos)?, OrderKey::Expr(e) => write!(f, "{:?}", e)? }
+    write!(f, " {}", if self.desc { "desc" } else { "asc" })
+  }
+}
+
 impl fmt::Debug for Agg<'_> {
   fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-    if let Some(op) = self.op { write!(f, "{}({:?})", op.name(), self.col) } else { write!(f, "{:?}", self.col) }
+    if let Some(lit) = self.lit { return write!(f, "{:?}", lit); }
+    if let Some(e) = &self.expr { return write!(f, "{:?}", e); }
+    match self.op {
+      Some(op) => if let Some(p) = op.p() {
+        write!(f, "{}({}) within group (order by {:?})", op.name(), p, self.col)?;
+      } else { write!(f, "{}({}{:?})", op.name(), if self.distinct { "distinct " } else { "" }, self.col)?; }
+      None => write!(f, "{:?}", self.col)?,
+    }
+    if !self.filter.is_empty() { write!(f, " filter (where {:?})", self.filter)?; }
+    Ok(())
   }
 }
 
@@ -168,6 +299,16 @@ impl fmt::Debug for Cond<'_> {
       Cond::Cmp(op, l, r) => write!(f, "{:?} {} {:?}", l, op.name(), r),
       Cond::Null(x, null) => write!(f, "{:?} is {}null", x, if *null { "" } else { "not " }),
       Cond::Like(x, like) => write!(f, "{:?} like '{}'", x, like),
+      Cond::LikeCol(x, like) => write!(f, "{:?} like {:?}", x, like),
+      Cond::Mod(x, m, op, r) => write!(f, "{:?} % {} {} {:?}", x, m, op.name(), r),
+      Cond::Len(x, op, r) => write!(f, "length({:?}) {} {}", x, op.name(), r),
+      Cond::In(x, s) => write!(f, "{:?} in ({:?})", x, s),
+      Cond::CmpSelect(op, x, s) => write!(f, "{:?} {} ({:?})", x, op.name(), s),
+      Cond::InList(x, l) | Cond::NotInList(x, l) => {
+        write!(f, "{:?} {}in (", x, if let Cond::NotInList(..) = self { "not " } else { "" })?;
+        for (i, v) in l.iter().enumerate() { write!(f, "{}{:?}", if i == 0 { "" } else { ", " }, v)?; }
+        write!(f, ")")
+      }
     }
   }
 }
@@ -180,6 +321,11 @@ impl fmt::Debug for Expr<'_> {
       Expr::Like(x, like) => write!(f, "({:?}) like '{}'", x, like),
       Expr::And(box (l, r)) => write!(f, "({:?}) and ({:?})", l, r), Expr::Or(box (l, r)) => write!(f, "({:?}) or ({:?})", l, r),
       Expr::Cmp(op, box (l, r)) => write!(f, "({:?}) {} ({:?})", l, op.name(), r), Expr::Bin(op, box (l, r)) => write!(f, "({:?}) {} ({:?})", l, op.name(), r),
+      Expr::Call(name, args) => {
+        write!(f, "{}(", name)?;
+        for (idx, a) in args.iter().enumerate() { write!(f, "{}{:?}", if idx == 0 { "" } else { ", " }, a)?; }
+        write!(f, ")")
+      }
     }
   }
 }
\ No newline at end of file
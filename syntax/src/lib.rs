@@ -11,6 +11,26 @@ use typed_arena::Arena;
 
 use common::{ParserError as PE, ParserErrorKind::*, Error};
 
+// the category of a token, e.g., for highlighting
+// keywords are not listed, so a new keyword in the lexer is automatically a `Keyword`
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum TokenClass { Punct, Lit, Ty, Agg, Ident, Keyword, End }
+
+impl TokenKind {
+  pub fn class(self) -> TokenClass {
+    use TokenKind::*;
+    match self {
+      Lt | Le | Ge | Gt | Eq | Ne | LPar | RPar | Add | Sub | Mul | Div | Mod | Comma | Semicolon => TokenClass::Punct,
+      Null | True | False | FloatLit | IntLit | StrLit | UnterminatedStr => TokenClass::Lit, // so the highlighter colors a string being typed
+      Int | Bool | Char | Varchar | Float | Date => TokenClass::Ty,
+      Sum | Avg | Min | Max | Count | PercentileDisc | PercentileCont => TokenClass::Agg,
+      Id1 | Dot => TokenClass::Ident,
+      _Err | _Eof => TokenClass::End,
+      _ => TokenClass::Keyword,
+    }
+  }
+}
+
 pub fn work<'a>(code: &'a str, alloc: &'a Arena<u8>) -> Result<Vec<Stmt<'a>>, Error<'a>> {
   let mut p = Parser { pe: vec![], alloc };
   match p.parse(&mut Lexer::new(code.as_bytes())) {
@@ -18,6 +38,7 @@ pub fn work<'a>(code: &'a str, alloc: &'a Arena<u8>) -> Result<Vec<Stmt<'a>>, Er
     Err(t) => {
       match t.ty {
         TokenKind::_Err => p.pe.push(PE { line: t.line, col: t.col, kind: UnexpectedChar(t.piece[0] as char) }),
+        TokenKind::UnterminatedStr => p.pe.push(PE { line: t.line, col: t.col, kind: UnterminatedString }),
         _ => p.pe.push(PE { line: t.line, col: t.col, kind: SyntaxError }),
       }
       Err(Error::ParserErrors(p.pe.into()))
@@ -1,7 +1,7 @@
 use std::str::{self, FromStr};
 use typed_arena::Arena;
 
-use common::{BareTy::{*, self}, FixTy, ColTy, ParserError as PE, ParserErrorKind::*, Lit, CLit, AggOp::*, BinOp::*, CmpOp::*};
+use common::{BareTy::{*, self}, FixTy, ColTy, ParserError as PE, ParserErrorKind::*, Lit, CLit, today, today_offset, parse_date, AggOp::*, BinOp::*, CmpOp::{*, self}};
 use crate::ast::*;
 use crate::Stmt::AddPrimary;
 
@@ -20,6 +20,21 @@ impl<'p> Parser<'p> {
       unsafe { str::from_utf8_unchecked(s) }
     } else { s }
   }
+
+  fn top_limit(&mut self, top: Option<Token<'p>>, limit: Option<u32>) -> Option<u32> {
+    match (top, limit) {
+      (Some(t), Some(_)) => (self.pe.push(PE { line: t.line, col: t.col, kind: TopWithLimit }), limit).1,
+      (Some(t), None) => t.parse(Some, |line, col, s| self.pe.push(PE { line, col, kind: InvalidInt(s) })),
+      (None, limit) => limit,
+    }
+  }
+
+  // the fraction of a percentile, stored by its bits (see `AggOp`)
+  fn percentile(&mut self, t: Token<'p>) -> u64 {
+    let p: f64 = t.parse(|p| p, |line, col, s| self.pe.push(PE { line, col, kind: InvalidFloat(s) }));
+    if !(0.0 <= p && p <= 1.0) { self.pe.push(PE { line: t.line, col: t.col, kind: InvalidPercentile(t.str()) }); }
+    p.to_bits()
+  }
 }
 
 impl<'p> Token<'p> {
@@ -27,7 +42,9 @@ impl<'p> Token<'p> {
   fn str(&self) -> &'p str { unsafe { str::from_utf8_unchecked(self.piece) } }
   fn parse<T: FromStr + Default, U>(&self, ok: impl Fn(T) -> U, mut err: impl FnMut(u32, u32, &'p str)) -> U {
     let s = self.str();
-    ok(s.parse().unwrap_or_else(|_| (err(self.line, self.col, s), T::default()).1))
+    // `_` can separate digits, e.g., 1_000
+    let res = if s.contains('_') { s.replace('_', "").parse() } else { s.parse() };
+    ok(res.unwrap_or_else(|_| (err(self.line, self.col, s), T::default()).1))
   }
 }
 
@@ -55,11 +72,14 @@ priority = [
 '(s|S)(h|H)(o|O)(w|W)' = 'Show'
 '(d|D)(e|E)(s|S)(c|C)' = 'Desc'
 '(a|A)(l|L)(t|T)(e|E)(r|R)\s+(t|T)(a|A)(b|B)(l|L)(e|E)' = 'AlterTable'
+'(a|A)(l|L)(t|T)(e|E)(r|R)\s+(d|D)(a|A)(t|T)(a|A)(b|B)(a|A)(s|S)(e|E)' = 'AlterDataBase'
 '(a|A)(d|D)(d|D)' = 'Add1'
 '(r|R)(e|E)(n|N)(a|A)(m|M)(e|E)\s+(t|T)(o|O)' = 'RenameTo'
+'(r|R)(e|E)(n|N)(a|A)(m|M)(e|E)\s+(c|C)(o|O)(l|L)(u|U)(m|M)(n|N)' = 'RenameColumn'
 '(d|D)(a|A)(t|T)(a|A)(b|B)(a|A)(s|S)(e|E)(s|S)' = 'DataBases'
 '(d|D)(a|A)(t|T)(a|A)(b|B)(a|A)(s|S)(e|E)' = 'DataBase'
 '(t|T)(a|A)(b|B)(l|L)(e|E)(s|S)' = 'Tables'
+'(i|I)(n|N)(d|D)(e|E)(x|X)(e|E)(s|S)' = 'Indexes'
 '(t|T)(a|A)(b|B)(l|L)(e|E)' = 'Table'
 '(s|S)(e|E)(l|L)(e|E)(c|C)(t|T)' = 'Select'
 '(d|D)(e|E)(l|L)(e|E)(t|T)(e|E)' = 'Delete'
@@ -70,12 +90,25 @@ priority = [
 '(s|S)(e|E)(t|T)' = 'Set'
 '(f|F)(r|R)(o|O)(m|M)' = 'From'
 '(w|W)(h|H)(e|E)(r|R)(e|E)' = 'Where'
+'(f|F)(i|I)(l|L)(t|T)(e|E)(r|R)' = 'Filter'
+'(g|G)(r|R)(o|O)(u|U)(p|P)\s+(b|B)(y|Y)' = 'GroupBy'
+'(o|O)(r|R)(d|D)(e|E)(r|R)\s+(b|B)(y|Y)' = 'OrderBy'
+'(i|I)(n|N)(t|T)(o|O)\s+(o|O)(u|U)(t|T)(f|F)(i|I)(l|L)(e|E)' = 'IntoOutfile'
+'(i|I)(n|N)(c|C)(l|L)(u|U)(d|D)(i|I)(n|N)(g|G)\s+(d|D)(a|A)(t|T)(a|A)' = 'IncludingData'
+'(l|L)(e|E)(f|F)(t|T)\s+((o|O)(u|U)(t|T)(e|E)(r|R)\s+)?(j|J)(o|O)(i|I)(n|N)' = 'LeftJoin'
+'(a|A)(s|S)(c|C)' = 'Asc'
+'(l|L)(i|I)(m|M)(i|I)(t|T)' = 'Limit'
+'(t|T)(o|O)(p|P)' = 'Top'
 '(s|S)(u|U)(m|M)' = 'Sum'
 '(a|A)(v|V)(g|G)' = 'Avg'
 '(m|M)(i|I)(n|N)' = 'Min'
 '(m|M)(a|A)(x|X)' = 'Max'
 '(c|C)(o|O)(u|U)(n|N)(t|T)' = 'Count'
+'(p|P)(e|E)(r|R)(c|C)(e|E)(n|N)(t|T)(i|I)(l|L)(e|E)_(d|D)(i|I)(s|S)(c|C)' = 'PercentileDisc'
+'(p|P)(e|E)(r|R)(c|C)(e|E)(n|N)(t|T)(i|I)(l|L)(e|E)_(c|C)(o|O)(n|N)(t|T)' = 'PercentileCont'
+'(w|W)(i|I)(t|T)(h|H)(i|I)(n|N)\s+(g|G)(r|R)(o|O)(u|U)(p|P)' = 'WithinGroup'
 '(n|N)(o|O)(t|T)\s+(n|N)(u|U)(l|L)(l|L)' = 'NotNull'
+'(n|N)(o|O)(t|T)\s+(i|I)(n|N)' = 'NotIn'
 '(p|P)(r|R)(i|I)(m|M)(a|A)(r|R)(y|Y)\s+(k|K)(e|E)(y|Y)' = 'PrimaryKey'
 '(f|F)(o|O)(r|R)(e|E)(i|I)(g|G)(n|N)\s+(k|K)(e|E)(y|Y)' = 'ForeignKey'
 '(u|U)(n|N)(i|I)(q|Q)(u|U)(e|E)' = 'Unique'
@@ -83,8 +116,16 @@ priority = [
 '(i|I)(n|N)(d|D)(e|E)(x|X)' = 'Index'
 '(c|C)(h|H)(e|E)(c|C)(k|K)' = 'Check'
 '(d|D)(e|E)(f|F)(a|A)(u|U)(l|L)(t|T)' = 'Default'
+'(d|D)(i|I)(s|S)(t|T)(i|I)(n|N)(c|C)(t|T)' = 'Distinct'
+'(c|C)(o|O)(m|M)(m|M)(e|E)(n|N)(t|T)' = 'Comment'
+'(w|W)(i|I)(t|T)(h|H)' = 'With'
+'(a|A)(s|S)' = 'As'
 '(i|I)(n|N)' = 'In'
+'(o|O)(n|N)\s+(c|C)(o|O)(n|N)(f|F)(l|L)(i|I)(c|C)(t|T)' = 'OnConflict'
+'(d|D)(o|O)\s+(n|N)(o|O)(t|T)(h|H)(i|I)(n|N)(g|G)' = 'DoNothing'
+'(d|D)(o|O)\s+(u|U)(p|P)(d|D)(a|A)(t|T)(e|E)\s+(s|S)(e|E)(t|T)' = 'DoUpdateSet'
 '(o|O)(n|N)' = 'On'
+'(t|T)(o|O)' = 'To'
 '(i|I)(s|S)' = 'Is'
 '(b|B)(i|I)(g|G)(i|I)(n|N)(t|T)' = 'Int' # handle bigint as int, decimal as float
 '(i|I)(n|N)(t|T)(e|E)(g|G)(e|E)(r|R)' = 'Int'
@@ -99,6 +140,8 @@ priority = [
 '(o|O)(r|R)' = 'Or'
 '(n|N)(u|U)(l|L)(l|L)' = 'Null'
 '(t|T)(r|R)(u|U)(e|E)' = 'True'
+'(c|C)(u|U)(r|R)(r|R)(e|E)(n|N)(t|T)_(d|D)(a|A)(t|T)(e|E)' = 'CurrentDate'
+'(c|C)(u|U)(r|R)(r|R)(e|E)(n|N)(t|T)_(d|D)(a|A)(t|T)(e|E)\s*(\+|-)\s*\d(_?\d)*' = 'CurrentDateOff'
 '(f|F)(a|A)(l|L)(s|S)(e|E)' = 'False'
 '<' = 'Lt'
 '<=' = 'Le'
@@ -106,6 +149,7 @@ priority = [
 '>' = 'Gt'
 '=' = 'Eq'
 '(<>)|(!=)' = 'Ne'
+'(l|L)(e|E)(n|N)(g|G)(t|T)(h|H)\s*\(' = 'LengthLPar'
 '\(' = 'LPar'
 '\)' = 'RPar'
 '\+' = 'Add'
@@ -118,10 +162,12 @@ priority = [
 ';' = 'Semicolon'
 '--[^\n]*' = '_Eps'
 '\s+' = '_Eps'
-'-?\d+\.\d*' = 'FloatLit'
-'-?\d+' = 'IntLit'
+'-?\d(_?\d)*\.(\d(_?\d)*)?((e|E)(\+|-)?\d+)?' = 'FloatLit'
+'-?\d(_?\d)*(e|E)(\+|-)?\d+' = 'FloatLit'
+'-?\d(_?\d)*' = 'IntLit'
 "'(('')|[^'])*'" = 'StrLit'
-'[A-Za-z]\w*' = 'Id1'
+"'(('')|[^'])*" = 'UnterminatedStr' # only matches when there is no closing ', otherwise `StrLit` is longer
+'[A-Za-z_]\w*' = 'Id1'
 '.' = '_Err'
 "##)]
 impl<'p> Parser<'p> {
@@ -145,6 +191,8 @@ impl<'p> Parser<'p> {
   fn stmt_use_db0(_: Token, db: &'p str) -> Stmt<'p> { Stmt::UseDb(db) }
   #[rule(Stmt -> Use DataBase Id)]
   fn stmt_use_db1(_: Token, _: Token, db: &'p str) -> Stmt<'p> { Stmt::UseDb(db) }
+  #[rule(Stmt -> AlterDataBase Id RenameTo Id)]
+  fn stmt_rename_db(_: Token, old: &'p str, _: Token, new: &'p str) -> Stmt<'p> { Stmt::RenameDb { old, new } }
   #[rule(Stmt -> Drop Table Id)]
   fn stmt_drop_table(_: Token, _: Token, table: &'p str) -> Stmt<'p> { Stmt::DropTable(table) }
   #[rule(Stmt -> Create Index Id On Id LPar Id RPar)]
@@ -153,20 +201,30 @@ impl<'p> Parser<'p> {
   fn stmt_drop_index(_: Token, _: Token, index: &'p str) -> Stmt<'p> { Stmt::DropIndex { index, table: None } }
   #[rule(Stmt -> Create Table Id LPar FieldList RPar)]
   fn stmt_create_table(_: Token, _: Token, table: &'p str, _: Token, (cols, cons): FieldList<'p>, _: Token) -> Stmt<'p> { CreateTable { table, cols, cons }.into() }
+  #[rule(Stmt -> Create Table Id Like Id)]
+  fn stmt_create_table_like(_: Token, _: Token, table: &'p str, _: Token, like: &'p str) -> Stmt<'p> { Stmt::CreateTableLike { table, like, data: false } }
+  #[rule(Stmt -> Create Table Id Like Id IncludingData)]
+  fn stmt_create_table_like_data(_: Token, _: Token, table: &'p str, _: Token, like: &'p str, _: Token) -> Stmt<'p> { Stmt::CreateTableLike { table, like, data: true } }
   #[rule(Stmt -> Show Tables)]
   fn stmt_show_tables(_: Token, _: Token) -> Stmt<'p> { Stmt::ShowTables }
   #[rule(Stmt -> Desc Id)]
   fn stmt_show_table0(_: Token, table: &'p str) -> Stmt<'p> { Stmt::ShowTable(table) }
   #[rule(Stmt -> Show Table Id)]
   fn stmt_show_table1(_: Token, _: Token, table: &'p str) -> Stmt<'p> { Stmt::ShowTable(table) }
-  #[rule(Stmt -> Select Mul From IdList WhereM)]
-  fn stmt_select0(_: Token, _: Token, _: Token, tables: Vec<&'p str>, where_: Vec<Cond<'p>>) -> Stmt<'p> { Select { ops: None, tables, where_ }.into() }
-  #[rule(Stmt -> Select AggList From IdList WhereM)]
-  fn stmt_select1(_: Token, ops: Vec<Agg<'p>>, _: Token, tables: Vec<&'p str>, where_: Vec<Cond<'p>>) -> Stmt<'p> { Select { ops: Some(ops), tables, where_ }.into() }
-  #[rule(Stmt -> InsertInto Id Values LitListList)]
-  fn stmt_insert0(_: Token, table: &'p str, _: Token, vals: Vec<Vec<CLit<'p>>>) -> Stmt<'p> { Insert { table, cols: None, vals }.into() }
-  #[rule(Stmt -> InsertInto Id LPar IdList RPar Values LitListList)]
-  fn stmt_insert1(_: Token, table: &'p str, _: Token, cols: Vec<&'p str>, _: Token, _: Token, vals: Vec<Vec<CLit<'p>>>) -> Stmt<'p> { Insert { table, cols: Some(cols), vals }.into() }
+  #[rule(Stmt -> Show Indexes From Id)]
+  fn stmt_show_indexes(_: Token, _: Token, _: Token, table: &'p str) -> Stmt<'p> { Stmt::ShowIndexes(table) }
+  #[rule(Stmt -> SelectStmt)]
+  fn stmt_select(s: Select<'p>) -> Stmt<'p> { s.into() }
+  #[rule(Stmt -> SelectStmt IntoOutfile StrLit)]
+  fn stmt_select_outfile(select: Select<'p>, _: Token, path: Token) -> Stmt<'p> { Stmt::SelectOutfile { select, path: path.str_trim() } }
+  #[rule(Stmt -> InsertInto Id Values ValueList OnConflictM)]
+  fn stmt_insert0(_: Token, table: &'p str, _: Token, vals: Vec<Vec<CLit<'p>>>, on_conflict: Option<OnConflict<'p>>) -> Stmt<'p> { Insert { table, cols: None, vals, on_conflict }.into() }
+  #[rule(Stmt -> InsertInto Id LPar IdList RPar Values ValueList OnConflictM)]
+  fn stmt_insert1(_: Token, table: &'p str, _: Token, cols: Vec<&'p str>, _: Token, _: Token, vals: Vec<Vec<CLit<'p>>>, on_conflict: Option<OnConflict<'p>>) -> Stmt<'p> { Insert { table, cols: Some(cols), vals, on_conflict }.into() }
+  #[rule(Stmt -> InsertInto Id SelectStmt)]
+  fn stmt_insert_select0(_: Token, table: &'p str, select: Select<'p>) -> Stmt<'p> { InsertSelect { table, cols: None, select }.into() }
+  #[rule(Stmt -> InsertInto Id LPar IdList RPar SelectStmt)]
+  fn stmt_insert_select1(_: Token, table: &'p str, _: Token, cols: Vec<&'p str>, _: Token, select: Select<'p>) -> Stmt<'p> { InsertSelect { table, cols: Some(cols), select }.into() }
   #[rule(Stmt -> Update Id Set SetList WhereM)]
   fn stmt_update(_: Token, table: &'p str, _: Token, sets: Vec<(&'p str, Expr<'p>)>, where_: Vec<Cond<'p>>) -> Stmt<'p> { Update { table, sets, where_ }.into() }
   #[rule(Stmt -> Delete From Id WhereM)]
@@ -178,6 +236,8 @@ impl<'p> Parser<'p> {
   fn alter_drop_index1(_: Token, table: &'p str, _: Token, _: Token, index: &'p str) -> Stmt<'p> { Stmt::DropIndex { index, table: Some(table) } }
   #[rule(Stmt -> AlterTable Id RenameTo Id)]
   fn alter_rename(_: Token, old: &'p str, _: Token, new: &'p str) -> Stmt<'p> { Stmt::Rename { old, new } }
+  #[rule(Stmt -> AlterTable Id RenameColumn Id To Id)]
+  fn alter_rename_col(_: Token, table: &'p str, _: Token, old: &'p str, _: Token, new: &'p str) -> Stmt<'p> { Stmt::RenameCol { table, old, new } }
   #[rule(Stmt -> AlterTable Id Add1 ForeignKey LPar Id RPar References Id LPar Id RPar)]
   fn alter_add_foreign(_: Token, table: &'p str, _: Token, _: Token, _: Token, col: &'p str, _: Token, _: Token, f_table: &'p str, _: Token, f_col: &'p str, _: Token) -> Stmt<'p> { AddForeign { table, col, f_table, f_col }.into() }
   #[rule(Stmt -> AlterTable Id Drop ForeignKey Id)]
@@ -191,21 +251,121 @@ impl<'p> Parser<'p> {
   #[rule(Stmt -> AlterTable Id Drop Id)]
   fn alter_drop_col(_: Token, table: &'p str, _: Token, col: &'p str) -> Stmt<'p> { Stmt::DropCol { table, col } }
 
+  #[rule(SelectStmt -> SelectTop Mul From FromList WhereM GroupByM OrderByM LimitM)]
+  fn select0(&mut self, top: Option<Token<'p>>, _: Token, _: Token, from: (Vec<&'p str>, Option<Vec<Cond<'p>>>), where_: Vec<Cond<'p>>, group_by: Vec<Expr<'p>>, order_by: Vec<OrderBy<'p>>, limit: Option<u32>) -> Select<'p> { let (tables, left_on) = from; Select { ops: None, tables, left_on, where_, group_by, order_by, limit: self.top_limit(top, limit), with: vec![] } }
+  #[rule(SelectStmt -> SelectTop AggList From FromList WhereM GroupByM OrderByM LimitM)]
+  fn select1(&mut self, top: Option<Token<'p>>, ops: Vec<Agg<'p>>, _: Token, from: (Vec<&'p str>, Option<Vec<Cond<'p>>>), where_: Vec<Cond<'p>>, group_by: Vec<Expr<'p>>, order_by: Vec<OrderBy<'p>>, limit: Option<u32>) -> Select<'p> { let (tables, left_on) = from; Select { ops: Some(ops), tables, left_on, where_, group_by, order_by, limit: self.top_limit(top, limit), with: vec![] } }
+  // the ctes of a nested `with` come after the outer ones, so that they can refer to them
+  #[rule(SelectStmt -> With CteList SelectStmt)]
+  fn select_with(_: Token, mut with: Vec<Cte<'p>>, mut s: Select<'p>) -> Select<'p> { (with.append(&mut s.with), s.with = with, s).2 }
+
+  #[rule(CteList -> Cte)]
+  fn cte_list0(c: Cte<'p>) -> Vec<Cte<'p>> { vec![c] }
+  #[rule(CteList -> CteList Comma Cte)]
+  fn cte_list1(mut cl: Vec<Cte<'p>>, _: Token, c: Cte<'p>) -> Vec<Cte<'p>> { (cl.push(c), cl).1 }
+
+  #[rule(Cte -> Id As LPar SelectStmt RPar)]
+  fn cte0(name: &'p str, _: Token, _: Token, select: Select<'p>, _: Token) -> Cte<'p> { Cte { name, cols: None, select } }
+  #[rule(Cte -> Id LPar IdList RPar As LPar SelectStmt RPar)]
+  fn cte1(name: &'p str, _: Token, cols: Vec<&'p str>, _: Token, _: Token, _: Token, select: Select<'p>, _: Token) -> Cte<'p> { Cte { name, cols: Some(cols), select } }
+
+  #[rule(FromList -> IdList)]
+  fn from_list0(tables: Vec<&'p str>) -> (Vec<&'p str>, Option<Vec<Cond<'p>>>) { (tables, None) }
+  #[rule(FromList -> IdList LeftJoin Id On CondList)]
+  fn from_list1(mut tables: Vec<&'p str>, _: Token, t: &'p str, _: Token, on: Vec<Cond<'p>>) -> (Vec<&'p str>, Option<Vec<Cond<'p>>>) { (tables.push(t), (tables, Some(on))).1 }
+
+  // `top n` is the same as `limit n`
+  #[rule(SelectTop -> Select)]
+  fn select_top0(_: Token) -> Option<Token<'p>> { None }
+  #[rule(SelectTop -> Select Top IntLit)]
+  fn select_top1(_: Token, _: Token, t: Token<'p>) -> Option<Token<'p>> { Some(t) }
+
   #[rule(WhereM -> Where CondList)]
   fn where_m1(_: Token, where_: Vec<Cond<'p>>) -> Vec<Cond<'p>> { where_ }
   #[rule(WhereM ->)]
   fn where_m0() -> Vec<Cond<'p>> { vec![] }
 
+  #[rule(GroupByM -> GroupBy GroupByList)]
+  fn group_by_m1(_: Token, group_by: Vec<Expr<'p>>) -> Vec<Expr<'p>> { group_by }
+  #[rule(GroupByM ->)]
+  fn group_by_m0() -> Vec<Expr<'p>> { vec![] }
+
+  #[rule(GroupByList -> GroupByItem)]
+  fn group_by_list0(g: Expr<'p>) -> Vec<Expr<'p>> { vec![g] }
+  #[rule(GroupByList -> GroupByList Comma GroupByItem)]
+  fn group_by_list1(mut gl: Vec<Expr<'p>>, _: Token, g: Expr<'p>) -> Vec<Expr<'p>> { (gl.push(g), gl).1 }
+
+  #[rule(GroupByItem -> ColRef)]
+  fn group_by_item0(c: ColRef<'p>) -> Expr<'p> { Expr::Atom(Atom::ColRef(c)) }
+  #[rule(GroupByItem -> BinExpr)]
+  fn group_by_item1(e: Expr<'p>) -> Expr<'p> { e }
+  #[rule(GroupByItem -> Call)]
+  fn group_by_item2(e: Expr<'p>) -> Expr<'p> { e }
+
+  #[rule(OrderByM -> OrderBy OrderByList)]
+  fn order_by_m1(_: Token, order_by: Vec<OrderBy<'p>>) -> Vec<OrderBy<'p>> { order_by }
+  #[rule(OrderByM ->)]
+  fn order_by_m0() -> Vec<OrderBy<'p>> { vec![] }
+
+  #[rule(OrderByList -> OrderByItem)]
+  fn order_by_list0(o: OrderBy<'p>) -> Vec<OrderBy<'p>> { vec![o] }
+  #[rule(OrderByList -> OrderByList Comma OrderByItem)]
+  fn order_by_list1(mut ol: Vec<OrderBy<'p>>, _: Token, o: OrderBy<'p>) -> Vec<OrderBy<'p>> { (ol.push(o), ol).1 }
+
+  #[rule(OrderByItem -> OrderKey)]
+  fn order_by_item0(key: OrderKey<'p>) -> OrderBy<'p> { OrderBy { key, desc: false } }
+  #[rule(OrderByItem -> OrderKey Asc)]
+  fn order_by_item1(key: OrderKey<'p>, _: Token) -> OrderBy<'p> { OrderBy { key, desc: false } }
+  #[rule(OrderByItem -> OrderKey Desc)]
+  fn order_by_item2(key: OrderKey<'p>, _: Token) -> OrderBy<'p> { OrderBy { key, desc: true } }
+
+  #[rule(OrderKey -> Agg)]
+  fn order_key0(agg: Agg<'p>) -> OrderKey<'p> { OrderKey::Agg(agg) }
+  #[rule(OrderKey -> IntLit)]
+  fn order_key1(&mut self, t: Token) -> OrderKey<'p> { t.parse(OrderKey::Pos, |line, col, s| self.pe.push(PE { line, col, kind: InvalidInt(s) })) }
+  #[rule(OrderKey -> BinExpr)]
+  fn order_key2(e: Expr<'p>) -> OrderKey<'p> { OrderKey::Expr(e) }
+  #[rule(OrderKey -> Call)]
+  fn order_key3(e: Expr<'p>) -> OrderKey<'p> { OrderKey::Expr(e) }
+
+  // an expression in order by, group by or the select list, where a single col or lit is handled by other rules
+  // so it must have an arithmetic operator at the top level
+  #[rule(BinExpr -> Expr Add Expr)]
+  fn bin_expr_add(l: Expr<'p>, _: Token, r: Expr<'p>) -> Expr<'p> { Expr::Bin(Add, box (l, r)) }
+  #[rule(BinExpr -> Expr Sub Expr)]
+  fn bin_expr_sub(l: Expr<'p>, _: Token, r: Expr<'p>) -> Expr<'p> { Expr::Bin(Sub, box (l, r)) }
+  #[rule(BinExpr -> Expr Mul Expr)]
+  fn bin_expr_mul(l: Expr<'p>, _: Token, r: Expr<'p>) -> Expr<'p> { Expr::Bin(Mul, box (l, r)) }
+  #[rule(BinExpr -> Expr Div Expr)]
+  fn bin_expr_div(l: Expr<'p>, _: Token, r: Expr<'p>) -> Expr<'p> { Expr::Bin(Div, box (l, r)) }
+  #[rule(BinExpr -> Expr Mod Expr)]
+  fn bin_expr_mod(l: Expr<'p>, _: Token, r: Expr<'p>) -> Expr<'p> { Expr::Bin(Mod, box (l, r)) }
+
+  #[rule(LimitM -> Limit IntLit)]
+  fn limit_m1(&mut self, _: Token, t: Token) -> Option<u32> { t.parse(Some, |line, col, s| self.pe.push(PE { line, col, kind: InvalidInt(s) })) }
+  #[rule(LimitM ->)]
+  fn limit_m0() -> Option<u32> { None }
+
   #[rule(IdList -> Id)]
   fn id_list0(i: &'p str) -> Vec<&'p str> { vec![i] }
   #[rule(IdList -> IdList Comma Id)]
   fn id_list1(mut il: Vec<&'p str>, _: Token, i: &'p str) -> Vec<&'p str> { (il.push(i), il).1 }
 
-  #[rule(AggList -> Agg)]
+  #[rule(AggList -> SelItem)]
   fn agg_list0(a: Agg<'p>) -> Vec<Agg<'p>> { vec![a] }
-  #[rule(AggList -> AggList Comma Agg)]
+  #[rule(AggList -> AggList Comma SelItem)]
   fn agg_list1(mut al: Vec<Agg<'p>>, _: Token, a: Agg<'p>) -> Vec<Agg<'p>> { (al.push(a), al).1 }
 
+  // a constant is only allowed in the select list, in order by an int is the position of a col
+  #[rule(SelItem -> Agg)]
+  fn sel_item_agg(a: Agg<'p>) -> Agg<'p> { a }
+  #[rule(SelItem -> Lit)]
+  fn sel_item_lit(l: CLit<'p>) -> Agg<'p> { Agg { col: ColRef { table: None, col: "" }, op: None, filter: vec![], distinct: false, lit: Some(l), expr: None } }
+  #[rule(SelItem -> BinExpr)]
+  fn sel_item_expr(e: Expr<'p>) -> Agg<'p> { Agg { col: ColRef { table: None, col: "" }, op: None, filter: vec![], distinct: false, lit: None, expr: Some(e) } }
+  #[rule(SelItem -> Call)]
+  fn sel_item_call(e: Expr<'p>) -> Agg<'p> { Agg { col: ColRef { table: None, col: "" }, op: None, filter: vec![], distinct: false, lit: None, expr: Some(e) } }
+
   #[rule(LitList -> Lit)]
   fn lit_list0(l: CLit<'p>) -> Vec<CLit<'p>> { vec![l] }
   #[rule(LitList -> LitList Comma Lit)]
@@ -216,6 +376,12 @@ impl<'p> Parser<'p> {
   #[rule(LitListList -> LitListList Comma LPar LitList RPar)]
   fn lit_list_list1(mut ll: Vec<Vec<CLit<'p>>>, _: Token, _: Token, l: Vec<CLit<'p>>, _: Token) -> Vec<Vec<CLit<'p>>> { (ll.push(l), ll).1 }
 
+  // the value rows of insert, a trailing comma is allowed
+  #[rule(ValueList -> LitListList)]
+  fn value_list0(ll: Vec<Vec<CLit<'p>>>) -> Vec<Vec<CLit<'p>>> { ll }
+  #[rule(ValueList -> LitListList Comma)]
+  fn value_list1(ll: Vec<Vec<CLit<'p>>>, _: Token) -> Vec<Vec<CLit<'p>>> { ll }
+
   #[rule(Expr -> Atom)]
   fn expr_atom(a: Atom<'p>) -> Expr<'p> { Expr::Atom(a) }
   #[rule(Expr -> Sub Expr)]
@@ -233,6 +399,18 @@ impl<'p> Parser<'p> {
   fn expr_mod(l: Expr<'p>, _: Token, r: Expr<'p>) -> Expr<'p> { Expr::Bin(Mod, box (l, r)) }
   #[rule(Expr -> LPar Expr RPar)]
   fn expr_par(_: Token, e: Expr<'p>, _: Token) -> Expr<'p> { e }
+  #[rule(Expr -> Call)]
+  fn expr_call(e: Expr<'p>) -> Expr<'p> { e }
+
+  // a call of user-defined function, it is an expression by itself, so it is also a select item, order key or group by item
+  #[rule(Call -> Id LPar RPar)]
+  fn call0(name: &'p str, _: Token, _: Token) -> Expr<'p> { Expr::Call(name, vec![]) }
+  #[rule(Call -> Id LPar ExprList RPar)]
+  fn call1(name: &'p str, _: Token, args: Vec<Expr<'p>>, _: Token) -> Expr<'p> { Expr::Call(name, args) }
+  #[rule(ExprList -> Expr)]
+  fn expr_list0(e: Expr<'p>) -> Vec<Expr<'p>> { vec![e] }
+  #[rule(ExprList -> ExprList Comma Expr)]
+  fn expr_list1(mut el: Vec<Expr<'p>>, _: Token, e: Expr<'p>) -> Vec<Expr<'p>> { (el.push(e), el).1 }
   #[rule(Expr -> Expr Lt Expr)]
   fn expr_lt(l: Expr<'p>, _: Token, r: Expr<'p>) -> Expr<'p> { Expr::Cmp(Lt, box (l, r)) }
   #[rule(Expr -> Expr Le Expr)]
@@ -256,6 +434,13 @@ impl<'p> Parser<'p> {
   #[rule(Expr -> Expr Like StrLit)]
   fn expr_like(&self, e: Expr<'p>, _: Token, s: Token) -> Expr<'p> { Expr::Like(box e, self.escape(s.str_trim())) }
 
+  #[rule(OnConflictM -> OnConflict LPar Id RPar DoNothing)]
+  fn on_conflict_m0(_: Token, _: Token, col: &'p str, _: Token, _: Token) -> Option<OnConflict<'p>> { Some(OnConflict { col, sets: None }) }
+  #[rule(OnConflictM -> OnConflict LPar Id RPar DoUpdateSet SetList)]
+  fn on_conflict_m1(_: Token, _: Token, col: &'p str, _: Token, _: Token, sets: Vec<(&'p str, Expr<'p>)>) -> Option<OnConflict<'p>> { Some(OnConflict { col, sets: Some(sets) }) }
+  #[rule(OnConflictM ->)]
+  fn on_conflict_m2() -> Option<OnConflict<'p>> { None }
+
   #[rule(SetList -> Id Eq Expr)]
   fn set_list0(col: &'p str, _: Token, l: Expr<'p>) -> Vec<(&'p str, Expr<'p>)> { vec![(col, l)] }
   #[rule(SetList -> SetList Comma Id Eq Expr)]
@@ -271,38 +456,88 @@ impl<'p> Parser<'p> {
   fn field_list3(mut fl: FieldList<'p>, _: Token, c: ColCons<'p>) -> FieldList<'p> { (fl.1.push(c), fl).1 }
 
   #[rule(ColDecl -> Id ColTy)]
-  fn field0(col: &'p str, ty: ColTy) -> ColDecl<'p> { ColDecl { col, ty, notnull: false, dft: None } }
+  fn field0(col: &'p str, ty: ColTy) -> ColDecl<'p> { ColDecl { col, ty, notnull: false, dft: None, comment: None } }
   #[rule(ColDecl -> Id ColTy NotNull)]
-  fn field1(col: &'p str, ty: ColTy, _: Token) -> ColDecl<'p> { ColDecl { col, ty, notnull: true, dft: None } }
-  #[rule(ColDecl -> Id ColTy Default Lit)]
-  fn field2(col: &'p str, ty: ColTy, _: Token, dft: CLit<'p>) -> ColDecl<'p> { ColDecl { col, ty, notnull: false, dft: Some(dft) } }
-  #[rule(ColDecl -> Id ColTy NotNull Default Lit)]
-  fn field3(col: &'p str, ty: ColTy, _: Token, _: Token, dft: CLit<'p>) -> ColDecl<'p> { ColDecl { col, ty, notnull: true, dft: Some(dft) } }
+  fn field1(col: &'p str, ty: ColTy, _: Token) -> ColDecl<'p> { ColDecl { col, ty, notnull: true, dft: None, comment: None } }
+  #[rule(ColDecl -> Id ColTy Default Dft)]
+  fn field2(col: &'p str, ty: ColTy, _: Token, dft: Dft<'p>) -> ColDecl<'p> { ColDecl { col, ty, notnull: false, dft: Some(dft), comment: None } }
+  #[rule(ColDecl -> Id ColTy NotNull Default Dft)]
+  fn field3(col: &'p str, ty: ColTy, _: Token, _: Token, dft: Dft<'p>) -> ColDecl<'p> { ColDecl { col, ty, notnull: true, dft: Some(dft), comment: None } }
+  // as a default value, `current_date` should be evaluated on insert, not now
+  #[rule(Dft -> ConstLit)]
+  fn dft_lit(lit: CLit<'p>) -> Dft<'p> { Dft::Lit(lit) }
+  #[rule(Dft -> CurrentDate)]
+  fn dft_current_date(_: Token) -> Dft<'p> { Dft::CurrentDate }
+  #[rule(ColDecl -> ColDecl Comment StrLit)]
+  fn field_comment(c: ColDecl<'p>, _: Token, s: Token) -> ColDecl<'p> { ColDecl { comment: Some(s.str_trim()), ..c } }
   #[rule(ColCons -> ForeignKey LPar Id RPar References Id LPar Id RPar)]
   fn field5(_: Token, _: Token, col: &'p str, _: Token, _: Token, f_table: &'p str, _: Token, f_col: &'p str, _: Token) -> ColCons<'p> { ColCons::Foreign { col, f_table, f_col } }
   #[rule(ColCons -> PrimaryKey LPar IdList RPar)]
   fn field6(_: Token, _: Token, il: Vec<&'p str>, _: Token) -> ColCons<'p> { ColCons::Primary(il) }
   #[rule(ColCons -> Unique LPar Id RPar)]
   fn field7(_: Token, _: Token, col: &'p str, _: Token) -> ColCons<'p> { ColCons::Unique(col) }
-  #[rule(ColCons -> Check LPar Id In LPar LitList RPar RPar)]
-  fn field8(_: Token, _: Token, col: &'p str, _: Token, _: Token, ll: Vec<CLit<'p>>, _: Token, _: Token) -> ColCons<'p> { ColCons::Check(col, ll) }
+  // `check (col in (...))` is still stored as the old per-col check, any other cond list becomes a check over the whole row
+  // an empty per-col check list means no check, so `check (col in ())` is also a check over the whole row
+  #[rule(ColCons -> Check LPar CondList RPar)]
+  fn field8(_: Token, l: Token, mut cl: Vec<Cond<'p>>, r: Token) -> ColCons<'p> {
+    if cl.len() == 1 {
+      match cl[0] {
+        Cond::InList(ColRef { table: None, col }, ref ll) if !ll.is_empty() => if let Some(Cond::InList(_, ll)) = cl.pop() { return ColCons::Check(col, ll); }
+        _ => {}
+      }
+    }
+    // `l` and `r` are pieces of the same input string (the lexer never copies), so the bytes between them are a slice of it
+    let text = unsafe { str::from_utf8_unchecked(std::slice::from_raw_parts(l.piece.as_ptr().add(1), r.piece.as_ptr() as usize - l.piece.as_ptr() as usize - 1)) };
+    ColCons::RowCheck(text.trim(), cl)
+  }
 
   #[rule(Agg -> ColRef)]
-  fn agg0(col: ColRef<'p>) -> Agg<'p> { Agg { col, op: None } }
+  fn agg0(col: ColRef<'p>) -> Agg<'p> { Agg { col, op: None, filter: vec![], distinct: false, lit: None, expr: None } }
+  // `t.*` selects all cols of `t`, like `count(t.*)`, "*" is used as the col name
+  #[rule(Agg -> Id Dot Mul)]
+  fn agg_tbl_all(table: &'p str, _: Token, _: Token) -> Agg<'p> { Agg { col: ColRef { table: Some(table), col: "*" }, op: None, filter: vec![], distinct: false, lit: None, expr: None } }
   #[rule(Agg -> Avg LPar ColRef RPar)]
-  fn agg_avg(_: Token, _: Token, col: ColRef<'p>, _: Token) -> Agg<'p> { Agg { col, op: Some(Avg) } }
+  fn agg_avg(_: Token, _: Token, col: ColRef<'p>, _: Token) -> Agg<'p> { Agg { col, op: Some(Avg), filter: vec![], distinct: false, lit: None, expr: None } }
   #[rule(Agg -> Sum LPar ColRef RPar)]
-  fn agg_sum(_: Token, _: Token, col: ColRef<'p>, _: Token) -> Agg<'p> { Agg { col, op: Some(Sum) } }
+  fn agg_sum(_: Token, _: Token, col: ColRef<'p>, _: Token) -> Agg<'p> { Agg { col, op: Some(Sum), filter: vec![], distinct: false, lit: None, expr: None } }
   #[rule(Agg -> Min LPar ColRef RPar)]
-  fn agg_min(_: Token, _: Token, col: ColRef<'p>, _: Token) -> Agg<'p> { Agg { col, op: Some(Min) } }
+  fn agg_min(_: Token, _: Token, col: ColRef<'p>, _: Token) -> Agg<'p> { Agg { col, op: Some(Min), filter: vec![], distinct: false, lit: None, expr: None } }
   #[rule(Agg -> Max LPar ColRef RPar)]
-  fn agg_max(_: Token, _: Token, col: ColRef<'p>, _: Token) -> Agg<'p> { Agg { col, op: Some(Max) } }
+  fn agg_max(_: Token, _: Token, col: ColRef<'p>, _: Token) -> Agg<'p> { Agg { col, op: Some(Max), filter: vec![], distinct: false, lit: None, expr: None } }
   #[rule(Agg -> Count LPar ColRef RPar)]
-  fn agg_count(_: Token, _: Token, col: ColRef<'p>, _: Token) -> Agg<'p> { Agg { col, op: Some(Count) } }
+  fn agg_count(_: Token, _: Token, col: ColRef<'p>, _: Token) -> Agg<'p> { Agg { col, op: Some(Count), filter: vec![], distinct: false, lit: None, expr: None } }
   // for CountAll, `col` is not accessible (for compatibility, `col` is not defined as Option<ColRef>)
   // "*" is just for the convenience of printing
+  // `distinct` only counts/sums each distinct non-null value once
+  #[rule(Agg -> Avg LPar Distinct ColRef RPar)]
+  fn agg_avg_distinct(_: Token, _: Token, _: Token, col: ColRef<'p>, _: Token) -> Agg<'p> { Agg { col, op: Some(Avg), filter: vec![], distinct: true, lit: None, expr: None } }
+  #[rule(Agg -> Sum LPar Distinct ColRef RPar)]
+  fn agg_sum_distinct(_: Token, _: Token, _: Token, col: ColRef<'p>, _: Token) -> Agg<'p> { Agg { col, op: Some(Sum), filter: vec![], distinct: true, lit: None, expr: None } }
+  #[rule(Agg -> Count LPar Distinct ColRef RPar)]
+  fn agg_count_distinct(_: Token, _: Token, _: Token, col: ColRef<'p>, _: Token) -> Agg<'p> { Agg { col, op: Some(Count), filter: vec![], distinct: true, lit: None, expr: None } }
   #[rule(Agg -> Count LPar Mul RPar)]
-  fn agg_count_all(_: Token, _: Token, _: Token, _: Token) -> Agg<'p> { Agg { col: ColRef { table: None, col: "*" }, op: Some(CountAll) } }
+  fn agg_count_all(_: Token, _: Token, _: Token, _: Token) -> Agg<'p> { Agg { col: ColRef { table: None, col: "*" }, op: Some(CountAll), filter: vec![], distinct: false, lit: None, expr: None } }
+  // `count(t.*)` counts the rows that `t` is not null in, which only differs from `count(*)` for the right table of left join
+  #[rule(Agg -> Count LPar Id Dot Mul RPar)]
+  fn agg_count_tbl(_: Token, _: Token, table: &'p str, _: Token, _: Token, _: Token) -> Agg<'p> { Agg { col: ColRef { table: Some(table), col: "*" }, op: Some(CountAll), filter: vec![], distinct: false, lit: None, expr: None } }
+
+  // percentile_disc(p) within group (order by col): the first value whose cumulative distribution reaches p
+  // percentile_cont(p) interpolates the value at rank p * (n - 1) of the sorted values
+  #[rule(Agg -> PercentileDisc LPar Percentile RPar WithinGroup LPar OrderBy ColRef RPar)]
+  fn agg_percentile_disc(_: Token, _: Token, p: u64, _: Token, _: Token, _: Token, _: Token, col: ColRef<'p>, _: Token) -> Agg<'p> { Agg { col, op: Some(PercentileDisc(p)), filter: vec![], distinct: false, lit: None, expr: None } }
+  #[rule(Agg -> PercentileCont LPar Percentile RPar WithinGroup LPar OrderBy ColRef RPar)]
+  fn agg_percentile_cont(_: Token, _: Token, p: u64, _: Token, _: Token, _: Token, _: Token, col: ColRef<'p>, _: Token) -> Agg<'p> { Agg { col, op: Some(PercentileCont(p)), filter: vec![], distinct: false, lit: None, expr: None } }
+  #[rule(Percentile -> FloatLit)]
+  fn percentile0(&mut self, t: Token<'p>) -> u64 { self.percentile(t) }
+  #[rule(Percentile -> IntLit)]
+  fn percentile1(&mut self, t: Token<'p>) -> u64 { self.percentile(t) }
+  #[rule(Agg -> Agg Filter LPar Where CondList RPar)]
+  fn agg_filter(mut a: Agg<'p>, _: Token, _: Token, _: Token, mut where_: Vec<Cond<'p>>, _: Token) -> Agg<'p> { (a.filter.append(&mut where_), a).1 }
+
+  #[rule(ColRefList -> ColRef)]
+  fn col_ref_list0(c: ColRef<'p>) -> Vec<ColRef<'p>> { vec![c] }
+  #[rule(ColRefList -> ColRefList Comma ColRef)]
+  fn col_ref_list1(mut cl: Vec<ColRef<'p>>, _: Token, c: ColRef<'p>) -> Vec<ColRef<'p>> { (cl.push(c), cl).1 }
 
   #[rule(ColRef -> Id)]
   fn col_ref0(col: &'p str) -> ColRef<'p> { ColRef { table: None, col } }
@@ -332,24 +567,108 @@ impl<'p> Parser<'p> {
   fn cond_is_not_null(c: ColRef<'p>, _: Token, _: Token) -> Cond<'p> { Cond::Null(c, false) }
   #[rule(Cond -> ColRef Like StrLit)]
   fn cond_like(c: ColRef<'p>, _: Token, s: Token) -> Cond<'p> { Cond::Like(c, s.str_trim()) }
+  #[rule(Cond -> ColRef Like ColRef)]
+  fn cond_like_col(c: ColRef<'p>, _: Token, like: ColRef<'p>) -> Cond<'p> { Cond::LikeCol(c, like) }
+  #[rule(Cond -> ColRef In LPar SelectStmt RPar)]
+  fn cond_in(l: ColRef<'p>, _: Token, _: Token, s: Select<'p>, _: Token) -> Cond<'p> { Cond::In(l, box s) }
+  #[rule(Cond -> ColRef Lt LPar SelectStmt RPar)]
+  fn cond_lt_select(l: ColRef<'p>, _: Token, _: Token, s: Select<'p>, _: Token) -> Cond<'p> { Cond::CmpSelect(Lt, l, box s) }
+  #[rule(Cond -> ColRef Le LPar SelectStmt RPar)]
+  fn cond_le_select(l: ColRef<'p>, _: Token, _: Token, s: Select<'p>, _: Token) -> Cond<'p> { Cond::CmpSelect(Le, l, box s) }
+  #[rule(Cond -> ColRef Ge LPar SelectStmt RPar)]
+  fn cond_ge_select(l: ColRef<'p>, _: Token, _: Token, s: Select<'p>, _: Token) -> Cond<'p> { Cond::CmpSelect(Ge, l, box s) }
+  #[rule(Cond -> ColRef Gt LPar SelectStmt RPar)]
+  fn cond_gt_select(l: ColRef<'p>, _: Token, _: Token, s: Select<'p>, _: Token) -> Cond<'p> { Cond::CmpSelect(Gt, l, box s) }
+  #[rule(Cond -> ColRef Eq LPar SelectStmt RPar)]
+  fn cond_eq_select(l: ColRef<'p>, _: Token, _: Token, s: Select<'p>, _: Token) -> Cond<'p> { Cond::CmpSelect(Eq, l, box s) }
+  #[rule(Cond -> ColRef Ne LPar SelectStmt RPar)]
+  fn cond_ne_select(l: ColRef<'p>, _: Token, _: Token, s: Select<'p>, _: Token) -> Cond<'p> { Cond::CmpSelect(Ne, l, box s) }
+  #[rule(Cond -> ColRef In LPar LitList RPar)]
+  fn cond_in_list(l: ColRef<'p>, _: Token, _: Token, vals: Vec<CLit<'p>>, _: Token) -> Cond<'p> { Cond::InList(l, vals) }
+  #[rule(Cond -> ColRef In LPar RPar)]
+  fn cond_in_empty(l: ColRef<'p>, _: Token, _: Token, _: Token) -> Cond<'p> { Cond::InList(l, vec![]) }
+  #[rule(Cond -> ColRef NotIn LPar LitList RPar)]
+  fn cond_not_in_list(l: ColRef<'p>, _: Token, _: Token, vals: Vec<CLit<'p>>, _: Token) -> Cond<'p> { Cond::NotInList(l, vals) }
+  #[rule(Cond -> ColRef NotIn LPar RPar)]
+  fn cond_not_in_empty(l: ColRef<'p>, _: Token, _: Token, _: Token) -> Cond<'p> { Cond::NotInList(l, vec![]) }
+  // only to report a clear error instead of a syntax error, the returned cond is never used
+  #[rule(Cond -> AggFn LPar AggArg RPar CmpOp Atom)]
+  fn cond_agg(&mut self, f: Token, _: Token, c: ColRef<'p>, _: Token, _: CmpOp, _: Atom<'p>) -> Cond<'p> {
+    self.pe.push(PE { line: f.line, col: f.col, kind: AggInWhere });
+    Cond::Null(c, true)
+  }
+  #[rule(AggFn -> Avg)]
+  fn agg_fn_avg(t: Token) -> Token { t }
+  #[rule(AggFn -> Sum)]
+  fn agg_fn_sum(t: Token) -> Token { t }
+  #[rule(AggFn -> Min)]
+  fn agg_fn_min(t: Token) -> Token { t }
+  #[rule(AggFn -> Max)]
+  fn agg_fn_max(t: Token) -> Token { t }
+  #[rule(AggFn -> Count)]
+  fn agg_fn_count(t: Token) -> Token { t }
+  #[rule(AggArg -> ColRef)]
+  fn agg_arg_col(c: ColRef<'p>) -> ColRef<'p> { c }
+  #[rule(AggArg -> Mul)]
+  fn agg_arg_all(_: Token) -> ColRef<'p> { ColRef { table: None, col: "*" } }
+  #[rule(Cond -> ColRef Mod IntLit CmpOp Lit)]
+  fn cond_mod(&mut self, c: ColRef<'p>, _: Token, m: Token, op: CmpOp, r: CLit<'p>) -> Cond<'p> { m.parse(|m| Cond::Mod(c, m, op, r), |line, col, s| self.pe.push(PE { line, col, kind: InvalidInt(s) })) }
+  // `length` with `(` is one token, so `length` is still a valid identifier
+  #[rule(Cond -> LengthLPar ColRef RPar CmpOp IntLit)]
+  fn cond_len(&mut self, _: Token, c: ColRef<'p>, _: Token, op: CmpOp, r: Token) -> Cond<'p> { r.parse(|r| Cond::Len(c, op, r), |line, col, s| self.pe.push(PE { line, col, kind: InvalidInt(s) })) }
+
+  #[rule(CmpOp -> Lt)]
+  fn cmp_op_lt(_: Token) -> CmpOp { Lt }
+  #[rule(CmpOp -> Le)]
+  fn cmp_op_le(_: Token) -> CmpOp { Le }
+  #[rule(CmpOp -> Ge)]
+  fn cmp_op_ge(_: Token) -> CmpOp { Ge }
+  #[rule(CmpOp -> Gt)]
+  fn cmp_op_gt(_: Token) -> CmpOp { Gt }
+  #[rule(CmpOp -> Eq)]
+  fn cmp_op_eq(_: Token) -> CmpOp { Eq }
+  #[rule(CmpOp -> Ne)]
+  fn cmp_op_ne(_: Token) -> CmpOp { Ne }
 
   #[rule(Atom -> ColRef)]
   fn atom_col_ref(c: ColRef<'p>) -> Atom<'p> { Atom::ColRef(c) }
   #[rule(Atom -> Lit)]
   fn atom_lit(l: CLit<'p>) -> Atom<'p> { Atom::Lit(l) }
 
-  #[rule(Lit -> Null)]
+  // ConstLit is all literals except `current_date`
+  #[rule(Lit -> ConstLit)]
+  fn lit_const(lit: CLit<'p>) -> CLit<'p> { lit }
+  #[rule(ConstLit -> Null)]
   fn lit_null(_: Token) -> CLit<'p> { CLit::new(Lit::Null) }
-  #[rule(Lit -> True)]
+  #[rule(ConstLit -> True)]
   fn lit_true(_: Token) -> CLit<'p> { CLit::new(Lit::Bool(true)) }
-  #[rule(Lit -> False)]
+  #[rule(ConstLit -> False)]
   fn lit_false(_: Token) -> CLit<'p> { CLit::new(Lit::Bool(false)) }
-  #[rule(Lit -> IntLit)]
-  fn lit_int(&mut self, t: Token) -> CLit<'p> { t.parse(|x: i32| CLit::new(Lit::Number(x as f64)), |line, col, s| self.pe.push(PE { line, col, kind: InvalidInt(s) })) }
-  #[rule(Lit -> FloatLit)]
+  #[rule(ConstLit -> IntLit)]
+  fn lit_int(&mut self, t: Token) -> CLit<'p> { t.parse(|x: i32| CLit::new(Lit::Number(x as f64)), |line, col, s| self.pe.push(PE { line, col, kind: IntLiteralOverflow(s) })) }
+  #[rule(ConstLit -> FloatLit)]
   fn lit_float(&mut self, t: Token) -> CLit<'p> { t.parse(|x: f32| CLit::new(Lit::Number(x as f64)), |line, col, s| self.pe.push(PE { line, col, kind: InvalidFloat(s) })) }
-  #[rule(Lit -> StrLit)]
+  #[rule(ConstLit -> StrLit)]
   fn lit_str(t: Token) -> CLit<'p> { CLit::new(Lit::Str(t.str_trim())) }
+  // `date '2020-01-01'` is a date, unlike the string '2020-01-01', it can't be compared with a char col
+  #[rule(ConstLit -> Date StrLit)]
+  fn lit_date(&mut self, _: Token, t: Token) -> CLit<'p> {
+    let s = t.str_trim();
+    CLit::new(parse_date(s).map(Lit::Date).unwrap_or_else(|_| (self.pe.push(PE { line: t.line, col: t.col, kind: InvalidDate(s) }), Lit::Null).1))
+  }
+  #[rule(Lit -> CurrentDate)]
+  fn lit_current_date(_: Token) -> CLit<'p> { CLit::new(Lit::Date(today())) }
+  // `current_date - n` / `current_date + n` is n days before / after today, it is one token so that it doesn't conflict with `Expr - Expr`
+  #[rule(Lit -> CurrentDateOff)]
+  fn lit_current_date_off(&mut self, t: Token) -> CLit<'p> {
+    let s = t.str();
+    let off = s["current_date".len()..].trim_start();
+    let (sub, n) = (off.starts_with('-'), off[1..].trim_start().replace('_', ""));
+    match n.parse::<i32>().ok().and_then(|n| today_offset(if sub { -n } else { n })) {
+      Some(d) => CLit::new(Lit::Date(d)),
+      None => (self.pe.push(PE { line: t.line, col: t.col, kind: InvalidDate(s) }), CLit::new(Lit::Null)).1,
+    }
+  }
 
   #[rule(BareTy -> Bool)]
   fn bare_ty_bool(_: Token) -> BareTy { Bool }
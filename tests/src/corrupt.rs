@@ -0,0 +1,92 @@
+use typed_arena::Arena;
+use std::fs;
+
+use driver::Eval;
+use db::Db;
+use common::{*, Error::*};
+
+#[test]
+fn corrupt() {
+  let mut e = Eval::default();
+  e.exec_all("create database corrupt; use corrupt; create table t (c char(5), v varchar(5)); insert into t values ('abc', 'def');",
+             &Arena::default(), |_| {}, |_| {}).unwrap();
+  e.exec_all("select * from t;", &Arena::default(), |_| {}, |_| {}).unwrap();
+  unsafe {
+    let db = e.db().unwrap();
+    let tp = db.get_tp("t").unwrap().1;
+    let (data, _) = db.record_iter(tp).next().unwrap();
+    *data.add(tp.get_ci("c").unwrap().off as usize + 1) = 0xFF; // the first byte of "abc"
+  }
+  match e.exec_all("select * from t;", &Arena::default(), |_| {}, |_| {}) {
+    Err(ModifyError(_, CorruptString { .. })) => {}
+    r => panic!("expect CorruptString, found {:?}", r),
+  }
+  match e.exec_all("delete from t where c = 'abc';", &Arena::default(), |_| {}, |_| {}) {
+    Err(ModifyError(0, CorruptString { .. })) => {}
+    r => panic!("expect CorruptString, found {:?}", r),
+  }
+  e.exec_all("drop database corrupt;", &Arena::default(), |_| {}, |_| {}).unwrap();
+}
+
+#[test]
+fn db_version() {
+  let mut e = Eval::default();
+  e.exec_all("create database version; use version; create table t (id int); insert into t values (1);", &Arena::default(), |_| {}, |_| {}).unwrap();
+  let tp_id = unsafe { e.db().unwrap().get_tp("t").unwrap().0 };
+  e = Eval::default(); // close the db
+  let version_off = unsafe { offsets().0 };
+  let mut file = fs::read("version").unwrap();
+  file[version_off] = DB_VERSION as u8 + 1;
+  fs::write("version", &file).unwrap();
+  match Db::open("version") {
+    Err(UnsupportedDbVersion { found, expected: DB_VERSION }) if found == DB_VERSION + 1 => {}
+    r => panic!("expect UnsupportedDbVersion, found {:?}", r.map(|_| ())),
+  }
+  match e.exec_all("use version;", &Arena::default(), |_| {}, |_| {}) {
+    Err(ModifyError(_, UnsupportedDbVersion { .. })) => {}
+    r => panic!("expect UnsupportedDbVersion, found {:?}", r),
+  }
+  // a file created before the version was stored has 0 there
+  to_baseline("version", tp_id, 1);
+  e.exec_all("use version; insert into t values (2);", &Arena::default(), |_| {}, |_| {}).unwrap();
+  assert_eq!(e.db().unwrap().columns("t").unwrap().len(), 1);
+  e.exec_all("drop database version;", &Arena::default(), |_| {}, |_| {}).unwrap();
+}
+
+#[test]
+fn old_layout() {
+  let tp_id = {
+    let mut e = Eval::default();
+    e.exec_all("create database old_layout; use old_layout; create table t (id int); insert into t values (1);",
+               &Arena::default(), |_| {}, |_| {}).unwrap();
+    unsafe { e.db().unwrap().get_tp("t").unwrap().0 }
+  };
+  to_baseline("old_layout", tp_id, 1);
+  let mut e = Eval::default();
+  e.exec_all("use old_layout; insert into t values (2); select * from t;", &Arena::default(), |_| {}, |_| {}).unwrap();
+  e.exec_all("create database other; use other;", &Arena::default(), |_| {}, |_| {}).unwrap();
+  to_baseline("old_layout", tp_id, 127);
+  match e.exec_all("use old_layout;", &Arena::default(), |_| {}, |_| {}) {
+    Err(ModifyError(_, OldTableColTooMany(t))) if t == "t" => {}
+    r => panic!("expect OldTableColTooMany, found {:?}", r),
+  }
+  e.exec_all("drop database old_layout; drop database other;", &Arena::default(), |_| {}, |_| {}).unwrap();
+}
+
+// the offsets of `DbPage::version` and `DbPage::flags` in the db page, `TablePage::check` and `TablePage::col_num` in a table page
+unsafe fn offsets() -> (usize, usize, usize, usize) {
+  let (dp, tp) = (std::mem::zeroed::<physics::DbPage>(), std::mem::zeroed::<physics::TablePage>());
+  let (d, t) = (&dp as *const _ as usize, &tp as *const _ as usize);
+  (&dp.version as *const _ as usize - d, &dp.flags as *const _ as usize - d, &tp.check as *const _ as usize - t, &tp.col_num as *const _ as usize - t)
+}
+
+// turn the file back into the layout before the version and `DbFlags::ROW_CHECK`, where the check slot belonged to the 127th col
+fn to_baseline(path: &str, tp_id: u32, col_num: u8) {
+  let (version_off, flags_off, check_off, col_num_off) = unsafe { offsets() };
+  let tp_off = tp_id as usize * PAGE_SIZE;
+  let mut file = fs::read(path).unwrap();
+  for off in &[version_off, version_off + 1, flags_off, flags_off + 1] { file[*off] = 0; }
+  for b in &mut file[tp_off + check_off..tp_off + check_off + 4] { *b = 0; }
+  file[tp_off + col_num_off] = col_num;
+  fs::write(path, &file).unwrap();
+}
@@ -19,7 +19,7 @@ fn index() {
     let (table, col); // init later
     macro_rules! ins {
       () => {
-        e.exec(&Stmt::Insert(Insert { table: "index", vals: ins.iter().map(|x| vec![lit(*x)]).collect(), cols: None })).unwrap();
+        e.exec(&Stmt::Insert(Insert { table: "index", vals: ins.iter().map(|x| vec![lit(*x)]).collect(), cols: None, on_conflict: None })).unwrap();
         for (idx, &ins) in ins.iter().enumerate() {
           map.insert((ins, idx as i32));
         }
@@ -41,7 +41,12 @@ fn index() {
           let index_count = e.select(&Select {
             ops: None,
             tables: vec!["index"],
+            left_on: None,
             where_: vec![Cond::Cmp(CmpOp::Eq, ColRef { table: None, col: "id" }, Atom::Lit(lit(t)))],
+            group_by: vec![],
+            order_by: vec![],
+            limit: None,
+            with: vec![],
           }).unwrap().row_count();
           let map_count = map.range((&(t, 0))..(&(t, N as i32))).count();
           assert_eq!(index_count, map_count);
@@ -53,7 +58,7 @@ fn index() {
     (test.copy_from_slice(&ins), test.shuffle(&mut rng));
     e.exec(&Stmt::CreateDb("index")).unwrap();
     e.exec(&Stmt::UseDb("index")).unwrap();
-    e.exec(&CreateTable { table: "index", cols: vec![ColDecl { col: "id", ty: ColTy::FixTy(FixTy { size: 0, ty: Int }), notnull: true, dft: None }], cons: vec![] }.into()).unwrap();
+    e.exec(&CreateTable { table: "index", cols: vec![ColDecl { col: "id", ty: ColTy::FixTy(FixTy { size: 0, ty: Int }), notnull: true, dft: None, comment: None }], cons: vec![] }.into()).unwrap();
     e.exec(&CreateIndex { index: "id_index", table: "index", col: "id" }.into()).unwrap();
     unsafe { // modify IndexPage's cap to generate more splits
       let db = e.db().unwrap();
@@ -3,4 +3,8 @@ mod integrate;
 #[cfg(test)]
 mod index;
 #[cfg(test)]
-mod lob;
\ No newline at end of file
+mod lob;
+#[cfg(test)]
+mod corrupt;
+#[cfg(test)]
+mod sql;
@@ -18,8 +18,8 @@ fn lob() {
   e.exec(&CreateTable {
     table: "lob",
     cols: vec![
-      ColDecl { col: "id", ty: ColTy::FixTy(FixTy { size: 0, ty: Int }), notnull: true, dft: None },
-      ColDecl { col: "v", ty: ColTy::Varchar((MAX_LEN * LOB_SLOT_SIZE) as u16), notnull: true, dft: None }
+      ColDecl { col: "id", ty: ColTy::FixTy(FixTy { size: 0, ty: Int }), notnull: true, dft: None, comment: None },
+      ColDecl { col: "v", ty: ColTy::Varchar((MAX_LEN * LOB_SLOT_SIZE) as u16), notnull: true, dft: None, comment: None }
     ],
     cons: vec![],
   }.into()).unwrap();
@@ -33,7 +33,7 @@ fn lob() {
         vec.push(rng.gen_range(0, 0x80));
       }
       let str = String::from_utf8(vec).unwrap();
-      e.exec(&Stmt::Insert(Insert { table: "lob", vals: vec![vec![lit(i), CLit::new(Lit::Str(&str))]], cols: None })).unwrap();
+      e.exec(&Stmt::Insert(Insert { table: "lob", vals: vec![vec![lit(i), CLit::new(Lit::Str(&str))]], cols: None, on_conflict: None })).unwrap();
       result.push(Some(str));
     } else {
       if !result.is_empty() {
@@ -46,9 +46,14 @@ fn lob() {
   }
   for i in 0..N {
     let sel = e.select(&Select {
-      ops: Some(vec![Agg { col: ColRef { table: None, col: "v" }, op: None }]),
+      ops: Some(vec![Agg { col: ColRef { table: None, col: "v" }, op: None, filter: vec![], distinct: false, lit: None, expr: None }]),
       tables: vec!["lob"],
+      left_on: None,
       where_: vec![Cond::Cmp(CmpOp::Eq, ColRef { table: None, col: "id" }, Atom::Lit(lit(i)))],
+      group_by: vec![],
+      order_by: vec![],
+      limit: None,
+      with: vec![],
     }).unwrap();
     if let Some(str) = result[i].as_ref() {
       assert_eq!(sel.row_count(), 1);
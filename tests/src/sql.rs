@@ -0,0 +1,1575 @@
+use typed_arena::Arena;
+use std::{cell::RefCell, thread, time::Duration, sync::atomic::Ordering, path::Path, fs};
+
+use driver::{Eval, ResultSink, TempDb};
+use syntax::{ast::Stmt, Lexer, TokenKind, TokenClass};
+use query::SelectResult;
+use common::{*, Error::*};
+use db::{Db, ColMeta, ColStats, IndexMeta};
+
+macro_rules! ok { ($e: expr, $sql: expr) => { $e.exec_all($sql, &Arena::default(), |x| { let _ = format!("{:?}", x); }, |_| {}).unwrap(); }; }
+macro_rules! err { ($e: expr, $sql: expr) => { $e.exec_all($sql, &Arena::default(), |x| { let _ = format!("{:?}", x); }, |_| {}).unwrap_err(); }; }
+// like `err!`, but also check the kind of error
+macro_rules! err_is {
+  ($e: expr, $sql: expr, $p: pat) => {
+    match $e.exec_all($sql, &Arena::default(), |_| {}, |_| {}) {
+      Err(ModifyError(_, $p)) => {}
+      r => panic!("{}: unexpected result {:?}", $sql, r),
+    }
+  };
+}
+
+// each test works on its own in-memory db, so that tests can run in parallel and don't touch disk
+fn db() -> Eval { Eval::with_db(Db::open_memory().unwrap()) }
+
+// return the outputs of all stmts in `sql` (the csv for select)
+fn outputs(e: &mut Eval, sql: &str) -> Vec<String> {
+  let ret = RefCell::new(Vec::new());
+  e.exec_all(sql, &Arena::default(), |_| {}, |x| ret.borrow_mut().push(x.to_owned())).unwrap();
+  ret.into_inner()
+}
+
+// return the output of the last stmt in `sql`
+fn query(e: &mut Eval, sql: &str) -> String { outputs(e, sql).pop().unwrap_or_default() }
+
+#[test]
+fn insert_select() {
+  let mut e = db();
+  ok!(e, "create table log (id int, msg varchar(10), old bool); create table archive (id int, msg varchar(10), old bool);");
+  ok!(e, "insert into log values (1, 'a', true), (2, 'b', false), (3, 'c', true);");
+  ok!(e, "insert into archive select * from log where old = true;");
+  assert_eq!(query(&mut e, "select * from archive;"), "id,msg,old\n1,\"a\",true\n3,\"c\",true");
+  ok!(e, "insert into archive (old, id) select old, id from log where id = 2;");
+  assert_eq!(query(&mut e, "select * from archive where id = 2;"), "id,msg,old\n2,,false");
+  ok!(e, "insert into log select * from log; -- the select result is fixed before inserting");
+  assert_eq!(query(&mut e, "select count(*) from log;"), "count(*)\n6");
+  err!(e, "create table narrow (id int); insert into narrow select * from log; -- error, too long");
+  err!(e, "insert into narrow select msg from log; -- error, type mismatch");
+}
+
+#[test]
+fn modulo() {
+  let mut e = db();
+  ok!(e, "create table t (id int, f float); insert into t values (1, 1), (2, 2), (3, 3), (4, 4), (-5, 5), (null, 6);");
+  assert_eq!(query(&mut e, "select id from t where id % 2 = 0;"), "id\n2\n4");
+  assert_eq!(query(&mut e, "select id from t where id % 2 <> 0;"), "id\n1\n3\n-5");
+  assert_eq!(query(&mut e, "select id from t where id % 3 >= 1 and id > 0;"), "id\n1\n2\n4");
+  assert_eq!(query(&mut e, "select count(*) from t where id % 2 = null;"), "count(*)\n0");
+  err!(e, "select * from t where id % 0 = 0; -- error, mod by zero");
+  err!(e, "select * from t where f % 2 = 0; -- error, float col");
+  err!(e, "select * from t where id % 2 = 'a'; -- error, type mismatch");
+}
+
+#[test]
+fn length() {
+  let mut e = db();
+  ok!(e, "create table t (id int, c char(5), v varchar(10), length int);");
+  ok!(e, "insert into t values (1, '', 'abc', 1), (2, 'ab', '', 2), (3, 'abcde', 'abcdefghij', 3), (4, null, null, 4), (5, 'abc', 'é', 5);");
+  assert_eq!(query(&mut e, "select id from t where length(c) = 3;"), "id\n5");
+  assert_eq!(query(&mut e, "select id from t where length(c) < 3;"), "id\n1\n2");
+  assert_eq!(query(&mut e, "select id from t where LENGTH (v) >= 3 and length(v) <> 10;"), "id\n1");
+  assert_eq!(query(&mut e, "select id from t where length(v) = 2;"), "id\n5"); // in bytes
+  assert_eq!(query(&mut e, "select id from t where length(t.v) > -1;"), "id\n1\n2\n3\n5"); // null has no length
+  assert_eq!(query(&mut e, "select id from t where length > 4;"), "id\n5"); // still a valid col name
+  err_is!(e, "select * from t where length(id) = 0;", InvalidLenTy(_));
+  err!(e, "select * from t where length(c) = 'a';");
+  err!(e, "select * from t where length(c) = 99999999999;");
+}
+
+#[test]
+fn rename() {
+  let mut e = db();
+  ok!(e, "create table p (id int, v int, primary key(id)); create table c (pid int, foreign key (pid) references p(id));");
+  ok!(e, "insert into p values (1, 10); insert into c values (1);");
+  ok!(e, "alter table p rename to q; alter table q rename column id to pk;");
+  err!(e, "select id from q; -- error, renamed");
+  assert_eq!(query(&mut e, "select pk, v from q;"), "pk,v\n1,10");
+  err!(e, "insert into c values (2); -- error, the foreign link still works");
+  ok!(e, "insert into q values (2, 20); insert into c values (2);");
+  err!(e, "delete from q where pk = 2; -- error, there is foreign link to q.pk");
+  assert_eq!(query(&mut e, "select pk, pid from q, c where pk = pid;"), "pk,pid\n1,1\n2,2");
+  err!(e, "alter table q rename column pk to v; -- error, dup col");
+  err!(e, "alter table q rename to c; -- error, dup table");
+  err!(e, "alter table q rename column x to y; -- error, no such col");
+  ok!(e, "alter table c rename column pid to pid;");
+}
+
+#[test]
+fn sink() {
+  #[derive(Default)]
+  struct Sink { stmts: usize, rows: Vec<usize>, msgs: Vec<String> }
+  impl ResultSink for Sink {
+    fn on_stmt(&mut self, _s: &Stmt) { self.stmts += 1; }
+    fn on_select(&mut self, r: &SelectResult) { self.rows.push(r.row_count()); }
+    fn on_message(&mut self, msg: &str) { self.msgs.push(msg.to_owned()); }
+  }
+  let mut e = db();
+  let mut sink = Sink::default();
+  e.exec_all_sink("create table t (id int); insert into t values (1), (2); select * from t; select * from t where id = 1;", &Arena::default(), &mut sink).unwrap();
+  assert_eq!(sink.stmts, 4);
+  assert_eq!(sink.rows, vec![2, 1]);
+  assert_eq!(sink.msgs, vec!["".to_owned(), "2 column(s) affected".to_owned()]);
+}
+
+#[test]
+fn execute() {
+  let mut e = db();
+  let alloc = Arena::default();
+  assert!(e.execute("create table t (id int);", &alloc).unwrap().is_none());
+  assert!(e.execute("insert into t values (1), (2);", &alloc).unwrap().is_none());
+  assert_eq!(e.execute("select * from t where id > 1;", &alloc).unwrap().unwrap().csv(false), "2");
+  match e.execute("select * from t; select * from t;", &alloc) { Err(ModifyError(_, NotOneStmt(2))) => {} r => panic!("unexpected result {:?}", r.map(|_| ())) }
+  match e.execute("insert into t values (null, 1);", &alloc) { Err(ModifyError(0, InsertTooLong { .. })) => {} r => panic!("unexpected result {:?}", r.map(|_| ())) }
+}
+
+#[test]
+fn import() {
+  let mut e = db();
+  ok!(e, "create table t (id int not null, f float, b bool, d date, s char(5), v char(5) default 'x');");
+  macro_rules! import { ($csv: expr) => { e.import($csv, "t", &Arena::default()) }; }
+  assert_eq!(import!("id,f,b,d,s,v\n1,1.5,true,2019-01-01,\"a,\"\"b\",\n2,,,,\"\",\"\"\n").unwrap(), 2);
+  assert_eq!(import!("s,id\n\"c\",3\n").unwrap(), 1); // pick cols by the header, others use default
+  assert_eq!(query(&mut e, "select * from t;"), "id,f,b,d,s,v\n1,1.5,true,2019-01-01,\"a,\"\"b\",\n2,,,,\"\",\"\"\n3,,,,\"c\",\"x\"");
+  // the output of select can be imported back
+  let csv = query(&mut e, "select * from t;");
+  assert_eq!(import!(&csv).unwrap(), 3);
+  assert_eq!(query(&mut e, "select count(*) from t;"), "count(*)\n6");
+
+  macro_rules! import_err {
+    ($csv: expr, $row: expr, $col: expr) => {
+      match import!($csv) {
+        Err(ModifyError(_, ImportError { row, col, .. })) => assert_eq!((row, col), ($row, $col)),
+        r => panic!("expect ImportError, found {:?}", r),
+      }
+    };
+  }
+  import_err!("id,f\n7,1\n,2\n", 2, Some("id")); // empty field for not null
+  import_err!("f,id\n1,7\n2\n", 2, Some("id")); // missing field for not null
+  import_err!("id\nabc\n", 1, Some("id"));
+  import_err!("id,f\n7,1.x\n", 1, Some("f"));
+  import_err!("id,b\n7,yes\n", 1, Some("b"));
+  import_err!("id,d\n7,2019-13-01\n", 1, Some("d"));
+  import_err!("id,s\n7,abcdef\n", 1, None); // too long, reported by insert
+  import_err!("id\n7,8\n", 1, None);
+  assert_eq!(query(&mut e, "select count(*) from t where id = 7;"), "count(*)\n2"); // rows before the error are imported
+}
+
+#[test]
+fn empty_null() {
+  let mut e = db();
+  ok!(e, "create table t (id int, f float, d date, s char(5), v varchar(5)); create table u (id int not null);");
+  macro_rules! import { ($csv: expr) => { e.import($csv, "t", &Arena::default()) }; }
+  // off by default, '' is a string, which mismatches a numeric col
+  err!(e, "insert into t values ('', 1, null, '', '');");
+  assert!(import!("id,s\n\"\",\"\"\n").is_err());
+  ok!(e, "insert into t values (1, 1, null, '', '');");
+  e.set_empty_null(true);
+  ok!(e, "insert into t values ('', '', '', '', ''), (3, 3, '2020-01-01', '', null);");
+  assert_eq!(import!("id,f,d,s,v\n\"\",\"\",\"\",\"\",\"\"\n4,,,,\n").unwrap(), 2);
+  // string cols keep their empty strings
+  assert_eq!(query(&mut e, "select * from t;"), "id,f,d,s,v\n1,1,,\"\",\"\"\n,,,\"\",\"\"\n3,3,2020-01-01,\"\",\n,,,\"\",\"\"\n4,,,,");
+  assert_eq!(query(&mut e, "select count(*) from t where id is null and s = '';"), "count(*)\n2");
+  // not null still applies to the null it produces
+  err_is!(e, "insert into u values ('');", PutNullOnNotNull);
+  e.set_empty_null(false);
+  err!(e, "insert into t values ('', 1, null, '', '');");
+}
+
+#[test]
+fn int_bool() {
+  let mut e = db();
+  ok!(e, "create table t (id int, b bool);");
+  macro_rules! import { ($csv: expr) => { e.import($csv, "t", &Arena::default()) }; }
+  // off by default, an int mismatches a bool col
+  err!(e, "insert into t values (1, 1);");
+  err!(e, "select * from t where b = 1;");
+  assert!(import!("id,b\n1,1\n").is_err());
+  ok!(e, "insert into t values (1, true);");
+  e.set_int_bool(true);
+  ok!(e, "insert into t values (2, 0), (3, 1), (4, -2), (5, null);");
+  assert_eq!(import!("id,b\n6,0\n7,5\n8,false\n").unwrap(), 3);
+  assert_eq!(query(&mut e, "select * from t;"), "id,b\n1,true\n2,false\n3,true\n4,true\n5,\n6,false\n7,true\n8,false");
+  assert_eq!(query(&mut e, "select id from t where b = 1;"), "id\n1\n3\n4\n7");
+  assert_eq!(query(&mut e, "select id from t where b <> 1;"), "id\n2\n6\n8");
+  assert_eq!(query(&mut e, "select count(*) from t where b = 7;"), "count(*)\n4"); // nonzero is true
+  assert!(import!("id,b\n9,yes\n").is_err());
+  err!(e, "insert into t values (9, 'a');");
+  ok!(e, "create table c (id int, b bool, check (b = 1));");
+  e.set_int_bool(false);
+  err!(e, "insert into t values (9, 1);");
+  err!(e, "select * from t where b = 0;");
+  // the check keeps the setting it was created under
+  ok!(e, "insert into c values (1, true);");
+  err_is!(e, "insert into c values (2, false);", CheckFailed { .. });
+  ok!(e, "create table c1 like c;");
+  err_is!(e, "insert into c1 values (2, false);", CheckFailed { .. });
+  err!(e, "create table d (id int, b bool, check (b = 1));");
+}
+
+#[test]
+fn truncate_str() {
+  let mut e = db();
+  ok!(e, "create table t (id int, s varchar(5), c char(3));");
+  // off by default, an over-long string is an error
+  err_is!(e, "insert into t values (1, 'abcdefg', 'a');", ColLitMismatch { .. });
+  err_is!(e, "insert into t values (1, 'a', 'abcd');", ColLitMismatch { .. });
+  e.set_truncate_str(true);
+  ok!(e, "insert into t values (1, 'abcdefg', 'abcd'), (2, 'xyz', 'xy'), (3, 'ab\u{e9}\u{e9}', null);");
+  assert_eq!(e.import("id,s,c\n4,123456,7890\n", "t", &Arena::default()).unwrap(), 1);
+  ok!(e, "update t set s = 'uvwxyz' where id = 2;");
+  // the 2-byte char is not split
+  assert_eq!(query(&mut e, "select * from t;"), "id,s,c\n1,\"abcde\",\"abc\"\n2,\"uvwxy\",\"xy\"\n3,\"ab\u{e9}\",\n4,\"12345\",\"789\"");
+  assert_eq!(query(&mut e, "select id from t where s = 'abcde';"), "id\n1");
+  e.set_truncate_str(false);
+  err_is!(e, "update t set s = 'uvwxyz' where id = 2;", ColLitMismatch { .. });
+}
+
+#[test]
+fn like_date() {
+  let mut e = db();
+  ok!(e, "create table t (id int, d date); insert into t values (1, '2000-01-31'), (2, '2001-02-01'), (3, '2000-12-01'), (4, null);");
+  assert_eq!(query(&mut e, "select id from t where d like '2000-%';"), "id\n1\n3");
+  assert_eq!(query(&mut e, "select id from t where d like '%-01-%';"), "id\n1");
+  assert_eq!(query(&mut e, "select id from t where d like '____-0_-%';"), "id\n1\n2");
+  err!(e, "select id from t where id like '1'; -- error, like on int");
+}
+
+#[test]
+fn date_lit() {
+  let mut e = db();
+  ok!(e, "create table t (id int, d date, s char(10)); create table u (id int, d date default date '2000-01-01');");
+  ok!(e, "insert into t values (1, date '2020-01-01', '2020-01-01'), (2, '2020-02-01', '2020-02-01'), (3, null, null); insert into u (id) values (1);");
+  assert_eq!(query(&mut e, "select id from t where d = date '2020-01-01';"), "id\n1");
+  assert_eq!(query(&mut e, "select id from t where d > date '2020-01-15';"), "id\n2");
+  assert_eq!(query(&mut e, "select d from u;"), "d\n2000-01-01"); // not `current_date`
+  // a typed date is not a string, so it can't be compared with or put into a char col
+  err_is!(e, "select id from t where s = date '2020-01-01';", ColLitMismatch { .. });
+  err_is!(e, "insert into t (id, s) values (4, date '2020-01-01');", ColLitMismatch { .. });
+  match e.exec_all("select id from t where d = date '2020-13-01';", &Arena::default(), |_| {}, |_| {}) {
+    Err(ModifyError(_, ParserErrors(pe))) => match pe[..] { [ParserError { kind: ParserErrorKind::InvalidDate("2020-13-01"), .. }] => {} _ => panic!() },
+    _ => panic!(),
+  }
+}
+
+#[test]
+fn memory() {
+  const SQL: &str = "create table p (id int, name varchar(20), d date, primary key(id));
+    create table c (id int, pid int, v float, s char(5), foreign key (pid) references p(id)); create index c_v on c(v);
+    insert into p values (1, 'a', '2019-01-01'), (2, 'bb', '2019-01-02'), (3, null, null);
+    insert into c values (1, 1, 1.5, 'x'), (2, 1, 2.5, 'y'), (3, 2, 0.5, null), (4, 3, null, 'z');
+    insert into c values (5, 4, 0, 'w'); -- error, no such foreign
+    update p set name = 'cccccccccc' where id = 2;
+    delete from c where v < 1;
+    select * from p; select * from c where v >= 1.5; select p.name, c.s from p, c where p.id = c.pid;
+    select count(*), avg(v), max(name) from p, c where p.id = c.pid;
+    alter table c drop s; alter table c add s2 int default 7; select * from c;";
+  let (mut file, mut memory) = (Eval::default(), db());
+  ok!(file, "create database memory; use memory;");
+  let (file_ret, memory_ret) = (RefCell::new(Vec::new()), RefCell::new(Vec::new()));
+  // the error stops `exec_all`, so execute stmts one by one
+  for sql in SQL.split(';').filter(|s| !s.trim().is_empty()) {
+    let sql = format!("{};", sql);
+    let f = file.exec_all(&sql, &Arena::default(), |_| {}, |x| file_ret.borrow_mut().push(x.to_owned())).is_ok();
+    let m = memory.exec_all(&sql, &Arena::default(), |_| {}, |x| memory_ret.borrow_mut().push(x.to_owned())).is_ok();
+    assert_eq!(f, m, "{}", sql);
+  }
+  assert_eq!(file_ret.into_inner(), memory_ret.into_inner());
+  ok!(file, "drop database memory;");
+}
+
+#[test]
+fn insert_null() {
+  let mut e = db();
+  ok!(e, "create table t (a int, b int, c char(5), d bool, e int not null);");
+  ok!(e, "insert into t values (1, null, 'x', null, 0), (2, 0, 'y', false, 0);");
+  assert_eq!(query(&mut e, "select a from t where b is null;"), "a\n1");
+  assert_eq!(query(&mut e, "select a from t where b = 0;"), "a\n2"); // null is not stored as 0
+  assert_eq!(query(&mut e, "select a from t where d = false;"), "a\n2");
+  assert_eq!(query(&mut e, "select a from t where d is not null;"), "a\n2");
+  assert_eq!(query(&mut e, "select * from t where a = 1;"), "a,b,c,d,e\n1,,\"x\",,0");
+  err!(e, "insert into t values (3, 0, 'z', true, null); -- error, null on not null");
+  assert_eq!(query(&mut e, "select count(*) from t;"), "count(*)\n2");
+}
+
+#[test]
+fn cancel() {
+  let mut e = db();
+  let vals = vec!["(0)"; 20000].join(", ");
+  ok!(e, &format!("create table a (x int); create table b (x int); insert into a values {0}; insert into b values {0};", vals));
+  let cancel = e.cancel_flag();
+  cancel.store(true, Ordering::Relaxed);
+  err_is!(e, "select * from a;", Cancelled);
+  cancel.store(false, Ordering::Relaxed);
+  assert_eq!(query(&mut e, "select count(*) from a;"), "count(*)\n20000");
+  // 20000 * 20000 comparisons, and none of them matches
+  let t = thread::spawn(move || (thread::sleep(Duration::from_millis(50)), cancel.store(true, Ordering::Relaxed)));
+  err_is!(e, "select * from a, b where a.x <> b.x;", Cancelled);
+  t.join().unwrap();
+}
+
+#[test]
+fn header() {
+  let mut e = db();
+  ok!(e, "create table t (id int, s char(5)); insert into t values (1, 'a'), (2, 'b');");
+  assert_eq!(query(&mut e, "select * from t;"), "id,s\n1,\"a\"\n2,\"b\"");
+  e.set_header(false);
+  assert_eq!(query(&mut e, "select * from t;"), "1,\"a\"\n2,\"b\"");
+  assert_eq!(query(&mut e, "select * from t where id = 3;"), "");
+  assert_eq!(query(&mut e, "select count(*) from t;"), "2");
+  e.set_header(true);
+  assert_eq!(query(&mut e, "select * from t where id = 3;"), "id,s");
+}
+
+#[test]
+fn primary() {
+  let mut e = db();
+  // a single col primary key has an index (created with the table), which is used to check duplication like unique
+  ok!(e, "create table t (id int, v int, primary key(id)); insert into t values (1, 1), (2, 2);");
+  err_is!(e, "insert into t values (1, 3);", PutDupOnUnique { col: "id", .. });
+  err_is!(e, "insert into t values (null, 3);", PutNullOnNotNull);
+  err_is!(e, "insert into t (v) values (3);", PutNullOnNotNull);
+  err_is!(e, "update t set id = 1 where id = 2;", PutDupOnUnique { col: "id", .. });
+  ok!(e, "insert into t values (3, 3); update t set id = 4 where id = 3;");
+  // a composite primary key has no index, duplication is checked by the hash of all primary cols
+  ok!(e, "create table c (a int, b int, primary key(a, b)); insert into c values (1, 1), (1, 2), (2, 1);");
+  err_is!(e, "insert into c values (1, 2);", PutDupOnPrimary);
+  err_is!(e, "insert into c values (3, 1), (3, 1);", PutDupOnPrimary);
+  err_is!(e, "insert into c values (3, null);", PutNullOnNotNull);
+  assert_eq!(query(&mut e, "select count(*) from t;"), "count(*)\n3");
+  assert_eq!(query(&mut e, "select count(*) from c;"), "count(*)\n4"); // the first (3, 1) is inserted
+}
+
+#[test]
+fn group_by() {
+  let mut e = db();
+  ok!(e, "create table emp (id int, dept char(5), salary int); create table dept (name char(5), floor int);");
+  ok!(e, "insert into emp values (1, 'a', 10), (2, 'b', 20), (3, 'a', 20), (4, null, 30), (5, 'b', null), (6, null, 40);");
+  ok!(e, "insert into dept values ('a', 1), ('b', 2);");
+  // groups are in the order of their first appearance, null is a group
+  assert_eq!(query(&mut e, "select dept, count(*) from emp group by dept;"), "dept,count(*)\n\"a\",2\n\"b\",2\n,2");
+  assert_eq!(query(&mut e, "select avg(salary), dept, count(salary) from emp where id > 1 group by dept;"), "avg(salary),dept,count(salary)\n20,\"b\",1\n20,\"a\",1\n35,,2");
+  assert_eq!(query(&mut e, "select floor, sum(salary) from emp, dept where emp.dept = dept.name group by dept.floor;"), "floor,sum(salary)\n1,30\n2,20");
+  assert_eq!(query(&mut e, "select dept, count(*) from emp where id > 10 group by dept;"), "dept,count(*)");
+  // without group by, there is always one result row
+  assert_eq!(query(&mut e, "select count(*), max(id) from emp where id > 10;"), "count(*),max(id)\n0,");
+  // the output follows the select list
+  assert_eq!(query(&mut e, "select salary, id from emp where id = 1;"), "salary,id\n10,1");
+  err_is!(e, "select id, count(*) from emp group by dept;", MixedSelect);
+  err_is!(e, "select id, count(*) from emp;", MixedSelect);
+  err_is!(e, "select emp.dept from emp, dept where emp.dept = dept.name group by dept.name;", MixedSelect);
+  err_is!(e, "select count(*) from emp group by name;", NoSuchCol("name"));
+}
+
+#[test]
+fn date_cmp() {
+  let mut e = db();
+  ok!(e, "create table t (d date, s char(10)); insert into t values ('2019-12-31', '2019-12-31'), ('2020-01-02', '2020-01-02');");
+  // a string literal compared with a date col is parsed as a date
+  assert_eq!(query(&mut e, "select d from t where d > '2020-01-01';"), "d\n2020-01-02");
+  assert_eq!(query(&mut e, "select d from t where d >= '2019-12-31';"), "d\n2019-12-31\n2020-01-02");
+  err_is!(e, "select d from t where d > '2020-13-01';", InvalidDate { date: "2020-13-01", .. });
+  err_is!(e, "select d from t where d > 'abc';", InvalidDate { date: "abc", .. });
+  // a char col is compared as string, even if it holds dates; comparing it with a date col is never implicit
+  assert_eq!(query(&mut e, "select s from t where s > '2020-01-01';"), "s\n\"2020-01-02\"");
+  err_is!(e, "select * from t where s = d;", ColMismatch { .. });
+  err_is!(e, "select * from t where d < s;", ColMismatch { .. });
+}
+
+#[test]
+fn display() {
+  assert_eq!(format!("{}", Lit::Null), "");
+  assert_eq!(format!("{}", CLit::new(Lit::Bool(true))), "true");
+  assert_eq!(format!("{}", CLit::new(Lit::Number(3.0))), "3");
+  assert_eq!(format!("{}", CLit::new(Lit::Number(-1.5))), "-1.5");
+  assert_eq!(format!("{}", CLit::new(Lit::Str("it's \"x\""))), "it's \"x\"");
+  // debug formatting is kept for diagnostics
+  assert_eq!(format!("{:?}", CLit::new(Lit::Null)), "null");
+  assert_eq!(format!("{:?}", CLit::new(Lit::Str("a"))), "'a'");
+  // date can only come from the db
+  struct Sink(Vec<String>);
+  impl ResultSink for Sink {
+    fn on_select(&mut self, r: &SelectResult) { self.0.extend(r.data.iter().map(|x| format!("{}|{:?}", x, x))); }
+    fn on_message(&mut self, _: &str) {}
+  }
+  let mut e = db();
+  let mut sink = Sink(vec![]);
+  e.exec_all_sink("create table t (d date, s varchar(5)); insert into t values ('2020-1-2', null); select * from t;", &Arena::default(), &mut sink).unwrap();
+  assert_eq!(sink.0, vec!["2020-01-02|2020-01-02".to_owned(), "|null".to_owned()]);
+}
+
+#[test]
+fn join3() {
+  let mut e = db();
+  ok!(e, "create table a (x int); create table b (y int); create table c (z int);");
+  ok!(e, "insert into a values (1), (2), (3); insert into b values (10), (20); insert into c values (2), (3), (3), (4);");
+  // the only cross predicate is between table 0 and 2, it is applied when table 2 is joined
+  assert_eq!(query(&mut e, "select count(*) from a, b, c where a.x = c.z;"), "count(*)\n6");
+  assert_eq!(query(&mut e, "select count(*) from a, b, c where c.z = a.x and b.y = 10;"), "count(*)\n3");
+  assert_eq!(query(&mut e, "select a.x, b.y, c.z from a, b, c where a.x < c.z and b.y > 10;"), "x,y,z\n1,20,2\n1,20,3\n1,20,3\n1,20,4\n2,20,3\n2,20,3\n2,20,4\n3,20,4");
+  assert_eq!(query(&mut e, "select count(*) from a, b, c where a.x <> c.z;"), "count(*)\n18");
+}
+
+#[test]
+fn order_by() {
+  let mut e = db();
+  ok!(e, "create table t (id int, g int, s varchar(5)); insert into t values (3, 1, 'c'), (1, null, 'a'), (2, 2, null), (4, 1, 'b');");
+  assert_eq!(query(&mut e, "select id from t order by id;"), "id\n1\n2\n3\n4");
+  assert_eq!(query(&mut e, "select id from t order by s desc;"), "id\n3\n4\n1\n2"); // null is the smallest
+  assert_eq!(query(&mut e, "select id, g from t order by g asc, id desc limit 3;"), "id,g\n1,\n4,1\n3,1");
+  assert_eq!(query(&mut e, "select id from t limit 0;"), "id");
+  assert_eq!(query(&mut e, "select g, count(*) from t group by g order by g desc;"), "g,count(*)\n2,1\n1,2\n,1");
+  assert_eq!(query(&mut e, "select count(*) from t order by id limit 1;"), "count(*)\n4");
+  err_is!(e, "select g, count(*) from t group by g order by id;", MixedSelect);
+  err_is!(e, "select id from t order by x;", NoSuchCol("x"));
+  err!(e, "select id from t limit -1; -- error, invalid int");
+}
+
+#[test]
+fn order_by_agg() {
+  let mut e = db();
+  ok!(e, "create table emp (id int, dept char(5), salary int);");
+  ok!(e, "insert into emp values (1, 'a', 10), (2, 'b', 20), (3, 'b', 30), (4, 'c', 5), (5, 'c', 15), (6, 'c', 25), (7, 'd', 50);");
+  assert_eq!(query(&mut e, "select dept, count(*) from emp group by dept order by count(*) desc;"),
+             "dept,count(*)\n\"c\",3\n\"b\",2\n\"a\",1\n\"d\",1");
+  assert_eq!(query(&mut e, "select dept, count(*) from emp group by dept order by 2 desc, dept desc limit 3;"), "dept,count(*)\n\"c\",3\n\"b\",2\n\"d\",1");
+  assert_eq!(query(&mut e, "select dept, sum(salary) from emp group by dept order by sum(salary);"), "dept,sum(salary)\n\"a\",10\n\"c\",45\n\"b\",50\n\"d\",50");
+  // a position on a non-agg col works like the col itself
+  assert_eq!(query(&mut e, "select salary, id from emp where id < 4 order by 1 desc;"), "salary,id\n30,3\n20,2\n10,1");
+  assert_eq!(query(&mut e, "select max(salary) from emp order by 1;"), "max(salary)\n50");
+  err_is!(e, "select dept, count(*) from emp group by dept order by 3;", InvalidOrderPos(3));
+  err_is!(e, "select dept from emp group by dept order by 0;", InvalidOrderPos(0));
+  err_is!(e, "select dept, count(*) from emp group by dept order by max(salary);", OrderByNotSelected);
+  err_is!(e, "select count(*) from emp group by dept order by dept, count(*);", OrderByNotSelected);
+  err_is!(e, "select count(*) filter (where id > 1) from emp order by count(*);", OrderByNotSelected);
+}
+
+#[test]
+fn order_by_expr() {
+  let mut e = db();
+  ok!(e, "create table item (id int, price float, quantity int);");
+  ok!(e, "insert into item values (1, 2.5, 4), (2, 10, 1), (3, 1, 20), (4, null, 3), (5, 3, 3);");
+  // 10, 10, 20, null, 9; null is the smallest, equal keys keep their order
+  assert_eq!(query(&mut e, "select id from item order by price * quantity desc;"), "id\n3\n1\n2\n5\n4");
+  assert_eq!(query(&mut e, "select id from item order by price * quantity, id desc limit 3;"), "id\n4\n5\n2");
+  assert_eq!(query(&mut e, "select id from item order by (id - 3) * (id - 3), id;"), "id\n3\n2\n4\n1\n5");
+  err_is!(e, "select id from item order by id * x;", NoSuchCol("x"));
+  err_is!(e, "select id from item order by id * 'a';", IncompatibleBin { .. });
+  err_is!(e, "select quantity, count(*) from item group by quantity order by quantity * price;", MixedSelect);
+  assert_eq!(query(&mut e, "select quantity, count(*) from item group by quantity order by quantity % 4;"), "quantity,count(*)\n4,1\n20,1\n1,1\n3,2");
+}
+
+#[test]
+fn group_by_expr() {
+  let mut e = db();
+  ok!(e, "create table emp (id int, salary int); insert into emp values (1, 1200), (2, 1800), (3, 2500), (4, 2100), (5, null), (6, 1000);");
+  // an expression key is selected by writing the same expression, null is a group like in a col key
+  assert_eq!(query(&mut e, "select salary - salary % 1000, count(*) from emp group by salary - salary % 1000;"),
+    "(salary) - ((salary) % (1000)),count(*)\n1000,3\n2000,2\n,1");
+  assert_eq!(query(&mut e, "select count(*), max(salary) from emp group by salary - salary % 1000 order by 1;"), "count(*),max(salary)\n1,\n2,2500\n3,1800");
+  assert_eq!(query(&mut e, "select salary - salary % 1000, count(*) from emp group by salary - salary % 1000 order by 1 desc;"),
+    "(salary) - ((salary) % (1000)),count(*)\n2000,2\n1000,3\n,1");
+  // without group by, it is evaluated on each row
+  assert_eq!(query(&mut e, "select id, salary * 2 from emp where id < 3;"), "id,(salary) * (2)\n1,2400\n2,3600");
+  err_is!(e, "select salary, count(*) from emp group by salary - salary % 1000;", MixedSelect);
+  err_is!(e, "select salary - salary % 100, count(*) from emp group by salary - salary % 1000;", MixedSelect);
+  err_is!(e, "select id * 2, count(*) from emp;", MixedSelect);
+  err_is!(e, "select count(*) from emp group by salary * 'a';", IncompatibleBin { .. });
+}
+
+#[test]
+fn scalar_fn() {
+  let mut e = db();
+  ok!(e, "create table t (id int, v int, b bool); insert into t values (1, 3, null), (2, -4, null), (3, null, null);");
+  let db = e.db().unwrap();
+  db.register_function("twice", 1, LitTy::Number, |a| Ok(match a[0] { LitExt::Number(x) => LitExt::Number(x * 2.0), _ => LitExt::Null })).unwrap();
+  db.register_function("even", 1, LitTy::Bool, |a| Ok(match a[0] { LitExt::Number(x) => LitExt::Bool(x % 2.0 == 0.0), _ => LitExt::Null })).unwrap();
+  db.register_function("one", 0, LitTy::Number, |_| Ok(LitExt::Number(1.0))).unwrap();
+  db.register_function("liar", 0, LitTy::Number, |_| Ok(LitExt::Bool(true))).unwrap(); // not of its declared type
+  db.register_function("root", 1, LitTy::Number, |a| match a[0] {
+    LitExt::Number(x) if x < 0.0 => Err(FunctionFailed(format!("root of {}", x))),
+    LitExt::Number(x) => Ok(LitExt::Number(x.sqrt())),
+    _ => Ok(LitExt::Null),
+  }).unwrap();
+  match db.register_function("name", 1, LitTy::Str, |_| Ok(LitExt::Null)) { Err(InvalidFunctionRet("name")) => {} _ => panic!("expect InvalidFunctionRet") }
+  // a null arg gives null
+  assert_eq!(query(&mut e, "select id, twice(v) from t;"), "id,twice(v)\n1,6\n2,-8\n3,");
+  assert_eq!(query(&mut e, "select twice(v + one()) - 1 from t where id = 1;"), "(twice((v) + (one()))) - (1)\n7");
+  assert_eq!(query(&mut e, "select id from t order by twice(v) desc;"), "id\n1\n2\n3");
+  err_is!(e, "select liar() from t where id = 1;", FunctionRetMismatch { name: "liar", expect: LitTy::Number, actual: LitExt::Bool(true) });
+  // the error of a function stops the stmt, a null arg doesn't call it
+  assert_eq!(query(&mut e, "select id, root(v + 1) from t where id <> 2;"), "id,root((v) + (1))\n1,2\n3,");
+  err_is!(e, "select root(v) from t;", FunctionFailed(_));
+  err_is!(e, "select id from t order by root(v);", FunctionFailed(_));
+  err_is!(e, "update t set v = root(v) where id = 2;", FunctionFailed(_));
+  ok!(e, "update t set b = even(v);");
+  assert_eq!(query(&mut e, "select id, b from t;"), "id,b\n1,false\n2,true\n3,");
+  err_is!(e, "select twice(v, v) from t;", FunctionArity { name: "twice", expect: 1, actual: 2 });
+  err_is!(e, "select nope(v) from t;", NoSuchFunction("nope"));
+  err_is!(e, "update t set v = even(v);", ColLitMismatch { .. });
+}
+
+#[test]
+fn order_by_random() {
+  let mut e = db();
+  ok!(e, "create table t (id int, v float);");
+  ok!(e, &format!("insert into t values {};", (1..=20).map(|i| format!("({}, null)", i)).collect::<Vec<_>>().join(", ")));
+  let ids = |s: String| s.lines().skip(1).map(|x| x.parse::<u32>().unwrap()).collect::<Vec<_>>();
+  e.set_seed(Some(42));
+  let sample = ids(query(&mut e, "select id from t order by random() limit 5;"));
+  // each select restarts from the seed, so the sample is the same
+  assert_eq!(ids(query(&mut e, "select id from t order by random() limit 5;")), sample);
+  let mut all = ids(query(&mut e, "select id from t order by random();"));
+  assert_eq!(&all[..5], &sample[..]);
+  all.sort();
+  assert_eq!(all, (1..=20).collect::<Vec<_>>());
+  e.set_seed(Some(7));
+  assert_ne!(ids(query(&mut e, "select id from t order by random();")), ids(query(&mut e, "select id from t;")));
+  // update also restarts from the seed, and each row gets its own value in [0, 1)
+  ok!(e, "update t set v = random();");
+  let vs = query(&mut e, "select v from t;");
+  ok!(e, "update t set v = random();");
+  assert_eq!(query(&mut e, "select v from t;"), vs);
+  assert_eq!(query(&mut e, "select count(*) from t where v >= 0 and v < 1;"), "count(*)\n20");
+  assert_eq!(query(&mut e, "select count(distinct v) from t;"), "count(distinct v)\n20");
+  err_is!(e, "select random(1) from t;", FunctionArity { name: "random", expect: 0, actual: 1 });
+}
+
+#[test]
+fn scan_table() {
+  let mut e = db();
+  ok!(e, "create table t (id int, name varchar(10), ok bool); insert into t values (1, 'alice', true), (2, 'bob', null), (3, null, false);");
+  ok!(e, "delete from t where id = 2;");
+  let db = e.db().unwrap();
+  let mut rows = vec![];
+  db.scan_table("t", |vals, rid| rows.push((vals.to_vec(), rid))).unwrap();
+  // the deleted record is skipped, the others are in the order of their slots
+  assert_eq!(rows.iter().map(|(vals, _)| vals.clone()).collect::<Vec<_>>(), vec![
+    vec![LitExt::Number(1.0), LitExt::Str("alice".to_owned()), LitExt::Bool(true)],
+    vec![LitExt::Number(3.0), LitExt::Null, LitExt::Bool(false)],
+  ]);
+  assert!(rows[0].1.slot() < rows[1].1.slot());
+  match db.scan_table("nope", |_, _| {}) { Err(NoSuchTable("nope")) => {} r => panic!("unexpected result {:?}", r) }
+}
+
+#[test]
+fn delete_with_index() {
+  let mut e = db();
+  ok!(e, "create table log (id int, ts date, msg varchar(5)); create index ts_index on log(ts); create index id_index on log(id);");
+  ok!(e, "insert into log values (1, '2019-12-30', 'a'), (2, '2020-01-01', 'b'), (3, '2019-06-01', 'c'), (4, null, 'd'), (5, '2020-03-01', 'e'), (6, '2019-01-01', 'f');");
+  // the records are found by the index on ts, and removed from both indexes
+  assert_eq!(query(&mut e, "delete from log where ts < '2020-01-01' and id > 1;"), "2 column(s) affected");
+  assert_eq!(query(&mut e, "select id from log where ts <= '2020-12-31';"), "id\n1\n2\n5");
+  assert_eq!(query(&mut e, "select id from log where id >= 0;"), "id\n1\n2\n4\n5");
+  assert_eq!(query(&mut e, "delete from log where id in (1, 4, 7);"), "2 column(s) affected");
+  assert_eq!(query(&mut e, "select id, msg from log where id >= 0;"), "id,msg\n2,\"b\"\n5,\"e\"");
+  assert_eq!(query(&mut e, "select id from log where ts >= '2019-01-01';"), "id\n2\n5");
+  ok!(e, "insert into log values (3, '2019-06-01', 'g');");
+  assert_eq!(query(&mut e, "select id, msg from log where ts = '2019-06-01';"), "id,msg\n3,\"g\"");
+  assert_eq!(query(&mut e, "select count(*) from log;"), "count(*)\n3");
+}
+
+#[test]
+fn keyset_pagination() {
+  let mut e = db();
+  ok!(e, "create table t (id int, v int); create index id_index on t(id);");
+  // insert in a scattered order, so that the result is only ordered if the index (or sort) is used
+  let values = (0..1000).map(|i| format!("({}, {})", i * 7 % 1000, i)).collect::<Vec<_>>().join(", ");
+  ok!(e, &format!("insert into t values {}, (null, -1);", values));
+  let (mut last, mut seen) = (-1, 0);
+  loop {
+    let page = query(&mut e, &format!("select id from t where id > {} order by id limit 64;", last));
+    let ids = page.lines().skip(1).map(|x| x.parse::<i32>().unwrap()).collect::<Vec<_>>();
+    if ids.is_empty() { break; }
+    assert!(ids.len() <= 64);
+    assert_eq!(ids, (last + 1..last + 1 + ids.len() as i32).collect::<Vec<_>>());
+    (last = *ids.last().unwrap(), seen += ids.len());
+  }
+  assert_eq!(seen, 1000);
+  // the other preds are still checked while scanning the index
+  assert_eq!(query(&mut e, "select id from t where id >= 10 and v < 10 order by id limit 3;"), "id\n14\n21\n28");
+  assert_eq!(query(&mut e, "select id from t where id < 3 order by id limit 5;"), "id\n0\n1\n2");
+  // without a predicate rejecting null, the index can't be used, and null comes first
+  assert_eq!(query(&mut e, "select id from t order by id limit 2;"), "id\n\n0");
+}
+
+#[test]
+fn ordered_limit_analyze() {
+  let mut e = db();
+  ok!(e, "create table t (id int, v int); create index id_index on t(id);");
+  let values = (0..1000).map(|i| format!("({}, {})", i, i % 10)).collect::<Vec<_>>().join(", ");
+  ok!(e, &format!("insert into t values {};", values));
+  let alloc = Arena::default();
+  // the scan line without the time
+  let scan = |e: &Eval, sql: &'static str| match syntax::work(sql, &alloc).unwrap().pop() {
+    Some(Stmt::Select(s)) => e.explain_analyze(&s).unwrap().lines().nth(1).unwrap().split(" (").next().unwrap().to_owned(),
+    _ => unreachable!(),
+  };
+  // the index scan stops at the limit, instead of reading the other 889 records
+  assert_eq!(scan(&e, "select * from t where id > 100 order by id limit 10;"), "  scan `t`: 10 of 1000 rows, 1 index seeks, in the order of `id`, 10 records read");
+  assert_eq!(scan(&e, "select * from t where id > 100 and v = 0 order by id limit 3;"), "  scan `t`: 3 of 1000 rows, 1 index seeks, in the order of `id`, 30 records read");
+  assert_eq!(query(&mut e, "select id from t where id > 100 and v = 0 order by id limit 3;"), "id\n110\n120\n130");
+  // `v` has no index, so all records are read and sorted
+  assert_eq!(scan(&e, "select * from t where v > 0 order by v limit 10;"), "  scan `t`: 900 of 1000 rows");
+}
+
+#[test]
+fn keyword() {
+  fn classes(sql: &str) -> Vec<TokenClass> {
+    let mut lexer = Lexer::new(sql.as_bytes());
+    let mut ret = vec![];
+    loop {
+      match lexer.next().ty.class() { TokenClass::End => break ret, c => ret.push(c) }
+    }
+  }
+  use TokenClass::*;
+  assert_eq!(classes("select a from t group by a order by a desc limit 1;"),
+             vec![Keyword, Ident, Keyword, Ident, Keyword, Ident, Keyword, Ident, Keyword, Lit, Punct]);
+  assert_eq!(classes("GROUP  BY Order by ASC"), vec![Keyword, Keyword, Keyword]);
+  assert_eq!(classes("grouped group_by orders"), vec![Ident, Ident, Ident]);
+  assert_eq!(classes("count(x) int 'a'"), vec![Agg, Punct, Ident, Punct, Ty, Lit]);
+  match Lexer::new(b"group by").next().ty { TokenKind::GroupBy => {} _ => panic!("group by is not a keyword") }
+}
+
+#[test]
+fn lex_error() {
+  assert_eq!(Lexer::new(b"'it''s").next().ty.class(), TokenClass::Lit);
+  match Lexer::new(b"'it''s").next().ty { TokenKind::UnterminatedStr => {} _ => panic!("not an unterminated string") }
+  match Lexer::new(b"'it''s'").next().ty { TokenKind::StrLit => {} _ => panic!("not a string") }
+  let mut e = db();
+  ok!(e, "create table t (name char(8), d date);");
+  let kinds = |e: &mut Eval, sql: &str| match e.exec_all(sql, &Arena::default(), |_| {}, |_| {}) {
+    Err(ModifyError(_, ParserErrors(pe))) => pe.iter().map(|pe| format!("{}:{} {:?}", pe.line, pe.col, pe.kind)).collect::<Vec<_>>(),
+    r => panic!("{}: unexpected result {:?}", sql, r),
+  };
+  // the position is where the literal starts, even if it spans lines
+  assert_eq!(kinds(&mut e, "select *\nfrom t where name = 'abc;\nselect * from t;"), vec!["2:21 UnterminatedString"]);
+  assert_eq!(kinds(&mut e, "select * from t where d = date '2020-01-01;"), vec!["1:32 UnterminatedString"]);
+  assert_eq!(kinds(&mut e, "select * from t where name = 'a' # 1;"), vec!["1:34 UnexpectedChar('#')"]);
+}
+
+#[test]
+fn current_date() {
+  let mut e = db();
+  let today = format!("{}", Lit::Date(today()));
+  ok!(e, "create table t (id int, d date default current_date); insert into t (id) values (1), (2); insert into t values (3, current_date);");
+  assert_eq!(query(&mut e, "select d from t;"), format!("d\n{0}\n{0}\n{0}", today));
+  assert_eq!(query(&mut e, "select count(*) from t where d = current_date;"), "count(*)\n3");
+  ok!(e, "insert into t values (4, '2000-01-01'), (5, null); update t set d = current_date where id = 4;");
+  assert_eq!(query(&mut e, "select id from t where d <> CURRENT_DATE;"), "id");
+  assert_eq!(query(&mut e, "select id from t where d is null;"), "id\n5");
+  // existing records get the date when the col is added
+  ok!(e, "alter table t add d1 date default current_date; insert into t (id) values (6);");
+  assert_eq!(query(&mut e, "select count(*) from t where d1 = current_date;"), "count(*)\n6");
+  err_is!(e, "create table u (id int default current_date);", ColLitMismatch { .. });
+  err_is!(e, "create table u (s varchar(10) default current_date);", UnsupportedVarcharOp("s"));
+  err_is!(e, "insert into t (id) values (current_date);", ColLitMismatch { .. });
+}
+
+#[test]
+fn current_date_offset() {
+  let mut e = db();
+  ok!(e, "create table t (id int, d date); insert into t values (1, current_date - 40), (2, current_date - 30), (3, CURRENT_DATE-7), (4, current_date), (5, current_date + 1_0);");
+  let day = |n| format!("{}", Lit::Date(today_offset(n).unwrap()));
+  assert_eq!(query(&mut e, "select d from t where id = 1 or id = 5;"), format!("d\n{}\n{}", day(-40), day(10)));
+  // the rows within the last 30 days
+  assert_eq!(query(&mut e, "select id from t where d >= current_date - 30 and d <= current_date;"), "id\n2\n3\n4");
+  assert_eq!(query(&mut e, "select id from t where d < current_date - 7;"), "id\n1\n2");
+  ok!(e, "update t set d = current_date + 1 where id = 4;");
+  assert_eq!(query(&mut e, "select count(*) from t where d > current_date;"), "count(*)\n2");
+  err!(e, "select * from t where d > current_date - 99999999999;");
+  err!(e, "select * from t where d > current_date - 999999999;"); // out of the range of date
+}
+
+#[test]
+fn select_star_order() {
+  let mut e = db();
+  ok!(e, "create table t (a int, b char(3), c float); insert into t values (1, 'x', 1.5);");
+  assert_eq!(query(&mut e, "select * from t;"), "a,b,c\n1,\"x\",1.5");
+  // adding a bool col changes the physical layout (the float col is 4-aligned), but not the logical order
+  ok!(e, "alter table t add d bool default true; alter table t drop b; alter table t add e char(2);");
+  assert_eq!(query(&mut e, "select * from t;"), "a,c,d,e\n1,1.5,true,");
+  ok!(e, "insert into t values (2, 2.5, false, 'yy');");
+  assert_eq!(query(&mut e, "select * from t where a = 2;"), "a,c,d,e\n2,2.5,false,\"yy\"");
+}
+
+#[test]
+fn out_of_range() {
+  let mut e = db();
+  ok!(e, "create table t (i int, f float); create index i_index on t(i); insert into t values (2147483647, 300000000000000000000000000000000000000.0);");
+  err_is!(e, "insert into t values (2147483648, 0);", ParserErrors(_)); // an int literal must fit i32
+  ok!(e, "insert into t values (-2147483648, 0);");
+  assert_eq!(query(&mut e, "select i from t where i < 0;"), "i\n-2147483648");
+  match e.exec_all("insert into t values (0, 0), (2147483648, 0);", &Arena::default(), |_| {}, |_| {}) {
+    Err(ModifyError(_, ParserErrors(pe))) => match pe[..] { [ParserError { kind: ParserErrorKind::IntLiteralOverflow("2147483648"), line: 1, .. }] => {} _ => panic!() },
+    _ => panic!(),
+  }
+  err_is!(e, "insert into t values (2147483648.0, 0);", ValueOutOfRange { .. });
+  err_is!(e, "insert into t values (-2147483649.0, 0);", ValueOutOfRange { .. });
+  err_is!(e, "insert into t values (0, 1000000000000000000000000000000000000000.0);", ValueOutOfRange { .. }); // inf as f32
+  err_is!(e, "update t set i = i + 1;", ValueOutOfRange { .. });
+  err_is!(e, "update t set f = f * 10.0;", ValueOutOfRange { .. });
+  err_is!(e, "create table u (i int default 2147483648.0);", ValueOutOfRange { .. });
+  ok!(e, "insert into t values (-2147483648.0, -1.5);");
+  assert_eq!(query(&mut e, "select i from t where i < 0;"), "i\n-2147483648");
+}
+
+#[test]
+fn agg_filter() {
+  let mut e = db();
+  ok!(e, "create table t (id int, status char(5), v int); create table s (name char(5), ok bool, m int);");
+  ok!(e, "insert into t values (1, 'ok', 10), (2, 'fail', 20), (3, 'ok', null), (4, 'ok', 40), (5, 'fail', 50);");
+  ok!(e, "insert into s values ('ok', true, 3), ('fail', false, 3);");
+  assert_eq!(query(&mut e, "select count(*) filter (where status = 'ok'), count(*), sum(v) filter (where id > 1 and id < 5) from t;"),
+             "count(*),count(*),sum(v)\n3,5,60");
+  assert_eq!(query(&mut e, "select status, count(v) filter (where v > 15), max(id) from t group by status;"), "status,count(v),max(id)\n\"ok\",1,4\n\"fail\",2,5");
+  // nothing passes the filter
+  assert_eq!(query(&mut e, "select count(*) filter (where id > 10), avg(v) filter (where id > 10) from t;"), "count(*),avg(v)\n0,");
+  // filter can refer to any table, including predicates between tables
+  assert_eq!(query(&mut e, "select count(*) filter (where ok = true), count(*) filter (where t.id > s.m) from t, s where t.status = s.name;"), "count(*),count(*)\n3,2");
+  assert_eq!(query(&mut e, "select count(*) filter (where ok = true) filter (where id > 1) from t, s where t.status = s.name;"), "count(*)\n2");
+  err_is!(e, "select id filter (where id > 1) from t;", FilterOnNonAgg("id"));
+  err_is!(e, "select count(*) filter (where x > 1) from t;", NoSuchCol("x"));
+  err_is!(e, "select count(*) filter (where status > 1) from t;", ColLitMismatch { .. });
+}
+
+#[test]
+fn agg_distinct() {
+  let mut e = db();
+  ok!(e, "create table t (id int, name char(5), amount int, f float);");
+  ok!(e, "insert into t values (1, 'a', 10, 1.5), (2, 'a', 10, 1.5), (3, 'b', 20, 2.5), (4, 'b', null, 0.0), (5, null, 20, null);");
+  assert_eq!(query(&mut e, "select sum(distinct amount), sum(amount), avg(distinct amount), count(distinct amount), count(amount) from t;"),
+             "sum(distinct amount),sum(amount),avg(distinct amount),count(distinct amount),count(amount)\n30,60,15,2,4");
+  assert_eq!(query(&mut e, "select count(distinct name), sum(distinct f), count(distinct f) from t;"), "count(distinct name),sum(distinct f),count(distinct f)\n2,4,3");
+  assert_eq!(query(&mut e, "select name, sum(distinct amount) from t where name = 'b' group by name;"), "name,sum(distinct amount)\n\"b\",20");
+  err_is!(e, "select sum(distinct name) from t;", InvalidAgg { .. });
+}
+
+#[test]
+fn percentile() {
+  let mut e = db();
+  ok!(e, "create table t (g int, v int, f float, s char(2)); insert into t values (1, 5, 0.5, 'a'), (1, 1, 1.5, 'b'), (1, 4, null, 'c'), (1, 2, 2.5, 'd'), (1, 3, 3.5, 'e'), (2, 10, null, 'f'), (2, 20, null, 'g');");
+  // p = 0 is min, p = 1 is max, p = 0.5 is the median
+  assert_eq!(query(&mut e, "select g, percentile_disc(0) within group (order by v), percentile_disc(1) within group (order by v), percentile_disc(0.5) within group (order by v), percentile_cont(0.5) within group (order by v) from t group by g;"),
+             "g,percentile_disc(0) within group (order by v),percentile_disc(1) within group (order by v),percentile_disc(0.5) within group (order by v),percentile_cont(0.5) within group (order by v)\n1,1,5,3,3\n2,10,20,10,15");
+  // nulls are ignored, disc picks an existing value, cont interpolates between two
+  assert_eq!(query(&mut e, "select percentile_disc(0.5) within group (order by f), percentile_cont(0.5) within group (order by f), percentile_cont(0.375) within group (order by v) from t where g = 1;"),
+             "percentile_disc(0.5) within group (order by f),percentile_cont(0.5) within group (order by f),percentile_cont(0.375) within group (order by v)\n1.5,2,2.5");
+  assert_eq!(query(&mut e, "select percentile_cont(0.5) within group (order by f) from t where g = 2;"), "percentile_cont(0.5) within group (order by f)\n");
+  match e.exec_all("select percentile_disc(1.5) within group (order by v) from t;", &Arena::default(), |_| {}, |_| {}) {
+    Err(ModifyError(_, ParserErrors(pe))) => match pe[..] { [ParserError { kind: ParserErrorKind::InvalidPercentile("1.5"), .. }] => {} _ => panic!() },
+    r => panic!("unexpected result {:?}", r),
+  }
+  err_is!(e, "select percentile_disc(0.5) within group (order by s) from t;", InvalidAgg { op: AggOp::PercentileDisc(_), .. });
+}
+
+#[test]
+fn group_key() {
+  let mut e = db();
+  ok!(e, "create table t (f float); insert into t values (0.0), (-0.0), (null), (null), (1.5), (1.5);");
+  // group by and distinct agree on which values are the same: nulls are one group, -0.0 is 0.0
+  assert_eq!(query(&mut e, "select count(*) from t group by f;"), "count(*)\n2\n2\n2");
+  assert_eq!(query(&mut e, "select count(distinct f), sum(distinct f) from t;"), "count(distinct f),sum(distinct f)\n2,1.5");
+  assert_eq!(LitExt::Number(-0.0).group_key(), LitExt::Number(0.0).group_key());
+  assert_eq!(LitExt::Null.group_key(), Lit::Null.group_key());
+  assert_ne!(LitExt::Str("a".into()).group_key(), LitExt::Str("A".into()).group_key());
+}
+
+#[test]
+fn no_db() {
+  let mut e = Eval::default();
+  err_is!(e, "select * from t;", NoDbInUse);
+  err_is!(e, "create table t (id int);", NoDbInUse);
+  err_is!(e, "insert into t values (1);", NoDbInUse);
+  err_is!(e, "show tables;", NoDbInUse);
+  match e.import("id\n1", "t", &Arena::default()) { Err(ModifyError(0, NoDbInUse)) => {} r => panic!("unexpected result {:?}", r) }
+  // stmts not depending on a db still work
+  assert_eq!(outputs(&mut e, "show databases;").len(), 1);
+}
+
+#[test]
+fn top() {
+  let mut e = db();
+  ok!(e, "create table t (id int); insert into t values (1), (2), (3), (4), (5), (6), (7);");
+  assert_eq!(query(&mut e, "select top 5 * from t;"), "id\n1\n2\n3\n4\n5");
+  assert_eq!(query(&mut e, "select TOP 2 id from t where id > 3 order by id desc;"), "id\n7\n6");
+  assert_eq!(query(&mut e, "select top 10 count(*) from t;"), "count(*)\n7");
+  err_is!(e, "select top 5 * from t limit 5;", ParserErrors(_));
+  err_is!(e, "select top -1 * from t;", ParserErrors(_));
+}
+
+#[test]
+fn introspect() {
+  let mut e = db();
+  ok!(e, "create table p (id int, name varchar(10) not null, primary key(id));");
+  ok!(e, "create table c (pid int, d date, unique(d), foreign key (pid) references p(id));");
+  let db = e.db().unwrap();
+  assert_eq!(db.tables(), vec!["p", "c"]);
+  let meta = |name, ty, notnull, primary, unique, index, foreign| ColMeta { name, ty, notnull, primary, unique, index, foreign, comment: None };
+  let (int, date) = (ColTy::FixTy(FixTy { size: 0, ty: BareTy::Int }), ColTy::FixTy(FixTy { size: 0, ty: BareTy::Date }));
+  assert_eq!(db.columns("p").unwrap(), vec![meta("id", int, true, true, false, true, None), meta("name", ColTy::Varchar(10), true, false, false, false, None)]);
+  assert_eq!(db.columns("c").unwrap(), vec![meta("pid", int, false, false, false, true, Some(("p", "id"))), meta("d", date, false, false, true, true, None)]);
+  match db.columns("x") { Err(NoSuchTable("x")) => {} _ => panic!("expect NoSuchTable") }
+}
+
+#[test]
+fn show_indexes() {
+  let mut e = db();
+  ok!(e, "create table t (id int, s char(5), v int, w int, primary key(id), unique(w)); create index t_s on t(s);");
+  let db = e.db().unwrap();
+  let meta = |name, col, unique| IndexMeta { name, col, unique };
+  assert_eq!(db.indexes("t").unwrap(), vec![meta("", "id", true), meta("t_s", "s", false), meta("", "w", true)]);
+  match db.indexes("x") { Err(NoSuchTable("x")) => {} _ => panic!("expect NoSuchTable") }
+  ok!(e, "drop index t_s; create index t_v on t(v);");
+  assert_eq!(query(&mut e, "show indexes from t;"), "table `t`: index count = 3\n  - index <internal>: col `id`, unique, asc\n  - index `t_v`: col `v`, not unique, asc\n  - index <internal>: col `w`, unique, asc");
+  err_is!(e, "show indexes from x;", NoSuchTable("x"));
+}
+
+#[test]
+fn create_table_like() {
+  let mut e = db();
+  ok!(e, "create table p (id int, primary key(id)); insert into p values (1), (2);");
+  ok!(e, "create table t (id int not null default 7 comment 'key', s char(5), v varchar(9), d date default current_date, pid int, \
+    primary key(id), unique(s), check (s in ('a', 'b', 'c')), check (id > 0), foreign key (pid) references p(id)); create index t_d on t(d);");
+  ok!(e, "insert into t values (1, 'a', 'x', '2020-01-01', 1), (2, 'b', null, null, 2);");
+  ok!(e, "create table u like t;");
+  assert_eq!(query(&mut e, "select count(*) from u;"), "count(*)\n0");
+  let db = e.db().unwrap();
+  // the foreign key (and its internal index) is not copied, so pid is an ordinary col
+  let (t, u) = (db.columns("t").unwrap(), db.columns("u").unwrap());
+  assert_eq!(u, t.into_iter().map(|c| ColMeta { foreign: None, index: c.index && c.foreign.is_none(), ..c }).collect::<Vec<_>>());
+  // the copy of a named index is named `<table>_<index>`
+  let meta = |name, col, unique| IndexMeta { name, col, unique };
+  assert_eq!(db.indexes("u").unwrap(), vec![meta("", "id", true), meta("", "s", true), meta("u_t_d", "d", false)]);
+  // the defaults and checks are copied
+  ok!(e, "insert into u (s) values ('c');");
+  assert_eq!(query(&mut e, "select id, s from u where d = current_date;"), "id,s\n7,\"c\"");
+  err_is!(e, "insert into u values (8, 'd', null, null, null);", PutNotInCheck { .. });
+  err_is!(e, "insert into u values (-1, 'a', null, null, null);", CheckFailed { .. });
+  err_is!(e, "insert into u values (7, 'a', null, null, null);", PutDupOnUnique { col: "id", .. });
+  ok!(e, "insert into u values (8, 'a', null, null, 5);");
+  ok!(e, "create table q (x int); create index w_t_d on q(x);");
+  err_is!(e, "create table w like t;", LikeIndexName { table: "w", like: "t" });
+  ok!(e, "drop index w_t_d; create table w like t including data;");
+  assert_eq!(query(&mut e, "select * from w;"), "id,s,v,d,pid\n1,\"a\",\"x\",2020-01-01,1\n2,\"b\",,,2");
+  assert_eq!(query(&mut e, "select id from w where d = '2020-01-01';"), "id\n1");
+  err_is!(e, "create table w like t;", DupTable("w"));
+  err_is!(e, "create table x like nothing;", NoSuchTable("nothing"));
+  err_is!(e, "create table table_copy_1 like t;", LikeIndexName { table: "table_copy_1", like: "t" });
+}
+
+#[test]
+fn bool_cmp() {
+  let mut e = db();
+  ok!(e, "create table t (id int, b bool); insert into t values (1, true), (2, false), (3, null);");
+  ok!(e, "create table u (k int, c bool); insert into u values (10, false), (20, true);");
+  // false < true
+  let scan = [query(&mut e, "select id from t where b < true;"), query(&mut e, "select id from t where b >= false order by id;"), query(&mut e, "select id from t where b > false;")];
+  assert_eq!(scan, ["id\n2", "id\n1\n2", "id\n1"]);
+  assert_eq!(query(&mut e, "select id, k from t, u where t.b < u.c;"), "id,k\n2,20");
+  // the index path agrees with the plain scan
+  ok!(e, "create index b_index on t(b);");
+  let index = [query(&mut e, "select id from t where b < true;"), query(&mut e, "select id from t where b >= false order by id;"), query(&mut e, "select id from t where b > false;")];
+  assert_eq!(index, scan);
+}
+
+#[test]
+fn open() {
+  let mut e = Eval::default();
+  ok!(e, "create database open_test; use open_test; create table t (id int); insert into t values (1);");
+  assert_eq!(e.path(), Some("open_test"));
+  let mut e1 = Eval::default();
+  e1.open("./open_test").unwrap();
+  assert_eq!(e1.path(), Some("./open_test"));
+  drop(e1);
+  // the lob file of a db is not a db itself
+  match e.open("open_test.lob") { Err(InvalidMagic(_)) | Err(InvalidSize { .. }) => {} r => panic!("expect invalid db, found {:?}", r) }
+  // the db in use is unchanged
+  assert_eq!(e.path(), Some("open_test"));
+  assert_eq!(query(&mut e, "select * from t;"), "id\n1");
+  ok!(e, "drop database open_test;");
+}
+
+#[test]
+fn rename_db() {
+  let mut e = Eval::default();
+  ok!(e, "create database rename_a; create database rename_c; use rename_a; create table t (id int, s varchar(5)); insert into t values (1, 'x');");
+  err_is!(e, "alter database rename_a rename to rename_b;", DbInUse("rename_a"));
+  let mut e1 = Eval::default();
+  ok!(e1, "use rename_c;");
+  err_is!(e, "alter database rename_c rename to rename_d;", DbInUse("rename_c"));
+  drop(e1);
+  err_is!(e, "alter database rename_c rename to rename_a;", DbExists("rename_a"));
+  ok!(e, "use rename_c; alter database rename_a rename to rename_b;");
+  err!(e, "use rename_a;");
+  ok!(e, "use rename_b;");
+  assert_eq!(query(&mut e, "select * from t;"), "id,s\n1,\"x\""); // the lob file is renamed with it
+  err!(e, "alter database rename_x rename to rename_y; -- error, no such db");
+  ok!(e, "use rename_c; drop database rename_b; drop database rename_c;");
+}
+
+#[test]
+fn negative_index() {
+  let mut e = db();
+  ok!(e, "create table t (id int, v int); create index id_index on t(id);");
+  // enough keys to split the index into several pages
+  let values = (-1000..1000).rev().map(|i| format!("({}, {})", i, i % 7)).collect::<Vec<_>>().join(", ");
+  ok!(e, &format!("insert into t values {};", values));
+  assert_eq!(query(&mut e, "select count(*), min(id), max(id) from t where id >= -5;"), "count(*),min(id),max(id)\n1005,-5,999");
+  assert_eq!(query(&mut e, "select id from t where id >= -3 and id < 3;"), "id\n-3\n-2\n-1\n0\n1\n2");
+  assert_eq!(query(&mut e, "select count(*) from t where id < 0;"), "count(*)\n1000");
+  assert_eq!(query(&mut e, "select id from t where id <= -999;"), "id\n-1000\n-999");
+}
+
+#[test]
+fn catalog() {
+  let mut e = db();
+  ok!(e, "create table p (id int, name varchar(10) not null, primary key(id)); create table c (pid int, s char(5), d date, ok bool);");
+  ok!(e, "insert into p values (1, 'a'), (2, 'b'); insert into c values (1, 'x', '2000-01-01', true);");
+  assert_eq!(query(&mut e, "select * from __tables;"), "name,ncols,nrows\n\"p\",2,2\n\"c\",4,1");
+  assert_eq!(query(&mut e, "select name from __tables where nrows > 1;"), "name\n\"p\"");
+  assert_eq!(query(&mut e, "select name, type, notnull from __columns where tbl_name = 'c';"),
+             "name,type,notnull\n\"pid\",\"int\",false\n\"s\",\"char(5)\",false\n\"d\",\"date\",false\n\"ok\",\"bool\",false");
+  assert_eq!(query(&mut e, "select tbl_name, name from __columns where notnull = true;"), "tbl_name,name\n\"p\",\"id\"\n\"p\",\"name\"");
+  assert_eq!(query(&mut e, "select count(*) from __tables, __columns where __tables.name = __columns.tbl_name;"), "count(*)\n6");
+  err_is!(e, "select * from __tables, p;", CatalogJoin("p"));
+  // read-only
+  err_is!(e, "insert into __tables values ('x', 1, 1);", NoSuchTable("__tables"));
+}
+
+#[test]
+fn float_special() {
+  let mut e = db();
+  ok!(e, "create table t (v float); insert into t values (-0.0), (0.0), (1.5), (-1.5);");
+  assert_eq!(query(&mut e, "select v, count(*) from t group by v;"), "v,count(*)\n0,2\n1.5,1\n-1.5,1");
+  assert_eq!(query(&mut e, "select count(*) from t where v = -0.0;"), "count(*)\n2");
+  assert_eq!(query(&mut e, "select min(v), max(v) from t where v >= 0.0;"), "min(v),max(v)\n0,1.5");
+  assert_eq!(query(&mut e, "select v from t order by v desc;"), "v\n1.5\n0\n0\n-1.5");
+  // inf (and nan) can't be stored
+  err_is!(e, "update t set v = v / 0.0 where v > 1.0;", ValueOutOfRange { .. });
+  err_is!(e, "update t set v = v * 340000000000000000000000000000000000000.0 where v > 1.0;", ValueOutOfRange { .. });
+  ok!(e, "create table u (v float, unique(v)); insert into u values (0.0);");
+  err_is!(e, "insert into u values (-0.0);", PutDupOnUnique { .. });
+}
+
+#[test]
+fn left_join() {
+  let mut e = db();
+  ok!(e, "create table a (id int, name varchar(5)); create table b (aid int, v int);");
+  ok!(e, "insert into a values (1, 'x'), (2, 'y'), (3, 'z'), (null, 'w'); insert into b values (1, 10), (1, 20), (3, 30), (4, 40);");
+  assert_eq!(query(&mut e, "select a.id, b.v from a left join b on a.id = b.aid order by a.id, b.v;"), "id,v\n,\n1,10\n1,20\n2,\n3,30");
+  assert_eq!(query(&mut e, "select count(*), count(a.*), count(b.*), count(b.v) from a left outer join b on a.id = b.aid;"),
+             "count(*),count(a.*),count(b.*),count(b.v)\n5,5,3,3");
+  assert_eq!(query(&mut e, "select a.id, count(b.*) from a left join b on a.id = b.aid group by a.id order by a.id;"), "id,count(b.*)\n,0\n1,2\n2,0\n3,1");
+  // a cond on b in `on` only decides the match, but in `where` it is applied after join
+  assert_eq!(query(&mut e, "select count(*), count(b.*) from a left join b on a.id = b.aid and b.v > 15;"), "count(*),count(b.*)\n4,2");
+  assert_eq!(query(&mut e, "select a.id from a left join b on a.id = b.aid where b.v > 15 order by a.id;"), "id\n1\n3");
+  // anti-join: a null test on b in `where` sees the null of an unmatched row, instead of filtering the scan of b
+  assert_eq!(query(&mut e, "select a.name from a left join b on a.id = b.aid where b.aid is null order by a.name;"), "name\n\"w\"\n\"y\"");
+  assert_eq!(query(&mut e, "select count(*) from a left join b on a.id = b.aid where b.aid is not null;"), "count(*)\n3");
+  // a cond on a in `on` doesn't filter a
+  assert_eq!(query(&mut e, "select count(*), count(b.*) from a left join b on a.id = b.aid and a.name = 'x';"), "count(*),count(b.*)\n5,2");
+  err_is!(e, "select count(c.*) from a left join b on a.id = b.aid;", NoSuchTable("c"));
+}
+
+#[test]
+fn temp_db() {
+  let mut t = TempDb::memory().unwrap();
+  t.exec("create table t (id int, s varchar(5), ok bool); insert into t values (1, 'a', true), (2, null, null);").unwrap();
+  assert_eq!(t.query("select * from t order by id desc;").unwrap(),
+             vec![vec![LitExt::Number(2.0), LitExt::Null, LitExt::Null], vec![LitExt::Number(1.0), LitExt::Str("a".into()), LitExt::Bool(true)]]);
+  assert_eq!(t.query("create table u (v int);").unwrap(), Vec::<Vec<LitExt>>::new());
+  match t.exec("insert into t values (3, 'b', true), (4, 'toolong', true);") {
+    Err(ModifyError(1, ColLitMismatch { .. })) => {}
+    r => panic!("expect ColLitMismatch, found {:?}", r),
+  }
+}
+
+#[test]
+fn flush() {
+  let mut e = Eval::default();
+  ok!(e, "create database flush_test; use flush_test; create table t (id int, s varchar(300)); insert into t values (1, 'a'), (2, null);");
+  e.db().unwrap().flush().unwrap();
+  ok!(e, "update t set s = 'b' where id = 2;");
+  // flushing again (or with nothing changed) is fine
+  e.db().unwrap().flush().unwrap();
+  e.db().unwrap().flush().unwrap();
+  drop(e);
+  let mut e = Eval::default();
+  e.open("flush_test").unwrap();
+  assert_eq!(query(&mut e, "select * from t;"), "id,s\n1,\"a\"\n2,\"b\"");
+  ok!(e, "drop database flush_test;");
+  db().db().unwrap().flush().unwrap();
+}
+
+#[test]
+fn temp_db_file() {
+  let path = "temp_db_test";
+  {
+    let mut t = TempDb::file(path).unwrap();
+    t.exec("create table t (v int); insert into t values (1);").unwrap();
+    assert_eq!(t.query("select v from t;").unwrap(), vec![vec![LitExt::Number(1.0)]]);
+    assert!(Path::new(path).exists());
+  }
+  assert!(!Path::new(path).exists());
+}
+
+#[test]
+fn lit_ext_try_from() {
+  use std::convert::TryInto;
+  let mut t = TempDb::memory().unwrap();
+  t.exec("create table t (id int, name varchar(5), ok bool, score float, d date); insert into t values (1, 'a', true, 1.5, '2020-01-02'), (2, null, null, null, null);").unwrap();
+  let rows = t.query("select * from t;").unwrap();
+  let (id, name, ok, score): (i32, &str, bool, f64) = ((&rows[0][0]).try_into().unwrap(), (&rows[0][1]).try_into().unwrap(), (&rows[0][2]).try_into().unwrap(), (&rows[0][3]).try_into().unwrap());
+  assert_eq!((id, name, ok, score), (1, "a", true, 1.5));
+  assert_eq!((&rows[0][4]).try_into().ok(), Some(parse_date("2020-01-02").unwrap()));
+  // null is None for Option, and the value is Some
+  let (name, score): (Option<&str>, Option<f64>) = ((&rows[1][1]).try_into().unwrap(), (&rows[1][3]).try_into().unwrap());
+  assert_eq!((name, score), (None, None));
+  assert_eq!((&rows[1][0]).try_into().map(|x: Option<i32>| x).unwrap(), Some(2));
+  // null, another type, or a number that is not an i32 is an error
+  match (&rows[1][1]).try_into().map(|x: &str| x) { Err(LitConvert { lit: LitExt::Null, to: "str" }) => {} r => panic!("expect LitConvert, found {:?}", r) }
+  match (&rows[0][1]).try_into().map(|x: i32| x) { Err(LitConvert { to: "i32", .. }) => {} r => panic!("expect LitConvert, found {:?}", r) }
+  match (&rows[0][3]).try_into().map(|x: i32| x) { Err(LitConvert { lit: LitExt::Number(_), to: "i32" }) => {} r => panic!("expect LitConvert, found {:?}", r) }
+  match (&rows[0][0]).try_into().map(|x: Option<bool>| x) { Err(LitConvert { to: "bool", .. }) => {} r => panic!("expect LitConvert, found {:?}", r) }
+}
+
+#[test]
+fn in_select() {
+  let mut e = db();
+  ok!(e, "create table a (id int, v int); create index id_index on a(id); create table b (aid int, s char(5));");
+  ok!(e, "insert into a values (1, 10), (2, 20), (3, 30), (4, 40); insert into b values (2, 'x'), (4, 'y'), (null, 'z');");
+  assert_eq!(query(&mut e, "delete from a where id in (select aid from b);"), "2 column(s) affected");
+  assert_eq!(query(&mut e, "select * from a;"), "id,v\n1,10\n3,30");
+  // the index is updated, too
+  assert_eq!(query(&mut e, "select * from a where id = 2;"), "id,v");
+  assert_eq!(query(&mut e, "select id from a where id in (select count(*) from b);"), "id\n3");
+  // the select is done before deleting, even on the same table
+  ok!(e, "insert into a values (10, 1);");
+  assert_eq!(query(&mut e, "delete from a where id in (select v from a where v < 20);"), "2 column(s) affected");
+  assert_eq!(query(&mut e, "select * from a;"), "id,v\n3,30");
+  err_is!(e, "delete from a where id in (select aid, s from b);", InSelectCols(2));
+  err_is!(e, "delete from a where id in (select s from b);", ColMismatch { .. });
+}
+
+#[test]
+fn scalar_subquery() {
+  let mut e = db();
+  ok!(e, "create table emp (name char(5), dept int, salary int); create table dept (id int, name char(5));");
+  ok!(e, "insert into emp values ('a', 1, 10), ('b', 1, 20), ('c', 2, 25), ('d', 2, null); insert into dept values (1, 'x'), (2, 'y');");
+  // the avg is 18.33.., it is not truncated when compared with an int col
+  assert_eq!(query(&mut e, "select name from emp where salary > (select avg(salary) from emp);"), "name\n\"b\"\n\"c\"");
+  assert_eq!(query(&mut e, "select name from emp where salary <= (select avg(salary) from emp);"), "name\n\"a\"");
+  assert_eq!(query(&mut e, "select name from emp where dept = (select id from dept where name = 'y') and salary is not null;"), "name\n\"c\"");
+  assert_eq!(query(&mut e, "update emp set salary = salary + 1 where salary = (select max(salary) from emp);"), "1 column(s) affected");
+  // no row is null, which matches nothing
+  assert_eq!(query(&mut e, "select name from emp where dept = (select id from dept where name = 'z');"), "name");
+  err_is!(e, "select name from emp where dept = (select id from dept);", SubqueryNotScalar { cols: 1, rows: 2 });
+  err_is!(e, "select name from emp where dept = (select id, name from dept where id = 1);", SubqueryNotScalar { cols: 2, rows: 1 });
+  err_is!(e, "select name from emp where dept = (select name from dept where id = 1);", IncompatibleCmp { .. });
+}
+
+#[test]
+fn cte() {
+  let mut e = db();
+  ok!(e, "create table log (id int, ts date, msg varchar(8)); insert into log values (1, '2019-12-01', 'a'), (2, '2020-02-01', 'b'), (3, '2020-03-01', 'c'), (4, '2020-03-05', 'b');");
+  assert_eq!(query(&mut e, "with recent as (select * from log where ts > '2020-01-01') select count(*) from recent;"), "count(*)\n3");
+  // the second cte reads the first, the cols are named by the col list or the header
+  assert_eq!(query(&mut e, "with recent as (select id, msg from log where ts > '2020-01-01'), per_msg (msg, n) as (select msg, count(*) from recent group by msg) select * from per_msg where n > 1;"), "msg,n\n\"b\",2");
+  assert_eq!(query(&mut e, "with r as (select id, msg from log) select id from r where msg = 'b';"), "id\n2\n4");
+  assert_eq!(query(&mut e, "with c as (select count(*) from log), a (m) as (select avg(id) from log) select * from a;"), "m\n2.5");
+  err_is!(e, "with r as (select * from r) select * from r;", RecursiveCte("r"));
+  err_is!(e, "with a as (select * from b), b as (select * from log) select * from a;", NoSuchTable("b")); // only earlier ctes are visible
+  err_is!(e, "with r as (select * from log) select * from r, log;", CteJoin("log"));
+  err_is!(e, "with r (a) as (select id, msg from log) select * from r;", CteCols("r"));
+  err_is!(e, "with r as (select count(*), count(*) from log) select * from r;", CteCols("r"));
+}
+
+#[test]
+fn warn_cross_join() {
+  let mut e = db();
+  ok!(e, "create table a (id int); create table b (aid int); create table c (bid int); insert into a values (1); insert into b values (1);");
+  assert_eq!(outputs(&mut e, "select * from a, b;"), vec!["id,aid\n1,1"]);
+  e.set_warn_cross_join(true);
+  let out = outputs(&mut e, "select * from a, b;");
+  assert!(out[0].starts_with("Warning: table `b`"));
+  assert_eq!(out[1], "id,aid\n1,1");
+  assert_eq!(outputs(&mut e, "select * from a, b where id = aid;"), vec!["id,aid\n1,1"]);
+  assert_eq!(outputs(&mut e, "select * from a left join b on a.id = b.aid;"), vec!["id,aid\n1,1"]);
+  // linked indirectly
+  assert_eq!(outputs(&mut e, "select count(*) from a, b, c where c.bid = aid and a.id = b.aid;"), vec!["count(*)\n0"]);
+  assert!(outputs(&mut e, "select count(*) from a, b, c where a.id = b.aid;")[0].starts_with("Warning: table `c`"));
+}
+
+#[test]
+fn num_lit() {
+  let kinds = |sql: &str| {
+    let mut lexer = Lexer::new(sql.as_bytes());
+    let mut ret = vec![];
+    loop {
+      let t = lexer.next();
+      let kind = match t.ty { TokenKind::_Eof => break ret, TokenKind::IntLit => "int", TokenKind::FloatLit => "float", _ => "other" };
+      assert_eq!(t.ty.class(), TokenClass::Lit);
+      ret.push(format!("{} {}", kind, std::str::from_utf8(t.piece).unwrap()));
+    }
+  };
+  assert_eq!(kinds("1_000 1.5e3 2E-2 -1_5.0_1 7e+1"), vec!["int 1_000", "float 1.5e3", "float 2E-2", "float -1_5.0_1", "float 7e+1"]);
+  let mut e = db();
+  ok!(e, "create table t (i int, f float); insert into t values (1_000, 1.5e3), (-2_0, 2E-2), (3, 7e+1);");
+  assert_eq!(query(&mut e, "select * from t where i >= 1_000;"), "i,f\n1000,1500");
+  assert_eq!(query(&mut e, "select i from t where f = 2e-2;"), "i\n-20");
+  assert_eq!(query(&mut e, "select sum(f) from t where f > 1e1 and i < 1_0;"), "sum(f)\n70");
+  // `_` must be between digits
+  err!(e, "insert into t values (1__0, 0.0);");
+}
+
+#[test]
+fn explain_analyze() {
+  let mut e = db();
+  ok!(e, "create table a (id int); create table b (aid int); insert into a values (1), (2), (3); insert into b values (1), (1), (3), (4);");
+  let alloc = Arena::default();
+  // the lines of the tree without the time
+  let steps = |e: &Eval, sql: &'static str| match syntax::work(sql, &alloc).unwrap().pop() {
+    Some(Stmt::Select(s)) => e.explain_analyze(&s).unwrap().lines().map(|l| l.split(" (").next().unwrap().to_owned()).collect::<Vec<_>>(),
+    _ => unreachable!(),
+  };
+  assert_eq!(steps(&e, "select count(*) from b where aid > 1;"), vec!["select: 1 rows", "  scan `b`: 2 of 4 rows"]);
+  assert_eq!(steps(&e, "select * from a, b where id = aid and id < 3;"),
+    vec!["select: 2 rows", "  join `b`: 2 rows", "    scan `a`: 2 of 3 rows", "    scan `b`: 4 of 4 rows"]);
+  assert_eq!(steps(&e, "select * from a left join b on a.id = b.aid where b.aid is null;"),
+    vec!["select: 1 rows", "  where after join: 1 rows", "    join `b`: 4 rows", "      scan `a`: 3 of 3 rows", "      scan `b`: 4 of 4 rows"]);
+}
+
+#[test]
+fn explain_analyze_json() {
+  let mut e = db();
+  ok!(e, "create table a (id int); create table b (aid int); insert into a values (1), (2), (3); insert into b values (1), (1), (3), (4);");
+  let alloc = Arena::default();
+  let plan = match syntax::work("select * from a, b where id = aid and id < 3;", &alloc).unwrap().pop() {
+    Some(Stmt::Select(s)) => serde_json::from_str::<serde_json::Value>(&e.explain_analyze_json(&s).unwrap()).unwrap(),
+    _ => unreachable!(),
+  };
+  assert_eq!((&plan["node"], &plan["rows"]), (&"select".into(), &2.into()));
+  let join = &plan["input"];
+  assert_eq!((&join["node"], &join["table"], &join["rows"]), (&"join".into(), &"b".into(), &2.into()));
+  let (l, r) = (&join["inputs"][0], &join["inputs"][1]);
+  assert_eq!((&l["node"], &l["table"], &l["rows"], &l["all"]), (&"scan".into(), &"a".into(), &2.into(), &3.into()));
+  assert_eq!((&r["node"], &r["table"], &r["rows"], &r["all"]), (&"scan".into(), &"b".into(), &4.into(), &4.into()));
+  assert!(plan["time_us"].is_u64() && l["seeks"] == 0);
+}
+
+#[test]
+fn column_stats() {
+  let mut e = db();
+  ok!(e, "create table t (id int, f float, s varchar(5), d date); insert into t values (3, 1.5, 'b', '2020-01-02'), (1, null, 'a', null), (null, -0.5, null, '2019-12-31'), (2, null, null, null);");
+  let db = e.db().unwrap();
+  assert_eq!(db.column_stats("t", "id").unwrap(), ColStats { rows: 4, nulls: 1, distinct: 3, min: LitExt::Number(1.0), max: LitExt::Number(3.0), avg: Some(2.0) });
+  assert_eq!(db.column_stats("t", "f").unwrap(), ColStats { rows: 4, nulls: 2, distinct: 2, min: LitExt::Number(-0.5), max: LitExt::Number(1.5), avg: Some(0.5) });
+  assert_eq!(db.column_stats("t", "s").unwrap(), ColStats { rows: 4, nulls: 2, distinct: 2, min: LitExt::Str("a".into()), max: LitExt::Str("b".into()), avg: None });
+  let d = db.column_stats("t", "d").unwrap();
+  assert_eq!((d.nulls, d.min.lit().to_string(), d.max.lit().to_string()), (2, "2019-12-31".to_owned(), "2020-01-02".to_owned()));
+  // the same as the aggregates
+  assert_eq!(query(&mut e, "select count(id), min(id), max(id), avg(id) from t;"), "count(id),min(id),max(id),avg(id)\n3,1,3,2");
+  ok!(e, "delete from t;");
+  assert_eq!(e.db().unwrap().column_stats("t", "id").unwrap(), ColStats { rows: 0, nulls: 0, distinct: 0, min: LitExt::Null, max: LitExt::Null, avg: None });
+  match e.db().unwrap().column_stats("t", "x") { Err(NoSuchCol("x")) => {} r => panic!("expect NoSuchCol, found {:?}", r) }
+}
+
+#[test]
+fn analyze() {
+  let mut e = db();
+  ok!(e, "create table t (id int, s char(3));");
+  let csv = (0..100).fold(String::from("id,s"), |csv, i| csv + &format!("\n{},\"{}\"", i, if i % 10 == 0 { "" } else if i % 2 == 0 { "a" } else { "b" }));
+  assert_eq!(e.import(&csv, "t", &Arena::default()).unwrap(), 100);
+  ok!(e, "update t set s = null where s = '';");
+  let stats = e.db().unwrap().analyze("t").unwrap();
+  assert_eq!(stats, vec![
+    ColStats { rows: 100, nulls: 0, distinct: 100, min: LitExt::Number(0.0), max: LitExt::Number(99.0), avg: Some(49.5) },
+    ColStats { rows: 100, nulls: 10, distinct: 2, min: LitExt::Str("a".into()), max: LitExt::Str("b".into()), avg: None },
+  ]);
+  // the same as computing them one by one, and as the aggregates
+  assert_eq!(stats[1], e.db().unwrap().column_stats("t", "s").unwrap());
+  assert_eq!(query(&mut e, "select count(*), count(distinct id), min(id), max(id), avg(id) from t;"), "count(*),count(distinct id),min(id),max(id),avg(id)\n100,100,0,99,49.5");
+  // a drifted record count is corrected
+  unsafe { e.db().unwrap().get_tp("t").unwrap().1.count = 7; }
+  assert_eq!(query(&mut e, "select nrows from __tables;"), "nrows\n7");
+  e.db().unwrap().analyze("t").unwrap();
+  assert_eq!(query(&mut e, "select nrows from __tables;"), "nrows\n100");
+  match e.db().unwrap().analyze("x") { Err(NoSuchTable("x")) => {} r => panic!("expect NoSuchTable, found {:?}", r) }
+}
+
+#[test]
+fn col_comment() {
+  let mut e = db();
+  ok!(e, "create table t (id int not null default 1 comment 'user id', name varchar(10) comment 'full name', age int, check(id in (1, 2)));");
+  ok!(e, "alter table t add score float comment 'in [0, 100]';");
+  let comments = |e: &mut Eval| e.db().unwrap().columns("t").unwrap().iter().map(|c| c.comment.map(|x| x.to_owned())).collect::<Vec<_>>();
+  assert_eq!(comments(&mut e), vec![Some("user id".to_owned()), Some("full name".to_owned()), None, Some("in [0, 100]".to_owned())]);
+  assert!(e.db().unwrap().show_table("t").unwrap().contains("    - comment: \"user id\""));
+  // the check list and the default value sharing the page still work
+  ok!(e, "insert into t (name) values ('a');");
+  err!(e, "insert into t values (3, 'b', 0, 0.0);");
+  assert_eq!(query(&mut e, "select id, name from t;"), "id,name\n1,\"a\"");
+  err_is!(e, &format!("create table u (id int comment '{}');", "x".repeat(256)), CommentTooLong("id"));
+  err_is!(e, &format!("alter table t add x int comment '{}';", "x".repeat(256)), CommentTooLong("x"));
+}
+
+#[test]
+fn agg_in_where() {
+  let mut e = db();
+  ok!(e, "create table t (id int, v int); insert into t values (1, 1), (1, 2), (2, 3);");
+  let agg_in_where = |e: &mut Eval, sql| match e.exec_all(sql, &Arena::default(), |_| {}, |_| {}) {
+    Err(ModifyError(_, ParserErrors(pe))) => match pe[..] { [ParserError { kind: ParserErrorKind::AggInWhere, .. }] => true, _ => false },
+    _ => false,
+  };
+  assert!(agg_in_where(&mut e, "select id from t where count(*) > 1 group by id;"));
+  assert!(agg_in_where(&mut e, "select id from t where v = 1 and sum(t.v) >= 3 group by id;"));
+  assert!(agg_in_where(&mut e, "delete from t where max(v) = 3;"));
+  assert!(agg_in_where(&mut e, "select count(*) filter (where min(v) < 2) from t;"));
+  assert_eq!(query(&mut e, "select count(*) from t;"), "count(*)\n3"); // nothing is deleted
+}
+
+#[test]
+fn like_col() {
+  let mut e = db();
+  ok!(e, "create table t (name varchar(20), p char(10)); insert into t values ('apple', 'a%'), ('banana', '%an_na'), ('cherry', 'x%'), ('date', null);");
+  assert_eq!(query(&mut e, "select name from t where name like p;"), "name\n\"apple\"\n\"banana\"");
+  // a rules table, the pattern comes from the other table
+  ok!(e, "create table r (id int, rule varchar(10)); insert into r values (1, 'a%'), (2, '%e%'), (3, '%');");
+  assert_eq!(query(&mut e, "select id, name from t, r where name like rule and id < 3;"), "id,name\n1,\"apple\"\n2,\"apple\"\n2,\"cherry\"\n2,\"date\"");
+  assert_eq!(query(&mut e, "select count(*) from r, t where t.name like r.rule;"), "count(*)\n8");
+  assert_eq!(query(&mut e, "select id, name from r left join t on t.name like r.rule and t.name like '%an%';"), "id,name\n1,\n2,\n3,\"banana\"");
+  ok!(e, "delete from t where name like p;");
+  assert_eq!(query(&mut e, "select count(*) from t;"), "count(*)\n2");
+  err_is!(e, "select * from r, t where id like name;", InvalidLikeTy(_));
+}
+
+#[test]
+fn insert_rows() {
+  let mut e = db();
+  ok!(e, "create table t (id int, v int default 7, s char(4));");
+  // all rows omit the same trailing cols, and a trailing comma is allowed
+  ok!(e, "insert into t values (1, 1), (2, 2),;");
+  ok!(e, "insert into t (s, id) values ('a', 3), ('b', 4),;");
+  assert_eq!(query(&mut e, "select * from t;"), "id,v,s\n1,1,\n2,2,\n3,7,\"a\"\n4,7,\"b\"");
+  // ragged or too long rows are rejected before inserting any row
+  err_is!(e, "insert into t values (5, 5), (6), (7, 7);", InsertRowLen { row: 2, expect: 2, actual: 1 });
+  err_is!(e, "insert into t values (5), (6, 6, 'c', 0);", InsertRowLen { row: 2, expect: 1, actual: 4 });
+  err_is!(e, "insert into t (id) values (5, 5);", InsertRowLen { row: 1, expect: 1, actual: 2 });
+  err!(e, "insert into t values (5),, (6);");
+  err!(e, "insert into t values ,;");
+  assert_eq!(query(&mut e, "select count(*) from t;"), "count(*)\n4");
+}
+
+#[test]
+fn upsert() {
+  let mut e = db();
+  ok!(e, "create table kv (k int, v varchar(8), hits int, primary key(k), check (hits >= 0));");
+  ok!(e, "insert into kv values (1, 'a', 1), (2, 'b', 1);");
+  // the conflicting row is skipped, the others are inserted
+  assert_eq!(query(&mut e, "insert into kv values (1, 'x', 5), (3, 'c', 1) on conflict (k) do nothing;"), "1 column(s) affected");
+  assert_eq!(query(&mut e, "select * from kv;"), "k,v,hits\n1,\"a\",1\n2,\"b\",1\n3,\"c\",1");
+  // `excluded` is the row being inserted, other cols are those of the existing record
+  assert_eq!(query(&mut e, "insert into kv values (2, 'y', 3), (4, 'd', 1) on conflict (k) do update set v = excluded.v, hits = hits + excluded.hits;"), "2 column(s) affected");
+  assert_eq!(query(&mut e, "select * from kv where k >= 2;"), "k,v,hits\n2,\"y\",4\n3,\"c\",1\n4,\"d\",1");
+  // a later row can conflict with an earlier one of the same insert
+  ok!(e, "insert into kv (k, hits) values (5, 1), (5, 1) on conflict (k) do update set hits = hits + 1;");
+  assert_eq!(query(&mut e, "select v, hits from kv where k = 5;"), "v,hits\n,2");
+  // the update still runs the checks
+  err_is!(e, "insert into kv values (1, 'z', 0) on conflict (k) do update set hits = hits - 2;", CheckFailed { .. });
+  err_is!(e, "insert into kv values (1, 'z', 0) on conflict (v) do nothing;", ConflictNotUnique("v"));
+  err_is!(e, "insert into kv values (1, 'z', 0) on conflict (k) do update set v = other.v;", NoSuchTable("other"));
+  err_is!(e, "insert into kv values (1, 'z', 0);", PutDupOnUnique { col: "k", .. });
+  assert_eq!(query(&mut e, "select count(*) from kv;"), "count(*)\n5");
+}
+
+#[test]
+fn max_rows() {
+  let mut e = db();
+  let vals = (0..100).map(|i| format!("({})", i)).collect::<Vec<_>>().join(", ");
+  ok!(e, &format!("create table a (x int); create table b (y int); create table c (z int); insert into a values {0}; insert into b values {0}; insert into c values {0};", vals));
+  e.set_max_rows(1000);
+  // 100 * 100 * 100 rows, it stops as soon as the limit is exceeded
+  err_is!(e, "select count(*) from a, b, c;", ResultTooLarge(1000));
+  err_is!(e, "select count(*) from a, b;", ResultTooLarge(1000));
+  // `limit` doesn't help, because it applies to the final result
+  err_is!(e, "select * from a, b limit 1;", ResultTooLarge(1000));
+  assert_eq!(query(&mut e, "select count(*) from a, b where x = y;"), "count(*)\n100");
+  assert_eq!(query(&mut e, "select count(*) from a, b where x < 10;"), "count(*)\n1000");
+  e.set_max_rows(0);
+  assert_eq!(query(&mut e, "select count(*) from a, b;"), "count(*)\n10000");
+}
+
+#[test]
+fn tbl_all() {
+  let mut e = db();
+  ok!(e, "create table a (id int, name char(4)); create table b (aid int, v float); insert into a values (1, 'x'), (2, 'y'); insert into b values (1, 0.5);");
+  assert_eq!(query(&mut e, "select a.*, b.v from a, b where id = aid;"), "id,name,v\n1,\"x\",0.5");
+  assert_eq!(query(&mut e, "select v, a.*, aid from a, b where id = aid;"), "v,id,name,aid\n0.5,1,\"x\",1");
+  assert_eq!(query(&mut e, "select b.*, a.* from a left join b on a.id = b.aid;"), "aid,v,id,name\n1,0.5,1,\"x\"\n,,2,\"y\"");
+  assert_eq!(query(&mut e, "select a.* from a where id > 1;"), "id,name\n2,\"y\"");
+  assert_eq!(query(&mut e, "select a.*, count(*) from a group by id, name;"), "id,name,count(*)\n1,\"x\",1\n2,\"y\",1");
+  err_is!(e, "select c.* from a;", NoSuchTable("c"));
+  err_is!(e, "select a.*, count(*) from a group by id;", MixedSelect);
+}
+
+#[test]
+fn foreign_cycle() {
+  let mut e = db();
+  ok!(e, "create table a (id int, bid int not null, primary key(id));");
+  ok!(e, "create table b (id int, aid int not null, primary key(id), foreign key (aid) references a(id));");
+  // b -> a -> b, neither table could get its first record
+  err_is!(e, "alter table a add foreign key (bid) references b(id);", ForeignKeyCycle("bid"));
+  // a longer cycle, and a self cycle
+  ok!(e, "create table c (id int, bid int not null, primary key(id), foreign key (bid) references b(id));");
+  err_is!(e, "alter table a add foreign key (bid) references c(id);", ForeignKeyCycle("bid"));
+  err_is!(e, "alter table a add foreign key (bid) references a(id);", ForeignKeyCycle("bid"));
+  // a nullable link breaks the cycle
+  ok!(e, "alter table a add nid int; alter table a add foreign key (nid) references c(id);");
+  ok!(e, "insert into a values (1, 0, null); insert into b values (1, 1); insert into c values (1, 1); update a set nid = 1;");
+  // making the nullable link not null by primary key closes the cycle again
+  err_is!(e, "alter table a add primary key (nid);", ForeignKeyCycle("nid"));
+}
+
+#[test]
+fn write_csv() {
+  let mut e = db();
+  ok!(e, "create table t (id int, s varchar(10)); insert into t values (1, 'a\"b'), (2, null), (3, 'c,d');");
+  let alloc = Arena::default();
+  let s = match syntax::work("select * from t;", &alloc).unwrap().pop() { Some(Stmt::Select(s)) => s, _ => unreachable!() };
+  let r = e.select(&s).unwrap();
+  let path = "write_csv_test.csv";
+  for &header in &[true, false] {
+    r.write_csv(fs::File::create(path).unwrap(), header).unwrap();
+    assert_eq!(fs::read_to_string(path).unwrap(), r.csv(header));
+  }
+  assert_eq!(r.csv(true), "id,s\n1,\"a\"\"b\"\n2,\n3,\"c,d\"");
+  fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn into_outfile() {
+  let mut e = db();
+  ok!(e, "create table t (id int, s varchar(10)); insert into t values (1, 'a\"b'), (2, null), (3, 'c,d'); create table u (id int, s varchar(10));");
+  let path = "into_outfile_test.csv";
+  assert_eq!(query(&mut e, "select * from t where id > 1 into outfile 'into_outfile_test.csv';"), "2 column(s) affected");
+  assert_eq!(fs::read_to_string(path).unwrap(), "id,s\n2,\n3,\"c,d\"");
+  // an existing file is overwritten, and the file can be imported back
+  ok!(e, "select * from t order by id desc into outfile 'into_outfile_test.csv';");
+  assert_eq!(e.import(&fs::read_to_string(path).unwrap(), "u", &Arena::default()).unwrap(), 3);
+  assert_eq!(query(&mut e, "select * from u;"), "id,s\n3,\"c,d\"\n2,\n1,\"a\"\"b\"");
+  e.set_header(false);
+  ok!(e, "select count(*) from t into outfile 'into_outfile_test.csv';");
+  assert_eq!(fs::read_to_string(path).unwrap(), "3");
+  fs::remove_file(path).unwrap();
+  err!(e, "select * from x into outfile 'into_outfile_test.csv'; -- error, no such table");
+  assert!(!Path::new(path).exists()); // the file is created only after the select succeeds
+  err!(e, "select * from t into outfile 'no_such_dir/x.csv';");
+}
+
+#[test]
+fn write_jsonl() {
+  let mut e = db();
+  ok!(e, "create table t (id int, s varchar(10), f float, d date, b bool); insert into t values (1, 'a\"b\\', 1.5, '2020-01-02', true), (2, null, null, null, false), (3, 'x\ny', -2, null, null);");
+  let alloc = Arena::default();
+  let s = match syntax::work("select id, s, f, d, b, count(*) from t group by id, s, f, d, b;", &alloc).unwrap().pop() { Some(Stmt::Select(s)) => s, _ => unreachable!() };
+  let r = e.select(&s).unwrap();
+  let mut out = Vec::new();
+  r.write_jsonl(&mut out).unwrap();
+  let out = String::from_utf8(out).unwrap();
+  assert_eq!(out.lines().count(), r.row_count());
+  assert_eq!(out, r#"{"id":1,"s":"a\"b\\","f":1.5,"d":"2020-01-02","b":true,"count(*)":1}
+{"id":2,"s":null,"f":null,"d":null,"b":false,"count(*)":1}
+{"id":3,"s":"x\ny","f":-2,"d":null,"b":null,"count(*)":1}"#);
+}
+
+#[test]
+fn collation() {
+  let mut e = db();
+  ok!(e, "create table t (id int, s char(10), v varchar(10)); create index t_s on t(s); create table u (name char(10));");
+  ok!(e, "insert into t values (1, 'Apple', 'APPLE'), (2, 'apple', 'x'), (3, 'Banana', null); insert into u values ('APPLE');");
+  assert_eq!(query(&mut e, "select id from t where s = 'apple';"), "id\n2");
+  assert_eq!(query(&mut e, "select id from t order by s;"), "id\n1\n3\n2");
+  e.set_collation(Collation::NoCase);
+  // the index on `s` is in binary order, so it is not used
+  assert_eq!(query(&mut e, "select id from t where s = 'apple';"), "id\n1\n2");
+  assert_eq!(query(&mut e, "select id from t where s > 'APPLE';"), "id\n3");
+  assert_eq!(query(&mut e, "select id from t where s = v;"), "id\n1");
+  assert_eq!(query(&mut e, "select id from t where s like 'a%';"), "id\n1\n2");
+  assert_eq!(query(&mut e, "select id from t where s in (select name from u);"), "id\n1\n2");
+  assert_eq!(query(&mut e, "select id from t, u where s = name;"), "id\n1\n2");
+  assert_eq!(query(&mut e, "select id from t order by s limit 3;"), "id\n1\n2\n3");
+  e.set_collation(Collation::Binary);
+  assert_eq!(query(&mut e, "select id from t where s like 'a%';"), "id\n2");
+  assert_eq!(query(&mut e, "select id from t, u where s = name;"), "id");
+  assert_eq!(query(&mut e, "select id from t where s = v;"), "id");
+}
+
+#[test]
+fn char_varchar_join() {
+  let mut e = db();
+  ok!(e, "create table a (k char(8)); create table b (k varchar(3));");
+  ok!(e, "insert into a values ('ab'), ('abc'), ('x'); insert into b values ('abc'), ('ab'), ('AB');");
+  // a char shorter than its width equals the varchar with the same content, on either side of the join
+  assert_eq!(query(&mut e, "select count(*) from a, b where a.k = b.k;"), "count(*)\n2");
+  assert_eq!(query(&mut e, "select count(*) from b, a where b.k = a.k;"), "count(*)\n2");
+  assert_eq!(query(&mut e, "select count(*) from a, b where a.k < b.k;"), "count(*)\n1");
+  assert_eq!(query(&mut e, "select count(*) from a left join b on a.k = b.k;"), "count(*)\n3");
+  e.set_collation(Collation::NoCase);
+  assert_eq!(query(&mut e, "select count(*) from a, b where a.k = b.k;"), "count(*)\n3");
+}
+
+#[test]
+fn count_only() {
+  let mut e = db();
+  ok!(e, "create table t (id int, v int); create index t_id on t(id);");
+  let values = (0..1000).map(|i| format!("({}, {})", i, i % 10)).collect::<Vec<_>>().join(", ");
+  ok!(e, &format!("insert into t values {}, (null, null);", values));
+  // these are counted without collecting the records, the results are the same as the normal path
+  assert_eq!(query(&mut e, "select count(*) from t;"), "count(*)\n1001");
+  assert_eq!(query(&mut e, "select count(*) from t where id < 100 and v = 3;"), "count(*)\n10");
+  assert_eq!(query(&mut e, "select count(t.*) from t where v is null;"), "count(t.*)\n1");
+  assert_eq!(query(&mut e, "select count(*) from t where id > 2000;"), "count(*)\n0");
+  assert_eq!(query(&mut e, "select count(*) from t limit 0;"), "count(*)");
+  assert_eq!(query(&mut e, "select count(*) filter (where v = 1) from t where id >= 500;"), "count(*)\n50");
+  err_is!(e, "select count(*) from t where x = 1;", NoSuchCol("x"));
+  err_is!(e, "select count(*) from t where u.id = 1;", NoSuchTable("u"));
+  // the count of a join is taken over all joined rows, it is a Number (not truncated to the records of one table)
+  ok!(e, "create table u (w int);");
+  ok!(e, &format!("insert into u values {};", (0..200).map(|i| format!("({})", i)).collect::<Vec<_>>().join(", ")));
+  assert_eq!(query(&mut e, "select count(*), count(v) from t, u;"), "count(*),count(v)\n200200,200000");
+}
+
+#[test]
+fn in_list() {
+  let mut e = db();
+  ok!(e, "create table t (id int, v int, d date); create index t_id on t(id);");
+  let values = (0..1000).map(|i| format!("({}, {}, '2020-01-01')", i, i % 10)).collect::<Vec<_>>().join(", ");
+  ok!(e, &format!("insert into t values {}, (null, null, null);", values));
+  // the indexed col seeks the index for each value, the other col is scanned, duplicates and nulls match once or never
+  assert_eq!(query(&mut e, "select id from t where id in (7, 3, 7, null, 5000);"), "id\n3\n7");
+  assert_eq!(query(&mut e, "select count(*) from t where v in (1, 2) and id in (1, 2, 3, 11);"), "count(*)\n3");
+  assert_eq!(query(&mut e, "select count(*) from t where d in ('2020-01-01', date '2000-01-01');"), "count(*)\n1000");
+  err_is!(e, "select id from t where id in (1, 'a');", ColLitMismatch { .. });
+  let alloc = Arena::default();
+  let steps = |e: &Eval, sql: &'static str| match syntax::work(sql, &alloc).unwrap().pop() {
+    Some(Stmt::Select(s)) => e.explain_analyze(&s).unwrap().lines().map(|l| l.split(" (").next().unwrap().to_owned()).collect::<Vec<_>>(),
+    _ => unreachable!(),
+  };
+  assert_eq!(steps(&e, "select * from t where id in (1, 2);"), vec!["select: 2 rows", "  scan `t`: 2 of 1001 rows, 2 index seeks"]);
+  assert_eq!(steps(&e, "select * from t where v in (1, 2);"), vec!["select: 200 rows", "  scan `t`: 200 of 1001 rows"]);
+  // a long list is scanned
+  let many = format!("select * from t where id in ({});", (0..100).map(|i| i.to_string()).collect::<Vec<_>>().join(", "));
+  match syntax::work(&many, &alloc).unwrap().pop() {
+    Some(Stmt::Select(s)) => assert!(e.explain_analyze(&s).unwrap().lines().nth(1).unwrap().starts_with("  scan `t`: 100 of 1001 rows (")),
+    _ => unreachable!(),
+  }
+  ok!(e, "delete from t where id in (1, 2);");
+  assert_eq!(query(&mut e, "select count(*) from t where id in (1, 2, 3);"), "count(*)\n1");
+}
+
+#[test]
+fn empty_and_not_in_list() {
+  let mut e = db();
+  ok!(e, "create table t (id int, s char(5)); create index t_id on t(id); insert into t values (1, 'a'), (2, 'b'), (3, null), (null, 'd');");
+  assert_eq!(query(&mut e, "select count(*) from t where id in ();"), "count(*)\n0");
+  assert_eq!(query(&mut e, "select count(*) from t where s in ();"), "count(*)\n0");
+  // `not in ()` is true even for null, there is nothing to compare with
+  assert_eq!(query(&mut e, "select count(*) from t where id not in ();"), "count(*)\n4");
+  assert_eq!(query(&mut e, "select id from t where id NOT IN (1, 5);"), "id\n2\n3");
+  assert_eq!(query(&mut e, "select id from t where s not in ('a') and id is not null;"), "id\n2");
+  // `id not in (1, null)` is `id <> 1 and id <> null`, which is never true
+  assert_eq!(query(&mut e, "select count(*) from t where id not in (1, null);"), "count(*)\n0");
+  assert_eq!(query(&mut e, "select id from t where id in (1, null);"), "id\n1");
+  assert_eq!(query(&mut e, "delete from t where id not in (2, 3);"), "1 column(s) affected");
+  assert_eq!(query(&mut e, "select id from t;"), "id\n2\n3\n");
+  err_is!(e, "select id from t where id not in ('a');", ColLitMismatch { .. });
+  err_is!(e, "select id from t where id not in (1, 'a', null);", ColLitMismatch { .. });
+  // a row check with null in the list is always unknown, so it doesn't reject anything
+  ok!(e, "create table u (id int, check (id not in (1, null))); insert into u values (1), (2);");
+  ok!(e, "create table w (id int, check (id not in (1, 2))); insert into w values (3), (null);");
+  err_is!(e, "insert into w values (2);", CheckFailed { .. });
+  ok!(e, "create table x (id int, check (id in ())); insert into x values (null);");
+  err_is!(e, "insert into x values (1);", CheckFailed { .. });
+}
+
+#[test]
+fn qualified_header() {
+  let mut e = db();
+  ok!(e, "create table a (id int, name char(5)); create table b (id int, aid int); insert into a values (1, 'x'); insert into b values (2, 1);");
+  assert_eq!(query(&mut e, "select id, name from a;"), "id,name\n1,\"x\"");
+  // only `id` is in both tables, selecting the same col twice is not ambiguous
+  assert_eq!(query(&mut e, "select * from a, b where a.id = aid;"), "a.id,name,b.id,aid\n1,\"x\",2,1");
+  assert_eq!(query(&mut e, "select name, aid, a.id, a.id from a, b;"), "name,aid,id,id\n\"x\",1,1,1");
+  assert_eq!(query(&mut e, "select count(a.id), max(b.id) from a, b;"), "count(a.id),max(b.id)\n1,2");
+  assert_eq!(query(&mut e, "select b.id, a.id from a left join b on a.id = b.aid;"), "b.id,a.id\n2,1");
+}
+
+#[test]
+fn qualified_agg() {
+  let mut e = db();
+  ok!(e, "create table a (id int, score int); create table b (id int, aid int, score int);");
+  ok!(e, "insert into a values (1, 10), (2, 20), (3, null); insert into b values (1, 1, 100), (2, 1, 300), (null, 3, 5);");
+  // each agg reads the col of its own table, over the joined rows
+  assert_eq!(query(&mut e, "select avg(a.score), avg(b.score), count(a.score), count(b.score) from a, b where a.id = b.aid;"),
+             "avg(a.score),avg(b.score),count(a.score),count(b.score)\n10,135,2,3");
+  err_is!(e, "select avg(score) from a, b;", AmbiguousCol("score"));
+  // the row of `a` without a match has null for all cols of `b`, so only the non-null `b.id` are counted
+  assert_eq!(query(&mut e, "select count(b.id), count(b.*), count(a.id), count(*) from a left join b on a.id = b.aid;"),
+             "count(b.id),count(b.*),count(a.id),count(*)\n2,3,4,4");
+  assert_eq!(query(&mut e, "select a.id, sum(b.score), max(a.score) from a left join b on a.id = b.aid group by a.id order by a.id;"),
+             "id,sum(b.score),max(a.score)\n1,400,10\n2,,20\n3,5,");
+}
+
+#[test]
+fn row_check() {
+  let mut e = db();
+  ok!(e, "create table t (id int, start date, end date, n int, check (start <= end and n > 0), check (id in (1, 2, 3)));");
+  ok!(e, "insert into t values (1, '2020-01-01', '2020-02-01', 1);");
+  match e.exec_all("insert into t values (2, '2020-03-01', '2020-02-01', 1);", &Arena::default(), |_| {}, |_| {}) {
+    Err(ModifyError(0, CheckFailed { check, vals })) => {
+      assert_eq!(check, "start <= end");
+      assert_eq!(vals, vec![("start", LitExt::Date(parse_date("2020-03-01").unwrap())), ("end", LitExt::Date(parse_date("2020-02-01").unwrap()))]);
+    }
+    r => panic!("unexpected result {:?}", r),
+  }
+  err_is!(e, "insert into t values (2, '2020-01-01', '2020-02-01', 0);", CheckFailed { .. });
+  err_is!(e, "update t set end = '2019-01-01';", CheckFailed { .. });
+  // the old form is still the check of one col
+  err_is!(e, "insert into t values (4, '2020-01-01', '2020-02-01', 1);", PutNotInCheck { .. });
+  // a null col makes the cond unknown, which passes
+  ok!(e, "insert into t values (2, null, '2020-02-01', null);");
+  assert_eq!(query(&mut e, "select count(*) from t;"), "count(*)\n2");
+  err_is!(e, "alter table t drop end;", ColInCheck("end"));
+  err_is!(e, "alter table t rename column n to m;", ColInCheck("n"));
+  err_is!(e, "create table u (a int, b char(3), check (a < b));", ColMismatch { .. });
+  err_is!(e, "create table u (a int, b int, check (a < c));", NoSuchCol("c"));
+  err_is!(e, "select * from u;", NoSuchTable("u"));
+}
+
+#[test]
+fn select_const() {
+  let mut e = db();
+  ok!(e, "create table t (id int, name char(5)); insert into t values (1, 'a'), (2, 'b');");
+  assert_eq!(query(&mut e, "select 'label', name, 1.5 from t;"), "'label',name,1.5\n\"label\",\"a\",1.5\n\"label\",\"b\",1.5");
+  // a constant can be selected with aggs, and ordering by it changes nothing
+  assert_eq!(query(&mut e, "select 'n', count(*) from t;"), "'n',count(*)\n\"n\",2");
+  assert_eq!(query(&mut e, "select id, 0 from t order by 2, 1 desc;"), "id,0\n2,0\n1,0");
+  assert_eq!(query(&mut e, "select id from t where id in (select 2 from t);"), "id\n2");
+  // the result owns the constant strings
+  let r;
+  {
+    let (sql, alloc) = (String::from("select 'x', id from t;"), Arena::default());
+    r = match syntax::work(&sql, &alloc).unwrap().pop() { Some(Stmt::Select(s)) => e.select(&s).unwrap(), _ => unreachable!() };
+  }
+  assert_eq!(r.csv(false), "\"x\",1\n\"x\",2");
+}
+
+#[test]
+fn serialize_ast() {
+  let alloc = Arena::default();
+  let stmts = syntax::work("update t set v = v * 2 where id = 1; delete from t where name like 'a%' and d is null;", &alloc).unwrap();
+  assert_eq!(serde_json::to_string(&stmts).unwrap(), concat!(
+    r#"[{"Update":{"table":"t","sets":[["v",{"Bin":["Mul",[{"Atom":{"ColRef":{"table":null,"col":"v"}}},{"Atom":{"Lit":2.0}}]]}]],"#,
+    r#""where_":[{"Cmp":["Eq",{"table":null,"col":"id"},{"Lit":1.0}]}]}},"#,
+    r#"{"Delete":{"table":"t","where_":[{"Like":[{"table":null,"col":"name"},"a%"]},{"Null":[{"table":null,"col":"d"},true]}]}}]"#));
+}